@@ -208,8 +208,9 @@ const Staking = async () => {
   const anchorProvider = await getAnchorProvider();
   const program = new anchor.Program(tokenlockIdl, programId, anchorProvider);
   const stakeAmount = Number(document.getElementById('stake_amount').value);
+  const lockTier = Number(document.getElementById('lock_tier').value);
 
-  const res = await lib.staking(program, connection, stakingDataAccount, stakeAmount, provider);
+  const res = await lib.staking(program, connection, stakingDataAccount, stakeAmount, provider, lockTier);
   if(res[0] == null)
   {
     alert(res[1]);
@@ -276,16 +277,19 @@ const Funding = async () => {
 }
 
 
-const SetAPYMax = async () => {
+const ChangeSetting = async () => {
   const stakingDataAccount = new web3.PublicKey(document.getElementById('staking_address').value);
   const connection = new web3.Connection(URL, 'confirmed');
   const provider = await getProvider();
   const anchor_provider = await getAnchorProvider();
   const program = new anchor.Program(tokenlockIdl, programId, anchor_provider);
-  const apyMax = Number(document.getElementById('apy_max').value);
+  const apyMax = Number(document.getElementById('setting_apy_max').value);
+  const minStakePeriod = Number(document.getElementById('setting_min_stake_period').value);
+  const vestingDuration = Number(document.getElementById('vesting_duration').value);
+  const vestingCliff = Number(document.getElementById('vesting_cliff').value);
 
-  const res = await lib.setMaxApy(program, connection, 
-    stakingDataAccount, apyMax, provider);
+  const res = await lib.changeSetting(program, connection, 
+    stakingDataAccount, apyMax, minStakePeriod, vestingDuration, vestingCliff, provider);
   if(res[0] == null)
   {
     alert(res[1]);
@@ -323,14 +327,11 @@ const statRefresh = async () => {
 
 
 const myStat = async () => {
-  const stakingDataAccount = new web3.PublicKey(document.getElementById('staking_address').value);
   const state = new web3.PublicKey(document.getElementById('state_address').value);  
-  const connection = new web3.Connection(URL, 'confirmed');
   const anchor_provider = await getAnchorProvider();
   const program = new anchor.Program(tokenlockIdl, programId, anchor_provider);
   const stateData = await program.account.stakingState.fetch(state);
-  const stakingData = await program.account.stakingData.fetch(stakingDataAccount);  
-  const gainedReward = utils0.getGainedReward(stakingData, stateData);
+  const gainedReward = utils0.getGainedReward(stateData);
 
   document.getElementById('my_staked').innerHTML = stateData.totalStaked.toNumber();
   document.getElementById('my_rewarded').innerHTML = stateData.totalRewarded.toNumber();
@@ -383,8 +384,8 @@ const myStat = async () => {
   const fund_btn = document.getElementById('fund_btn');  
   fund_btn.addEventListener('click', Funding);  
 
-  const set_max_apy_btn = document.getElementById('set_max_apy_btn');  
-  set_max_apy_btn.addEventListener('click', SetAPYMax);    
+  const change_setting_btn = document.getElementById('change_setting_btn');  
+  change_setting_btn.addEventListener('click', ChangeSetting);    
 
   const claim_btn = document.getElementById('claim_btn');  
   claim_btn.addEventListener('click', ClaimReward);    
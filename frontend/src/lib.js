@@ -28,6 +28,7 @@ async function initialize(
     minTimeframeInSecond,
     minStakePeriod,
     signer,
+    rewardMint = mintAddress,
 ) {
     const stakingDataAccount = await utils.getStakingDataAccount(signer.publicKey, mintAddress, program.programId);
     const escrowAccount = await utils.getEscrowAccount(stakingDataAccount, program.programId);
@@ -44,6 +45,7 @@ async function initialize(
                 rewarderAccount: rewarderAccount,
                 authority: signer.publicKey,
                 mintAddress: mintAddress,
+                rewardMint: rewardMint,
                 tokenProgram: TOKEN_PROGRAM_ID,
                 systemProgram: anchor.web3.SystemProgram.programId,
                 rent: anchor.web3.SYSVAR_RENT_PUBKEY,
@@ -94,6 +96,7 @@ async function staking(
     stakingDataAccount,
     amount,
     signer,
+    lockTier = 0,
 ) {
     const stakingData = await utils.getStakingData(program, stakingDataAccount);
     if(stakingData == null)
@@ -107,17 +110,26 @@ async function staking(
         return "stakingState didn't init";
     }
     const stakerAccount = await utils.getAssociatedTokenAddress(stakingData.mintAddress, signer.publicKey, false);
+    const receiptEnabled = utils.receiptEnabled(stakingData);
+    const receiptAccount = receiptEnabled ? await utils.getOwnerReceiptAccount(stakingData, stakeState) : stakerAccount;
+    const stakingAuthority = await utils.getStakingAuthAccount(stakingDataAccount, program.programId);
+    const rewardStreams = await utils.getRewardStreamAccounts(stakingData, stakingDataAccount, program.programId);
     const inst = program.instruction.staking(
         new anchor.BN(amount),
+        lockTier,
         {
             accounts: {
                 stakingData: stakingDataAccount,
                 stakeStateAccount: stakeStateAccount,
                 escrowAccount: stakingData.escrowAccount,
                 stakerAccount: stakerAccount,
+                receiptMint: receiptEnabled ? stakingData.receiptMint : stakingData.mintAddress,
+                receiptAccount: receiptAccount,
                 authority: signer.publicKey,
+                stakingAuthority: stakingAuthority,
                 tokenProgram: TOKEN_PROGRAM_ID,
             },
+            remainingAccounts: rewardStreams,
         },
     );
     const res = await utils.performInstructions(connection, signer, [inst]);
@@ -147,8 +159,12 @@ async function unstaking(
         return "stakingState didn't init";
     }
     const reclaimer = await utils.getAssociatedTokenAddress(stakingData.mintAddress, signer.publicKey, false);
+    const rewardAccount = await utils.getAssociatedTokenAddress(utils.getRewardMint(stakingData), signer.publicKey, false);
+    const receiptEnabled = utils.receiptEnabled(stakingData);
+    const receiptAccount = receiptEnabled ? await utils.getOwnerReceiptAccount(stakingData, stakeState) : reclaimer;
 
     const stakingAuthority = await utils.getStakingAuthAccount(stakingDataAccount, program.programId);
+    const rewardStreams = await utils.getRewardStreamAccounts(stakingData, stakingDataAccount, program.programId);
     const inst = program.instruction.unstaking(
         new anchor.BN(amount),
         {
@@ -156,11 +172,18 @@ async function unstaking(
                 stakingData: stakingDataAccount,
                 stakeStateAccount: stakeStateAccount,
                 escrowAccount: stakingData.escrowAccount,
+                rewarderAccount: stakingData.rewarderAccount,
                 reclaimer: reclaimer,
+                rewardAccount: rewardAccount,
+                mintAddress: stakingData.mintAddress,
+                treasuryAccount: stakingData.penaltyMode === 1 ? stakingData.penaltyTreasury : stakingData.rewarderAccount,
+                receiptMint: receiptEnabled ? stakingData.receiptMint : stakingData.mintAddress,
+                receiptAccount: receiptAccount,
                 authority: signer.publicKey,
                 stakingAuthority: stakingAuthority,
                 tokenProgram: TOKEN_PROGRAM_ID,
             },
+            remainingAccounts: rewardStreams,
         },
     );   
     const res = await utils.performInstructions(connection, signer, [inst]);
@@ -187,8 +210,11 @@ async function claimReward(
     {
         return "stakingState didn't init";
     }
-    const claimer = await utils.getAssociatedTokenAddress(stakingData.mintAddress, signer.publicKey, false);
+    const claimer = await utils.getAssociatedTokenAddress(utils.getRewardMint(stakingData), signer.publicKey, false);
+    const vestingAccount = stakingData.vestingDuration.toNumber() > 0 ? await utils.getVestingAccount(stakeStateAccount, program.programId) : claimer;
+    const ownerReceiptAccount = utils.receiptEnabled(stakingData) ? await utils.getOwnerReceiptAccount(stakingData, stakeState) : claimer;
     const stakingAuthority = await utils.getStakingAuthAccount(stakingDataAccount, program.programId);
+    const rewardStreams = await utils.getRewardStreamAccounts(stakingData, stakingDataAccount, program.programId);
 
     const inst = program.instruction.claimReward(
         new anchor.BN(amount),
@@ -198,10 +224,13 @@ async function claimReward(
                 stakeStateAccount: stakeStateAccount,
                 rewarderAccount: stakingData.rewarderAccount,
                 claimer: claimer,
+                vestingAccount: vestingAccount,
+                ownerReceiptAccount: ownerReceiptAccount,
                 authority: signer.publicKey,
                 stakingAuthority: stakingAuthority,
                 tokenProgram: TOKEN_PROGRAM_ID,
             },
+            remainingAccounts: rewardStreams,
         },
     );    
     const res = await utils.performInstructions(connection, signer, [inst]);
@@ -224,7 +253,7 @@ async function funding(
         return "stakingData didn't init";
     }
 
-    const funderAccount = await utils.getAssociatedTokenAddress(stakingData.mintAddress, signer.publicKey, false);
+    const funderAccount = await utils.getAssociatedTokenAddress(utils.getRewardMint(stakingData), signer.publicKey, false);
     const inst = program.instruction.funding(
         new anchor.BN(amount),
         new anchor.BN(timeframeInSecond),
@@ -244,11 +273,14 @@ async function funding(
     return [null, formatError(program._idl.errors, res[1])];
 }
 
-async function setMaxApy(
+async function changeSetting(
     program,
     connection,
     stakingDataAccount,
     apyMax,
+    minStakePeriod,
+    vestingDuration,
+    vestingCliff,
     signer,
 ) {
     const stakingData = await utils.getStakingData(program, stakingDataAccount);
@@ -257,8 +289,11 @@ async function setMaxApy(
         return "stakingData didn't init";
     }
 
-    const inst = program.instruction.setMaxApy(
+    const inst = program.instruction.changeSetting(
         apyMax,
+        new anchor.BN(minStakePeriod),
+        new anchor.BN(vestingDuration),
+        new anchor.BN(vestingCliff),
         {
             accounts: {
                 stakingData: stakingDataAccount,
@@ -279,5 +314,5 @@ module.exports = {
     unstaking,
     funding,
     claimReward,    
-    setMaxApy,
+    changeSetting,
 };
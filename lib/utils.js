@@ -164,6 +164,28 @@ async function getStakingState(program, stakingStateAccount)
     }  
 }
 
+async function getRewardStream(program, rewardStreamAccount)
+{
+    try{
+        const accData = await program.account.rewardStream.fetch(rewardStreamAccount);
+        return accData;
+    }catch(e)
+    {
+        return null;
+    }  
+}
+
+async function getDonorRecord(program, donorRecordAccount)
+{
+    try{
+        const accData = await program.account.donorRecord.fetch(donorRecordAccount);
+        return accData;
+    }catch(e)
+    {
+        return null;
+    }  
+}
+
 async function createToken(connection, signer, decimals)
 {
     const mintAccount = anchor.web3.Keypair.generate();
@@ -256,49 +278,274 @@ async function getStakingStateAccount(stakingDataAcc, author, programId){
     return acc;    
 }
 
-function calculateReward(apyMax, poolStaked, poolReward, timeFrameStart, timeFrameEnd, staked, stakeStartTime, minStakePeriod, nowTs)
+async function getRewardStreamAccount(stakingDataAcc, index, programId){
+
+    const [acc] = await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from("staking-stream", "utf-8"), stakingDataAcc.toBuffer(), Buffer.from([index])], programId);
+    return acc;    
+}
+
+async function getFixedTermProductAccount(stakingDataAcc, index, programId){
+
+    const [acc] = await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from("staking-product", "utf-8"), stakingDataAcc.toBuffer(), Buffer.from([index])], programId);
+    return acc;    
+}
+
+async function getFixedTermDepositAccount(productAcc, author, nonce, programId){
+
+    const [acc] = await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from("staking-deposit", "utf-8"), productAcc.toBuffer(), author.toBuffer(), new anchor.BN(nonce).toArrayLike(Buffer, 'le', 8)], programId);
+    return acc;    
+}
+
+async function getShareMintAccount(stakingDataAcc, programId){
+
+    const [acc] = await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from("staking-share-mint", "utf-8"), stakingDataAcc.toBuffer()], programId);
+    return acc;    
+}
+
+async function getReceiptMintAccount(stakingDataAcc, programId){
+
+    const [acc] = await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from("staking-receipt-mint", "utf-8"), stakingDataAcc.toBuffer()], programId);
+    return acc;    
+}
+
+function receiptEnabled(stakingData){
+    return !stakingData.receiptMint.equals(new anchor.web3.PublicKey(0));
+}
+
+// account a position keeps its receipts in, same as StakingState::owner_receipt_account
+async function getOwnerReceiptAccount(stakingData, stakingState){
+    if (!stakingState.receiptAccount.equals(new anchor.web3.PublicKey(0))) {
+        return stakingState.receiptAccount;
+    }
+    return getAssociatedTokenAddress(stakingData.receiptMint, stakingState.onwerAddress);
+}
+
+// pools created before the reward mint was added pay rewards in the staking mint
+function getRewardMint(stakingData){
+    if (stakingData.rewardMint.equals(new anchor.web3.PublicKey(0))) {
+        return stakingData.mintAddress;
+    }
+    return stakingData.rewardMint;
+}
+
+async function getVestingAccount(stakeStateAcc, programId){
+
+    const [acc] = await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from("staking-vesting", "utf-8"), stakeStateAcc.toBuffer()], programId);
+    return acc;    
+}
+
+async function getDonorRecordAccount(stakingDataAcc, donor, programId){
+
+    const [acc] = await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from("staking-donor", "utf-8"), stakingDataAcc.toBuffer(), donor.toBuffer()], programId);
+    return acc;    
+}
+
+// remaining accounts of the instructions which settle every reward stream of the pool
+async function getRewardStreamAccounts(stakingData, stakingDataAcc, programId){
+    const accounts = [];
+    for (let i = 0; i < stakingData.rewardStreams; i++) {
+        accounts.push({
+            pubkey: await getRewardStreamAccount(stakingDataAcc, i, programId),
+            isWritable: true,
+            isSigner: false,
+        });
+    }
+    return accounts;
+}
+
+const REWARD_PER_TOKEN_PRECISION = 1_000_000_000_000n;
+
+// mirrors calculate::calculate_reward_per_token, integer math rounded down
+function calculateRewardPerToken(rewardPerTokenStored, poolReward, timeFrameStart, timeFrameEnd, lastUpdateTime, poolStaked, nowTs)
 {
-    if (staked === 0 || stakeStartTime >= timeFrameEnd){
-        return 0;
+    const frameSeconds = BigInt(timeFrameEnd - timeFrameStart);
+    if (frameSeconds === 0n || poolStaked === 0) {
+        return BigInt(rewardPerTokenStored);
     }
+    const from = Math.max(lastUpdateTime, timeFrameStart);
+    const to = Math.min(nowTs, timeFrameEnd);
+    if (to <= from) {
+        return BigInt(rewardPerTokenStored);
+    }
+    const delta = (BigInt(poolReward) * BigInt(to - from) * REWARD_PER_TOKEN_PRECISION) / (frameSeconds * BigInt(poolStaked));
+    return BigInt(rewardPerTokenStored) + delta;
+}
 
-    let frameSeconds = timeFrameEnd - timeFrameStart;
-    let seconds = nowTs - stakeStartTime;
+// mirrors calculate::calculate_earned
+function calculateEarned(rewardPerToken, rewardPerTokenPaid, staked)
+{
+    return Number((BigInt(staked) * (BigInt(rewardPerToken) - BigInt(rewardPerTokenPaid))) / REWARD_PER_TOKEN_PRECISION);
+}
 
-    if (seconds > frameSeconds) {
-        seconds = frameSeconds;
+// mirrors calculate::calculate_position_earned, the lock boost only earns until lockEnd
+function calculatePositionEarned(rewardPerToken, rewardPerTokenPaid, staked, lockBoost, lockEnd, settledTime, nowTs)
+{
+    const earned = calculateEarned(rewardPerToken, rewardPerTokenPaid, staked);
+    const boostEarned = calculateEarned(rewardPerToken, rewardPerTokenPaid, lockBoost);
+    if (nowTs <= lockEnd) {
+        return earned + boostEarned;
     }
-    if(seconds < minStakePeriod)
-    {
+    if (settledTime >= lockEnd) {
+        return earned;
+    }
+    return earned + Number((BigInt(boostEarned) * BigInt(lockEnd - settledTime)) / BigInt(nowTs - settledTime));
+}
+
+// mirrors calculate::convert_decimals
+function convertDecimals(amount, fromDecimals, toDecimals)
+{
+    if (fromDecimals === toDecimals) {
+        return amount;
+    }
+    return Number((BigInt(amount) * 10n ** BigInt(toDecimals)) / 10n ** BigInt(fromDecimals));
+}
+
+const SECONDS_PER_YEAR = 365 * 24 * 60 * 60;
+
+const CAP_HEADROOM_SCALE = 10000n * BigInt(SECONDS_PER_YEAR);
+
+// mirrors calculate::calculate_cap_allowance as a BigInt, apyMax is the APR cap in basis points and 0 for no cap
+function calculateCapAllowance(apyMax, staked, mintDecimals, rewardDecimals, seconds)
+{
+    if (apyMax === 0 || staked === 0) {
+        return 0n;
+    }
+    staked = convertDecimals(staked, mintDecimals, rewardDecimals);
+    return BigInt(staked) * BigInt(apyMax) * BigInt(seconds);
+}
+
+// mirrors calculate::calculate_reward, returns the credited reward and the cap headroom left as a BigInt
+function calculateReward(apyMax, earned, capHeadroom)
+{
+    if (apyMax === 0) {
+        return [earned, 0n];
+    }
+    const cap = capHeadroom / CAP_HEADROOM_SCALE;
+    const gained = cap < BigInt(earned) ? cap : BigInt(earned);
+    return [Number(gained), capHeadroom - gained * CAP_HEADROOM_SCALE];
+}
+
+// mirrors calculate::calculate_legacy_reward, apyMax is a percent of staked counting the stake itself
+function calculateLegacyReward(apyMax, earned, staked, mintDecimals, rewardDecimals)
+{
+    if (staked === 0 || earned === 0) {
         return 0;
     }
+    staked = BigInt(convertDecimals(staked, mintDecimals, rewardDecimals));
+    const cap = (staked * BigInt(apyMax)) / 100n - staked;
+    return Number(cap < BigInt(earned) ? cap : BigInt(earned));
+}
 
-    let days = seconds/ (3600 * 24);
-    let frameDays = frameSeconds / (3600 * 24);
-    let gainedTotal = (poolReward * days * staked)/ (frameDays * poolStaked);
-    let gainedPerDay = gainedTotal / days;
-    let stakedPerDay = staked / days;
-    let gainedPercentPerDay = gainedPerDay * 100.00 / stakedPerDay;
-    let apdMax = apyMax / 365.50;
+// mirrors calculate::calculate_fixed_term_interest
+function calculateFixedTermInterest(principal, aprBps, term, mintDecimals, rewardDecimals)
+{
+    const converted = BigInt(convertDecimals(principal, mintDecimals, rewardDecimals));
+    return Number((converted * BigInt(aprBps) * BigInt(term)) / (10000n * BigInt(SECONDS_PER_YEAR)));
+}
 
-    if (gainedPercentPerDay > apdMax){
-        gainedPercentPerDay = apdMax;
+// part of a vesting schedule unlocked at nowTs, same as calculate_vested
+function calculateVested(amount, start, cliffEnd, end, nowTs)
+{
+    if (nowTs < cliffEnd) {
+        return 0;
+    }
+    if (nowTs >= end) {
+        return amount;
     }
+    return Number((BigInt(amount) * BigInt(nowTs - start)) / BigInt(end - start));
+}
 
-    let gained = gainedPercentPerDay * stakedPerDay * days / 100.00;
-    return gained;
+// staked amount of the pool weighted by the lock multipliers
+function getEffectiveStaked(stakingData)
+{
+    return stakingData.totalStaked.toNumber() + stakingData.totalLockBoost.toNumber();
 }
 
-function getGainedReward(stakingData, stakingState)
+// reward a position settles at nowTs, from a snapshot of the pool and the staker state
+function pendingReward(stakingData, stakingState, staked, stakedTime, nowTs)
 {
-    for(let i=0; i<stakingData.stakers.length; i++)
-    {
-        const staker = stakingData.stakers[i];
-        if(staker.stakerCrc !== stakingState.myCrc)
-            continue;
-        return staker.gainedReward.toNumber();
+    const timeframeStarted = stakingData.timeframeStarted.toNumber();
+    const rewardPerToken = calculateRewardPerToken(
+        stakingData.rewardPerTokenStored.toString(),
+        stakingData.poolReward.toString(),
+        timeframeStarted,
+        timeframeStarted + stakingData.timeframeInSecond.toNumber(),
+        stakingData.lastUpdateTime.toNumber(),
+        getEffectiveStaked(stakingData),
+        nowTs);
+    const pending = stakingState.pendingReward.toNumber();
+    const earned = pending + calculateEarned(rewardPerToken, stakingState.rewardPerTokenPaid.toString(), staked);
+    if (nowTs - stakedTime < stakingData.minStakePeriod.toNumber()) {
+        return 0;
+    }
+    if (!stakingData.apyMaxInBps) {
+        return calculateLegacyReward(stakingData.apyMax, earned, staked, stakingData.mintDecimals, stakingData.rewardDecimals);
+    }
+    const seconds = nowTs - Math.max(stakingState.settledTime.toNumber(), stakedTime);
+    const capHeadroom = BigInt(stakingState.capHeadroom.toString())
+        + calculateCapAllowance(stakingData.apyMax, staked, stakingData.mintDecimals, stakingData.rewardDecimals, seconds);
+    return calculateReward(stakingData.apyMax, earned, capHeadroom)[0];
+}
+
+// reward a position settles in a reward stream at nowTs
+function pendingStreamReward(stakingData, rewardStream, stakingState, staked, stakedTime, nowTs)
+{
+    const timeframeStarted = rewardStream.timeframeStarted.toNumber();
+    const rewardPerToken = calculateRewardPerToken(
+        rewardStream.rewardPerTokenStored.toString(),
+        rewardStream.poolReward.toString(),
+        timeframeStarted,
+        timeframeStarted + rewardStream.timeframeInSecond.toNumber(),
+        rewardStream.lastUpdateTime.toNumber(),
+        getEffectiveStaked(stakingData) - stakingData.vaultStaked.toNumber(),
+        nowTs);
+    const position = stakingState.streamPositions[rewardStream.index];
+    const settlement = stakingState.streamSettlements[rewardStream.index];
+    const rewardPerTokenPaid = position === undefined ? 0 : position.rewardPerTokenPaid.toString();
+    const pending = settlement === undefined ? 0 : settlement.pendingReward.toNumber();
+    const settledTime = settlement === undefined ? 0 : settlement.settledTime.toNumber();
+    const earned = pending + calculateEarned(rewardPerToken, rewardPerTokenPaid, staked);
+    if (nowTs - stakedTime < stakingData.minStakePeriod.toNumber()) {
+        return 0;
     }
-    return 0;
+    const capHeadroom = (settlement === undefined ? 0n : BigInt(settlement.capHeadroom.toString()))
+        + calculateCapAllowance(rewardStream.apyMax, staked, stakingData.mintDecimals, rewardStream.rewardDecimals, nowTs - Math.max(settledTime, stakedTime));
+    return calculateReward(rewardStream.apyMax, earned, capHeadroom)[0];
+}
+
+// vault assets and share supply once the vault compounds at nowTs, the exchange rate is assets / shares
+function getVaultExchangeRate(stakingData, shareSupply, nowTs)
+{
+    const timeframeStarted = stakingData.timeframeStarted.toNumber();
+    const rewardPerToken = calculateRewardPerToken(
+        stakingData.rewardPerTokenStored.toString(),
+        stakingData.poolReward.toString(),
+        timeframeStarted,
+        timeframeStarted + stakingData.timeframeInSecond.toNumber(),
+        stakingData.lastUpdateTime.toNumber(),
+        getEffectiveStaked(stakingData),
+        nowTs);
+    const vaultStaked = stakingData.vaultStaked.toNumber();
+    const earned = calculateEarned(rewardPerToken, stakingData.vaultRewardPerTokenPaid.toString(), vaultStaked);
+    const capHeadroom = BigInt(stakingData.vaultCapHeadroom.toString()) + calculateCapAllowance(stakingData.apyMax, vaultStaked,
+        stakingData.mintDecimals, stakingData.rewardDecimals, nowTs - stakingData.vaultSettledTime.toNumber());
+    const [compounded] = calculateReward(stakingData.apyMax, earned, capHeadroom);
+    return {
+        assets: vaultStaked + compounded,
+        shares: shareSupply,
+    };
+}
+
+function getGainedReward(stakingState)
+{
+    return stakingState.gainedReward.toNumber();
 }
 
 
@@ -317,13 +564,38 @@ module.exports = {
 
     getStakingData,
     getStakingState,
+    getRewardStream,
+    getDonorRecord,
 
     getStakingDataAccount,
     getEscrowAccount,
     getRewarderAccount,
     getStakingAuthAccount,
     getStakingStateAccount,
-
+    getRewardStreamAccount,
+    getRewardStreamAccounts,
+    getFixedTermProductAccount,
+    getFixedTermDepositAccount,
+    getShareMintAccount,
+    getReceiptMintAccount,
+    receiptEnabled,
+    getOwnerReceiptAccount,
+    getRewardMint,
+    getVestingAccount,
+    getDonorRecordAccount,
+
+    calculateRewardPerToken,
+    calculateEarned,
+    calculatePositionEarned,
+    convertDecimals,
+    calculateCapAllowance,
     calculateReward,
+    calculateLegacyReward,
+    calculateFixedTermInterest,
+    calculateVested,
+    getEffectiveStaked,
+    pendingReward,
+    pendingStreamReward,
+    getVaultExchangeRate,
     getGainedReward,
 };
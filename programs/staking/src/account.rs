@@ -1,18 +1,51 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
-    token::{TokenAccount, SetAuthority, Transfer}
+    token::{TokenAccount, Mint, SetAuthority, Transfer, Burn, MintTo},
+    associated_token::get_associated_token_address,
 };
 //use anchor_lang::solana_program::*;
+use crate::calculate::*;
+use crate::error::*;
 
 pub const STAKING_PDA_SEED: &[u8] = b"staking";
 pub const STAKER_PDA_SEED: &[u8] = b"staker";
 pub const STAKING_ESCROW_PDA_SEED: &[u8] = b"staking-escrow";
 pub const STAKING_REWARDER_PDA_SEED: &[u8] = b"staking-rewarder";
 pub const STAKING_AUTH_PDA_SEED: &[u8] = b"staking-author";
+pub const STAKING_STREAM_PDA_SEED: &[u8] = b"staking-stream";
+pub const STAKING_PRODUCT_PDA_SEED: &[u8] = b"staking-product";
+pub const STAKING_DEPOSIT_PDA_SEED: &[u8] = b"staking-deposit";
+pub const STAKING_SHARE_MINT_PDA_SEED: &[u8] = b"staking-share-mint";
+pub const STAKING_RECEIPT_MINT_PDA_SEED: &[u8] = b"staking-receipt-mint";
+pub const STAKING_VESTING_PDA_SEED: &[u8] = b"staking-vesting";
+pub const STAKING_DONOR_PDA_SEED: &[u8] = b"staking-donor";
 
+pub const PENALTY_MODE_REDISTRIBUTE: u8 = 0;
+pub const PENALTY_MODE_TREASURY: u8 = 1;
+pub const PENALTY_MODE_BURN: u8 = 2;
+
+pub const ROLLOVER_MODE_CARRY: u8 = 0;
+pub const ROLLOVER_MODE_FUNDER: u8 = 1;
+
+//lock duration a staker can choose in staking, multiplier_bps 10000 means 1x
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct LockTier {
+    pub duration: u64,
+    pub multiplier_bps: u32,
+}
+
+//queued funding, emits amount from start to end once the timeframe before it ended
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct EmissionEpoch {
+    pub start: u64,
+    pub end: u64,
+    pub amount: u64,
+}
+
+//entry of the legacy stakers list, positions are kept in StakingState now
 #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
 pub struct StakerState {
-    pub staker_crc: u32,
+    pub staker_crc: u32,  //crc32 of the StakingState key, only used to migrate
     pub staked_time: u64,
     pub staked_amount: u64,
     pub gained_reward: u64,
@@ -36,18 +69,103 @@ pub struct StakingData {
     pub min_timeframe_in_second: u64,
     pub timeframe_in_second: u64,
     pub timeframe_started: u64,
-    pub pool_reward: u64,
-    pub apy_max: u32,
+    pub pool_reward: u64,  //emitted over the whole timeframe at the current rate, top_up rescales it
+    pub apy_max: u32,  //APR cap in basis points, 0 for no cap
     pub min_stake_period: u64,
 
-    //stakers
-    pub stakers: Vec<StakerState>
+    //legacy stakers, drained by migrate_staker
+    pub stakers: Vec<StakerState>,
+
+    //reward accumulator
+    pub reward_per_token_stored: u128,
+    pub last_update_time: u64,
+    pub total_reward_accrued: u64,
+
+    //unbonding
+    pub unbonding_period: u64,
+    pub total_unbonding: u64,
+
+    //early withdrawal penalty
+    pub penalty_bps: u16,
+    pub penalty_period: u64,
+    pub penalty_mode: u8,
+    pub penalty_treasury: Pubkey,
+    pub total_penalty: u64,
+
+    //reward token
+    pub reward_mint: Pubkey,
+    pub mint_decimals: u8,
+    pub reward_decimals: u8,
+
+    //count of RewardStream accounts, their index goes from 0 to reward_streams - 1
+    pub reward_streams: u8,
+
+    //lockup multipliers, lock tier n of staking is lock_tiers[n - 1], 0 is no lock
+    pub lock_tiers: Vec<LockTier>,
+    pub total_lock_boost: u64,
+
+    //fixed term products, principal sits in escrow outside total_staked
+    pub fixed_term_products: u8,
+    pub total_fixed_term_principal: u64,
+    pub total_reserved_reward: u64,
+
+    //auto-compounding vault, its shares stand for vault_staked which is part of total_staked
+    pub share_mint: Pubkey,
+    pub vault_staked: u64,
+    pub vault_reward_per_token_paid: u128,
+
+    //liquid receipts minted 1:1 by staking
+    pub receipt_mint: Pubkey,
+
+    //claimed pool reward vests linearly over vesting_duration after vesting_cliff, 0 pays out directly
+    pub vesting_duration: u64,
+    pub vesting_cliff: u64,
+
+    //reward capped off by apy_max since the last funding, part of the left over reward funding rolls over
+    pub capped_reward: u64,
+    pub vault_settled_time: u64,
+
+    //what funding does with the reward no staker got in the closed timeframe
+    pub rollover_mode: u8,
+    //left over reward set aside for the funder with ROLLOVER_MODE_FUNDER
+    pub unallocated_reward: u64,
+
+    //funding schedule in start order, the first epoch becomes the timeframe once the clock reaches its start
+    pub emission_epochs: Vec<EmissionEpoch>,
+
+    //any signer can donate reward into the running timeframe or the next queued epoch
+    pub donations_enabled: bool,
+    pub total_donated: u64,
+
+    //settle crank, the keeper earns keeper_tip out of the free reward per settled position
+    pub keeper_tip: u64,
+    pub last_settle_time: u64,
+    pub total_settled_positions: u64,
+
+    //apy_max is an annual rate in basis points, pools initialized before read false and keep the percent cap
+    pub apy_max_in_bps: bool,
+
+    //penalty redistributed while no timeframe runs, the next timeframe adds it to its pool reward
+    pub pending_redistribution: u64,
+
+    //apy_max cap the vault accrued and didn't use yet, like StakingState::cap_headroom
+    pub vault_cap_headroom: u128,
 }
 
 impl StakingData{
-    pub const MAX_STAKERS: usize = 385;
+    pub const MAX_REWARD_STREAMS: u8 = 4;
+    pub const MAX_LOCK_TIERS: usize = 8;
+    pub const MAX_EMISSION_EPOCHS: usize = 16;
+    pub const MAX_SETTLE_PAGE: usize = 16;
+    //the settle tip never takes more than this share of the reward it settled
+    pub const MAX_KEEPER_TIP_BPS: u64 = 1000;
+    pub const MAX_MIGRATE_PAGE: usize = 16;
+    pub const SPACE: usize = 10240;
 
     pub fn index_of_staker(&self, crc: u32) -> i32{
+        if crc == 0 {
+            return -1;
+        }
         for i in 0..self.stakers.len() {
             if crc == self.stakers[i].staker_crc {
                 return i as i32;
@@ -55,13 +173,560 @@ impl StakingData{
         }
         return -1;
     }
+
+    pub fn count_of_staker(&self, crc: u32) -> usize{
+        self.stakers.iter().filter(|staker| staker.staker_crc == crc).count()
+    }
+
+    //owed to the legacy entries, it joins the accrued total once they are migrated
+    pub fn legacy_gained_reward(&self) -> Result<u64>{
+        let mut gained: u64 = 0;
+        for staker in self.stakers.iter() {
+            gained = gained.checked_add(staker.gained_reward).ok_or(StakingErrors::MathOverflow)?;
+        }
+        Ok(gained)
+    }
+
+    //entry of a staker state, crc collisions can't be told apart by it
+    pub fn legacy_staker_index(&self, crc: u32) -> Result<usize>{
+        let staker_index = self.index_of_staker(crc);
+        if staker_index < 0 {
+            return Err(StakingErrors::InvalidStakingStateAccountCantFindEntry.into());
+        }
+        if self.count_of_staker(crc) > 1 {
+            return Err(StakingErrors::AmbiguousLegacyStaker.into());
+        }
+        Ok(staker_index as usize)
+    }
+
+    //moves the legacy entry at index into its StakingState
+    pub fn migrate_staker(&mut self, index: usize, stake_state: &mut StakingState) -> Result<()>{
+        if stake_state.my_crc == 0 || index >= self.stakers.len() || self.stakers[index].staker_crc != stake_state.my_crc {
+            return Err(StakingErrors::InvalidStakingStateAccountCantFindEntry.into());
+        }
+        let staker = self.stakers.remove(index);
+        if staker.staked_amount != stake_state.total_staked {
+            return Err(StakingErrors::InvalidStakingStateAccountDosentMatchAmount.into());
+        }
+
+        stake_state.staked_time = staker.staked_time;
+        stake_state.gained_reward = stake_state.gained_reward.checked_add(staker.gained_reward).ok_or(StakingErrors::MathOverflow)?;
+        stake_state.my_crc = 0;
+        self.total_reward_accrued = self.total_reward_accrued.checked_add(staker.gained_reward).ok_or(StakingErrors::MathOverflow)?;
+        Ok(())
+    }
+
+    //picks the entry of a crc collision, the initializer names it and the values it has to hold
+    pub fn resolve_legacy_staker(&mut self, index: usize, staked_amount: u64, staked_time: u64, stake_state: &mut StakingState) -> Result<()>{
+        if index >= self.stakers.len() || self.stakers[index].staked_amount != staked_amount || self.stakers[index].staked_time != staked_time {
+            return Err(StakingErrors::InvalidStakingStateAccountCantFindEntry.into());
+        }
+        self.migrate_staker(index, stake_state)
+    }
+
+    //a legacy pool can hold more stakers than fit in front of the appended fields,
+    //past the end of the account they read as zero like on any account created before them
+    pub fn load_unchecked(info: &AccountInfo) -> Result<StakingData>{
+        let data = info.try_borrow_data()?;
+        let mut padded = data.to_vec();
+        padded.resize(data.len() + StakingData::SPACE, 0);
+        StakingData::try_deserialize(&mut &padded[..])
+    }
+
+    pub fn store(&self, info: &AccountInfo) -> ProgramResult{
+        self.check_size()?;
+        let mut data = info.try_borrow_mut_data()?;
+        let dst: &mut [u8] = &mut data;
+        let mut cursor = std::io::Cursor::new(dst);
+        self.try_serialize(&mut cursor)
+    }
+
+    //the account is never reallocated, lists growing in it have to fit its space
+    pub fn check_size(&self) -> Result<()>{
+        let size = self.try_to_vec().map_err(|_| StakingErrors::StakingDataTooLarge)?.len() + 8;
+        if size > StakingData::SPACE {
+            return Err(StakingErrors::StakingDataTooLarge.into());
+        }
+        Ok(())
+    }
+
+    //pools created before reward_mint existed pay in the staking mint
+    pub fn reward_mint(&self) -> Pubkey{
+        if self.reward_mint == Pubkey::default() {
+            return self.mint_address;
+        }
+        self.reward_mint
+    }
+
+    //staked amount weighted by the lock multipliers, rewards are shared on it
+    pub fn effective_staked(&self) -> u64{
+        self.total_staked + self.total_lock_boost
+    }
+
+    //vault shares don't earn the reward streams
+    pub fn stream_staked(&self) -> u64{
+        self.effective_staked() - self.vault_staked
+    }
+
+    pub fn authority_address(staking_account: &Pubkey) -> Pubkey
+    {
+        let (address, _bump) = Pubkey::find_program_address(
+            &[STAKING_AUTH_PDA_SEED, staking_account.as_ref()], &crate::ID);
+        address
+    }
+
+    pub fn vault_enabled(&self) -> bool{
+        self.share_mint != Pubkey::default()
+    }
+
+    //vault shares redeem at once and carry no entry time, so the pool can't hold stake back on exit
+    pub fn check_vault_exit(&self) -> ProgramResult{
+        if self.vault_enabled() && (self.unbonding_period > 0 || self.penalty_bps > 0 || self.min_stake_period > 0) {
+            return Err(StakingErrors::VaultExitRestricted.into());
+        }
+        Ok(())
+    }
+
+    pub fn receipt_enabled(&self) -> bool{
+        self.receipt_mint != Pubkey::default()
+    }
+
+    //credits the vault reward since its checkpoint as vault stake,
+    //the caller moves the returned amount from the rewarder into the escrow
+    pub fn compound_vault(&mut self, now_ts: u64) -> Result<u64>{
+        self.update_reward_per_token(now_ts)?;
+        let (gained, cap_headroom) = self.settle_reward(self.vault_staked, 0, self.vault_settled_time, self.vault_reward_per_token_paid,
+            self.vault_cap_headroom, now_ts)?;
+        self.vault_cap_headroom = cap_headroom;
+        self.vault_reward_per_token_paid = self.reward_per_token_stored;
+        self.vault_settled_time = now_ts;
+        self.vault_staked = self.vault_staked.checked_add(gained).ok_or(StakingErrors::MathOverflow)?;
+        self.total_staked = self.total_staked.checked_add(gained).ok_or(StakingErrors::MathOverflow)?;
+        self.total_reward_paid = self.total_reward_paid + gained;
+        Ok(gained)
+    }
+
+    //accrues piecewise: every queued epoch the clock reached closes the timeframe before it at its end
+    //and runs as the timeframe from its own start
+    pub fn update_reward_per_token(&mut self, now_ts: u64) -> Result<()>{
+        while self.emission_epochs.len() > 0 && now_ts >= self.emission_epochs[0].start {
+            let time_frame_end = self.timeframe_started + self.timeframe_in_second;
+            self.accrue_reward_per_token(time_frame_end.max(self.last_update_time))?;
+
+            let epoch = self.emission_epochs.remove(0);
+            self.pool_reward = epoch.amount.checked_add(self.pending_redistribution).ok_or(StakingErrors::MathOverflow)?;
+            self.pending_redistribution = 0;
+            self.timeframe_started = epoch.start;
+            self.timeframe_in_second = epoch.end - epoch.start;
+        }
+        self.accrue_reward_per_token(now_ts)
+    }
+
+    fn accrue_reward_per_token(&mut self, now_ts: u64) -> Result<()>{
+        let (reward_per_token, accrued) = calculate_reward_per_token(self.reward_per_token_stored, self.pool_reward,
+            self.timeframe_started, self.timeframe_started + self.timeframe_in_second,
+            self.last_update_time, self.effective_staked(), now_ts)?;
+        self.reward_per_token_stored = reward_per_token;
+        self.total_reward_accrued = self.total_reward_accrued.checked_add(accrued).ok_or(StakingErrors::MathOverflow)?;
+        self.last_update_time = now_ts;
+        Ok(())
+    }
+
+    //end of the running timeframe or of the last queued epoch, a new epoch can't start before it
+    pub fn emission_end(&self) -> u64{
+        match self.emission_epochs.last() {
+            Some(epoch) => epoch.end,
+            None => self.timeframe_started + self.timeframe_in_second,
+        }
+    }
+
+    //spreads amount over the rest of the running timeframe, its end moves out by extend_in_second
+    pub fn top_up_timeframe(&mut self, amount: u64, extend_in_second: u64, now_ts: u64) -> Result<()>{
+        let unemitted = self.unemitted_pool_reward(now_ts)?;
+        let remaining = unemitted.checked_add(amount).ok_or(StakingErrors::MathOverflow)?;
+        let time_frame_end = self.timeframe_started + self.timeframe_in_second;
+        let new_time_frame_end = time_frame_end.checked_add(extend_in_second).ok_or(StakingErrors::MathOverflow)?;
+        self.pool_reward = calculate_top_up_pool_reward(remaining, self.timeframe_started, new_time_frame_end, now_ts)?;
+        self.timeframe_in_second = new_time_frame_end - self.timeframe_started;
+        Ok(())
+    }
+
+    pub fn queued_reward(&self) -> Result<u64>{
+        let mut queued: u64 = 0;
+        for epoch in self.emission_epochs.iter() {
+            queued = queued.checked_add(epoch.amount).ok_or(StakingErrors::MathOverflow)?;
+        }
+        Ok(queued)
+    }
+
+    //settles the reward earned by a position since its checkpoint taken at settled_time,
+    //the part which isn't credited is released from the accrued total, returns it with the cap headroom left
+    pub fn settle_reward(&mut self, staked: u64, staked_time: u64, settled_time: u64, reward_per_token_paid: u128,
+        cap_headroom: u128, now_ts: u64) -> Result<(u64, u128)>{
+        let earned = calculate_earned(self.reward_per_token_stored, reward_per_token_paid, staked)?;
+        let cap_headroom = self.accrue_cap_headroom(cap_headroom, staked, settled_time.max(staked_time), now_ts)?;
+        if now_ts < staked_time + self.min_stake_period {
+            //forfeited inside min stake period isn't capped off
+            self.release_reward(earned);
+            return Ok((0, cap_headroom));
+        }
+        self.credit_reward(earned, staked, cap_headroom)
+    }
+
+    //cap headroom of staked since from, legacy pools cap each settled reward on its own and keep none
+    pub fn accrue_cap_headroom(&self, cap_headroom: u128, staked: u64, from: u64, now_ts: u64) -> Result<u128>{
+        if !self.apy_max_in_bps {
+            return Ok(cap_headroom);
+        }
+        let allowance = calculate_cap_allowance(self.apy_max as u64, staked, self.mint_decimals, self.reward_decimals, now_ts.saturating_sub(from))?;
+        cap_headroom.checked_add(allowance).ok_or(StakingErrors::MathOverflow.into())
+    }
+
+    //credits earned through apy_max, what is capped off is released from the accrued total
+    fn credit_reward(&mut self, earned: u64, staked: u64, cap_headroom: u128) -> Result<(u64, u128)>{
+        let (gained, cap_headroom) = if self.apy_max_in_bps {
+            calculate_reward(self.apy_max as u64, earned, cap_headroom)?
+        } else {
+            (calculate_legacy_reward(self.apy_max as u64, earned, staked, self.mint_decimals, self.reward_decimals)?, cap_headroom)
+        };
+        self.release_reward(earned - gained);
+        self.capped_reward = self.capped_reward.checked_add(earned - gained).ok_or(StakingErrors::MathOverflow)?;
+        Ok((gained, cap_headroom))
+    }
+
+    //settles a position since its checkpoint: inside min stake period what it earned is kept in pending_reward
+    //instead of being forfeited, once the position passed it pending and earned are credited together.
+    //an expired lock boost only earns until lock_end, the rest is released
+    pub fn settle_position(&mut self, stake_state: &mut StakingState, now_ts: u64) -> Result<u64>{
+        let (earned, expired) = calculate_position_earned(self.reward_per_token_stored, stake_state.reward_per_token_paid,
+            stake_state.total_staked, stake_state.lock_boost, stake_state.lock_end, stake_state.settled_time, now_ts)?;
+        self.release_reward(expired);
+        stake_state.cap_headroom = self.accrue_cap_headroom(stake_state.cap_headroom, stake_state.effective_staked(),
+            stake_state.settled_time.max(stake_state.staked_time), now_ts)?;
+        stake_state.reward_per_token_paid = self.reward_per_token_stored;
+        stake_state.settled_time = now_ts;
+        stake_state.pending_reward = stake_state.pending_reward.checked_add(earned).ok_or(StakingErrors::MathOverflow)?;
+        if !stake_state.passed_min_stake_period(self.min_stake_period, now_ts) {
+            return Ok(0);
+        }
+
+        let pending = stake_state.pending_reward;
+        stake_state.pending_reward = 0;
+        let (gained, cap_headroom) = self.credit_reward(pending, stake_state.effective_staked(), stake_state.cap_headroom)?;
+        stake_state.cap_headroom = cap_headroom;
+        stake_state.add_settled_reward(gained)?;
+        Ok(gained)
+    }
+
+    pub fn release_reward(&mut self, amount: u64){
+        self.total_reward_accrued = self.total_reward_accrued.saturating_sub(amount);
+    }
+
+    pub fn total_reward_in_pending(&self) -> u64{
+        self.total_reward_accrued.saturating_sub(self.total_reward_paid)
+    }
+
+    //part of pool_reward the running timeframe still has to emit
+    pub fn unemitted_pool_reward(&self, now_ts: u64) -> Result<u64>{
+        let time_frame_end = self.timeframe_started + self.timeframe_in_second;
+        if self.timeframe_in_second == 0 || now_ts >= time_frame_end {
+            return Ok(0);
+        }
+        let from = now_ts.max(self.timeframe_started);
+        let unemitted = mul_div(self.pool_reward as u128, (time_frame_end - from) as u128, self.timeframe_in_second as u128)?;
+        Ok(unemitted as u64)
+    }
+
+    //reward allocated to stakers: owed, owed to legacy entries, reserved for fixed term deposits,
+    //still to be emitted or redistributed by the next timeframe
+    pub fn allocated_reward(&self, now_ts: u64) -> Result<u64>{
+        let allocated = self.total_reward_in_pending()
+            .checked_add(self.total_reserved_reward).ok_or(StakingErrors::MathOverflow)?
+            .checked_add(self.unemitted_pool_reward(now_ts)?).ok_or(StakingErrors::MathOverflow)?
+            .checked_add(self.queued_reward()?).ok_or(StakingErrors::MathOverflow)?
+            .checked_add(self.pending_redistribution).ok_or(StakingErrors::MathOverflow)?
+            .checked_add(self.legacy_gained_reward()?).ok_or(StakingErrors::MathOverflow)?;
+        Ok(allocated)
+    }
+
+    //rewarder amount which is neither allocated nor set aside for the funder
+    pub fn free_reward(&self, rewarder_amount: u64, now_ts: u64) -> Result<u64>{
+        let liabilities = self.allocated_reward(now_ts)?
+            .checked_add(self.unallocated_reward).ok_or(StakingErrors::MathOverflow)?;
+        Ok(rewarder_amount.saturating_sub(liabilities))
+    }
+
+    //reward no staker got once the running timeframe is dropped by a funding:
+    //idle time, capped off or forfeited.
+    //reserved, set aside and pending redistribution are exact, a rewarder below them is an error,
+    //pending is rounded up by the accumulator and legacy entries were credited before the rewarder
+    //was reconciled, so they only take what is left
+    pub fn leftover_reward(&self, rewarder_amount: u64) -> Result<u64>{
+        let rest = rewarder_amount
+            .checked_sub(self.total_reserved_reward).ok_or(StakingErrors::RewarderUnderfunded)?
+            .checked_sub(self.unallocated_reward).ok_or(StakingErrors::RewarderUnderfunded)?
+            .checked_sub(self.pending_redistribution).ok_or(StakingErrors::RewarderUnderfunded)?;
+        Ok(rest.saturating_sub(self.total_reward_in_pending()).saturating_sub(self.legacy_gained_reward()?))
+    }
+}
+
+//extra reward stream of a pool, accrued on the same staked amounts as the pool reward
+#[account]
+#[derive(Default)]
+pub struct RewardStream {
+    pub staking_account: Pubkey,
+    pub index: u8,
+    pub funder_authority: Pubkey,
+    pub reward_mint: Pubkey,
+    pub reward_decimals: u8,
+    pub rewarder_account: Pubkey,
+    pub total_funded: u64,
+    pub total_reward_paid: u64,
+
+    //funding
+    pub min_timeframe_in_second: u64,
+    pub timeframe_in_second: u64,
+    pub timeframe_started: u64,
+    pub pool_reward: u64,
+    pub apy_max: u32,
+
+    //reward accumulator
+    pub reward_per_token_stored: u128,
+    pub last_update_time: u64,
+    pub total_reward_accrued: u64,
+}
+
+impl RewardStream{
+    pub fn address(staking_account: &Pubkey, index: u8) -> Pubkey
+    {
+        let (address, _bump) = Pubkey::find_program_address(
+            &[STAKING_STREAM_PDA_SEED, staking_account.as_ref(), &[index]], &crate::ID);
+        address
+    }
+
+    pub fn update_reward_per_token(&mut self, total_staked: u64, now_ts: u64) -> Result<()>{
+        let (reward_per_token, accrued) = calculate_reward_per_token(self.reward_per_token_stored, self.pool_reward,
+            self.timeframe_started, self.timeframe_started + self.timeframe_in_second,
+            self.last_update_time, total_staked, now_ts)?;
+        self.reward_per_token_stored = reward_per_token;
+        self.total_reward_accrued = self.total_reward_accrued.checked_add(accrued).ok_or(StakingErrors::MathOverflow)?;
+        self.last_update_time = now_ts;
+        Ok(())
+    }
+
+    //same as StakingData::settle_position, with the stream apy_max and reward decimals,
+    //streams are capped in basis points. returns the position index in stream_positions
+    pub fn settle_position(&mut self, staking_data: &StakingData, stake_state: &mut StakingState, now_ts: u64) -> Result<usize>{
+        self.update_reward_per_token(staking_data.stream_staked(), now_ts)?;
+        let position = stake_state.stream_position(self.index);
+        let settlement = stake_state.stream_settlements[position].clone();
+        let (earned, expired) = calculate_position_earned(self.reward_per_token_stored, stake_state.stream_positions[position].reward_per_token_paid,
+            stake_state.total_staked, stake_state.lock_boost, stake_state.lock_end, settlement.settled_time, now_ts)?;
+        self.total_reward_accrued = self.total_reward_accrued.saturating_sub(expired);
+        let allowance = calculate_cap_allowance(self.apy_max as u64, stake_state.effective_staked(), staking_data.mint_decimals, self.reward_decimals,
+            now_ts.saturating_sub(settlement.settled_time.max(stake_state.staked_time)))?;
+        let cap_headroom = settlement.cap_headroom.checked_add(allowance).ok_or(StakingErrors::MathOverflow)?;
+        let pending = settlement.pending_reward.checked_add(earned).ok_or(StakingErrors::MathOverflow)?;
+        stake_state.stream_positions[position].reward_per_token_paid = self.reward_per_token_stored;
+        stake_state.stream_settlements[position].settled_time = now_ts;
+        stake_state.stream_settlements[position].pending_reward = pending;
+        stake_state.stream_settlements[position].cap_headroom = cap_headroom;
+        if !stake_state.passed_min_stake_period(staking_data.min_stake_period, now_ts) {
+            return Ok(position);
+        }
+
+        let (gained, cap_headroom) = calculate_reward(self.apy_max as u64, pending, cap_headroom)?;
+        self.total_reward_accrued = self.total_reward_accrued.saturating_sub(pending - gained);
+        stake_state.stream_settlements[position].pending_reward = 0;
+        stake_state.stream_settlements[position].cap_headroom = cap_headroom;
+        stake_state.add_settled_stream_reward(position, gained)?;
+        Ok(position)
+    }
+
+    pub fn total_reward_in_pending(&self) -> u64{
+        self.total_reward_accrued.saturating_sub(self.total_reward_paid)
+    }
+}
+
+//staker state of the pool at info, loaded with StakingState::load_unchecked
+pub fn load_stake_state(program_id: &Pubkey, staking_account: &Pubkey, info: &AccountInfo) -> Result<StakingState>{
+    if info.owner != program_id || !info.is_writable {
+        return Err(StakingErrors::InvalidStakingStateAccount.into());
+    }
+    let stake_state = StakingState::load_unchecked(info)?;
+    if stake_state.staking_account != *staking_account
+        || *info.key != StakingState::address(staking_account, &stake_state.onwer_address) {
+        return Err(StakingErrors::InvalidStakingStateAccount.into());
+    }
+    Ok(stake_state)
+}
+
+//every reward stream of the pool in index order, passed through remaining_accounts
+pub fn load_reward_streams<'info>(program_id: &Pubkey, staking_data: &ProgramAccount<'info, StakingData>,
+    accounts: &[AccountInfo<'info>]) -> Result<Vec<ProgramAccount<'info, RewardStream>>>
+{
+    if accounts.len() < staking_data.reward_streams as usize {
+        return Err(StakingErrors::InvalidRewardStreams.into());
+    }
+    let mut streams = Vec::new();
+    for index in 0..staking_data.reward_streams {
+        let info = &accounts[index as usize];
+        if *info.key != RewardStream::address(staking_data.to_account_info().key, index) {
+            return Err(StakingErrors::InvalidRewardStreams.into());
+        }
+        streams.push(ProgramAccount::<RewardStream>::try_from(program_id, info)?);
+    }
+    Ok(streams)
+}
+
+//settles the position in every stream, has to run before the staked amount of the position or the pool changes
+pub fn settle_reward_streams(streams: &mut Vec<ProgramAccount<RewardStream>>, staking_data: &StakingData,
+    stake_state: &mut StakingState, now_ts: u64) -> Result<()>
+{
+    for stream in streams.iter_mut() {
+        stream.settle_position(staking_data, stake_state, now_ts)?;
+    }
+    Ok(())
+}
+
+//settles the position in the pool and every stream then drops an expired lock off the pool,
+//has to run before the staked amount of the position or the pool changes
+pub fn settle_position(staking_data: &mut StakingData, streams: &mut Vec<ProgramAccount<RewardStream>>,
+    stake_state: &mut StakingState, now_ts: u64) -> Result<u64>
+{
+    staking_data.update_reward_per_token(now_ts)?;
+    let gained = staking_data.settle_position(stake_state, now_ts)?;
+    settle_reward_streams(streams, staking_data, stake_state, now_ts)?;
+
+    let expired_boost = stake_state.expire_lock(now_ts);
+    staking_data.total_lock_boost = staking_data.total_lock_boost - expired_boost;
+    Ok(gained)
+}
+
+//amount leaves the position inside min stake period, the reward it earned is forfeited
+pub fn forfeit_pending_reward(staking_data: &mut StakingData, streams: &mut Vec<ProgramAccount<RewardStream>>,
+    stake_state: &mut StakingState, amount: u64) -> Result<()>
+{
+    if stake_state.total_staked == 0 {
+        return Ok(());
+    }
+    let forfeited = mul_div(stake_state.pending_reward as u128, amount as u128, stake_state.total_staked as u128)? as u64;
+    stake_state.pending_reward = stake_state.pending_reward - forfeited;
+    staking_data.release_reward(forfeited);
+
+    for stream in streams.iter_mut() {
+        let position = stake_state.stream_position(stream.index);
+        let pending = stake_state.stream_settlements[position].pending_reward;
+        let forfeited = mul_div(pending as u128, amount as u128, stake_state.total_staked as u128)? as u64;
+        stake_state.stream_settlements[position].pending_reward = pending - forfeited;
+        stream.total_reward_accrued = stream.total_reward_accrued.saturating_sub(forfeited);
+    }
+    Ok(())
+}
+
+//receipt accounts are plain AccountInfo since pools without receipts have none
+pub fn check_receipt_account(staking_data: &StakingData, receipt_account: &AccountInfo, owner: &Pubkey) -> Result<()>
+{
+    let account = Account::<TokenAccount>::try_from(receipt_account)?;
+    if account.mint != staking_data.receipt_mint || account.owner != *owner {
+        return Err(StakingErrors::InvalidReceiptAccount.into());
+    }
+    Ok(())
+}
+
+//receipt reward and away reward of a checkpoint split into the owner part and what the away receipts are owed:
+//
+//   owner part  = receipt_reward * held / receipt_staked + away_reward * returned / away_receipts
+//   away reward = the rest of both
+//
+//where returned are the receipts away at the last checkpoint the owner holds again
+fn split_receipt_reward(receipt_reward: u64, away_reward: u64, held: u64, receipt_staked: u64, away: u64, away_receipts: u64) -> Result<(u64, u64)>
+{
+    let mut owner_part = 0;
+    if receipt_staked > 0 {
+        owner_part = mul_div(receipt_reward as u128, held.min(receipt_staked) as u128, receipt_staked as u128)? as u64;
+    }
+    let returned = away_receipts.saturating_sub(away);
+    if returned > 0 {
+        owner_part = owner_part + mul_div(away_reward as u128, returned as u128, away_receipts as u128)? as u64;
+    }
+    let total = receipt_reward.checked_add(away_reward).ok_or(StakingErrors::MathOverflow)?;
+    Ok((owner_part, total - owner_part))
+}
+
+//checkpoint_receipts with the receipts the owner holds in its receipt account
+pub fn checkpoint_receipts(staking_data: &StakingData, stake_state: &mut StakingState, owner_receipt_account: &AccountInfo) -> Result<()>
+{
+    if !staking_data.receipt_enabled() || stake_state.receipt_staked == 0 {
+        return Ok(());
+    }
+    if *owner_receipt_account.key != stake_state.owner_receipt_account(&staking_data.receipt_mint) {
+        return Err(StakingErrors::InvalidReceiptAccount.into());
+    }
+    let held = Account::<TokenAccount>::try_from(owner_receipt_account)?.amount;
+    stake_state.checkpoint_receipts(held)
+}
+
+pub fn exit_reward_streams(streams: &Vec<ProgramAccount<RewardStream>>, program_id: &Pubkey) -> ProgramResult
+{
+    for stream in streams.iter() {
+        stream.exit(program_id)?;
+    }
+    Ok(())
+}
+
+//fixed term, fixed apr product of a pool, the apr of a deposit is fixed when it's made
+#[account]
+#[derive(Default)]
+pub struct FixedTermProduct {
+    pub staking_account: Pubkey,
+    pub index: u8,
+    pub term: u64,
+    pub apr_bps: u32,
+    pub capacity: u64,  //0 is unlimited
+    pub enabled: bool,
+    pub total_deposited: u64,
+    pub total_reserved_reward: u64,
+}
+
+//reward donated to a pool by one signer
+#[account]
+#[derive(Default)]
+pub struct DonorRecord {
+    pub staking_account: Pubkey,
+    pub donor: Pubkey,
+    pub total_donated: u64,
+    pub last_donated_time: u64,
+}
+
+impl DonorRecord{
+    pub fn address(staking_account: &Pubkey, donor: &Pubkey) -> Pubkey
+    {
+        let (address, _bump) = Pubkey::find_program_address(
+            &[STAKING_DONOR_PDA_SEED, staking_account.as_ref(), donor.as_ref()], &crate::ID);
+        address
+    }
+}
+
+//deposit of a user in a fixed term product, closed on redeem,
+//nonce is picked by the user so it can hold several deposits in one product
+#[account]
+#[derive(Default)]
+pub struct FixedTermDeposit {
+    pub product: Pubkey,
+    pub owner: Pubkey,
+    pub nonce: u64,
+    pub principal: u64,
+    pub interest: u64,
+    pub start_time: u64,
+    pub maturity_time: u64,
 }
 
 
 #[derive(Accounts)]
 pub struct Initialize<'info> {
     #[account(init,
-        seeds = [STAKING_PDA_SEED, authority.key.as_ref(), mint_address.key.as_ref()],
+        seeds = [STAKING_PDA_SEED, authority.key.as_ref(), mint_address.to_account_info().key.as_ref()],
         bump,
         payer = authority, 
         space = 10240)]
@@ -84,143 +749,1776 @@ pub struct Initialize<'info> {
         seeds = [STAKING_REWARDER_PDA_SEED, staking_data.to_account_info().key.as_ref()],
         bump,
         payer = authority,
-        token::mint = mint_address,
+        token::mint = reward_mint,
         token::authority = authority,
     )]    
     pub rewarder_account: Account<'info, TokenAccount>,
 
-    #[account(mut, signer)]
+    #[account(mut, signer)]
+    pub authority: AccountInfo<'info>,
+
+    pub mint_address: Account<'info, Mint>,
+
+    //same as mint_address when rewards are paid in the staking token
+    pub reward_mint: Account<'info, Mint>,
+
+    pub system_program: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+impl<'info> Initialize<'info> {
+    pub fn into_set_escrow_authority_context(&self) -> CpiContext<'_, '_, '_, 'info, SetAuthority<'info>> {
+        let cpi_accounts = SetAuthority {
+            account_or_mint: self.escrow_account.to_account_info().clone(),
+            current_authority: self.authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+    pub fn into_set_rewarder_authority_context(&self) -> CpiContext<'_, '_, '_, 'info, SetAuthority<'info>> {
+        let cpi_accounts = SetAuthority {
+            account_or_mint: self.rewarder_account.to_account_info().clone(),
+            current_authority: self.authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct StakerHistoryEntry {
+    pub time:   u64,
+    pub action: u8,  //0 staking, 1: unstaking, 2: claim rewarding, 3: withdraw unbonded, 4: cancel unbonding, 5: penalty, 6: claim stream rewarding,
+                     //7: receipt transfer out, 8: receipt transfer in, 9: receipt redeemed, 10: compound, 11: withdraw vested
+    pub amount: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct StreamPosition {
+    pub reward_per_token_paid: u128,
+    pub gained_reward: u64,
+}
+
+//amount of one unstake waiting for its end
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct UnbondingRequest {
+    pub amount: u64,
+    pub end: u64,
+}
+
+//schedule of an earlier claim still vesting on its own end
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct VestingTranche {
+    pub amount: u64,
+    pub start: u64,
+    pub cliff_end: u64,
+    pub end: u64,
+    pub released: u64,
+}
+
+//when a stream position was last settled, what it earned inside min stake period,
+//the reward of its receipts and its cap headroom, kept like the same fields of StakingState
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct StreamSettlement {
+    pub settled_time: u64,
+    pub pending_reward: u64,
+    pub receipt_reward: u64,
+    pub away_receipt_reward: u64,
+    pub cap_headroom: u128,
+}
+
+#[account]
+#[derive(Default)]
+pub struct StakingState {
+    pub staking_account: Pubkey,
+    pub my_crc: u32,  //set only on states created before the stakers list was dropped
+    pub mint_address: Pubkey,    
+    pub onwer_address: Pubkey,
+    pub total_staked: u64,
+    pub total_rewarded: u64,
+    pub last_staked: u64,
+    pub last_rewarded: u64,
+    pub history: Vec<StakerHistoryEntry>,
+
+    //reward_per_token of the pool when this position was last settled
+    pub reward_per_token_paid: u128,
+
+    //position
+    pub staked_time: u64,
+    pub gained_reward: u64,
+
+    //unstaked amount waiting in unbonding_requests, unbonding_end is the end of the last request
+    pub unbonding_amount: u64,
+    pub unbonding_end: u64,
+
+    //position in each reward stream, indexed by RewardStream::index
+    pub stream_positions: Vec<StreamPosition>,
+
+    //lockup, locked_amount can't be unstaked before lock_end and earns with lock_boost on top
+    pub locked_amount: u64,
+    pub lock_end: u64,
+    pub lock_boost: u64,
+
+    //part of total_staked which has receipts out, the rest was staked before receipts were enabled
+    pub receipt_staked: u64,
+
+    //lets anybody compound the position
+    pub auto_compound: bool,
+
+    //vesting schedule of the last claim, the ones before it keep vesting in vesting_tranches
+    pub vesting_amount: u64,
+    pub vesting_start: u64,
+    pub vesting_cliff_end: u64,
+    pub vesting_end: u64,
+    pub vesting_released: u64,
+    //unlocked by earlier schedules and not withdrawn yet
+    pub vested_amount: u64,
+
+    //when reward_per_token_paid was checkpointed, the cap headroom accrues from there
+    pub settled_time: u64,
+
+    //earned inside min stake period, credited once the position passed it
+    pub pending_reward: u64,
+    //indexed like stream_positions, kept apart since StreamPosition is laid out in existing accounts
+    pub stream_settlements: Vec<StreamSettlement>,
+
+    //one request per unstake, each matures on its own end
+    pub unbonding_requests: Vec<UnbondingRequest>,
+
+    //part of total_staked on the penalty clock and its start weighted by amount, apart from staked_time
+    //so a top-up or a cancelled unbonding doesn't put matured principal back under the penalty
+    pub penalty_amount: u64,
+    pub penalty_start: u64,
+
+    //token account the receipts of this position go to, redeem_receipt measures what the owner gave away against it
+    pub receipt_account: Pubkey,
+
+    //earlier claims still locked, each keeps the schedule it was claimed with
+    pub vesting_tranches: Vec<VestingTranche>,
+
+    //reward of the receipt-backed part settled since the owner last showed the receipts it holds,
+    //every receipt of the position has the same share of it
+    pub receipt_reward: u64,
+    //receipts the owner didn't hold at that time and the reward they are owed, redeem_receipt pays it to their holder
+    pub away_receipts: u64,
+    pub away_receipt_reward: u64,
+
+    //apy_max cap accrued by the position and not used by its settled reward yet, in CAP_HEADROOM_SCALE units,
+    //so the cap doesn't depend on how often the position is settled
+    pub cap_headroom: u128,
+}
+
+impl StakingState{
+    //588 entries filled the account before the fields after history were added
+    pub const MAX_HISTORY: usize = 512;
+    pub const MAX_UNBONDING_REQUESTS: usize = 16;
+    pub const MAX_VESTING_TRANCHES: usize = 16;
+    pub const SPACE: usize = 10240;
+
+    //a state created before the fields after history were added can hold more history than fits in front of them,
+    //past the end of the account they read as zero and history is cut to MAX_HISTORY so it fits again on store
+    pub fn load_unchecked(info: &AccountInfo) -> Result<StakingState>{
+        let data = info.try_borrow_data()?;
+        StakingState::decode_unchecked(&data)
+    }
+
+    pub fn decode_unchecked(data: &[u8]) -> Result<StakingState>{
+        let mut padded = data.to_vec();
+        padded.resize(data.len() + StakingState::SPACE, 0);
+        let mut stake_state = StakingState::try_deserialize(&mut &padded[..])?;
+        if stake_state.history.len() > StakingState::MAX_HISTORY {
+            let cut = stake_state.history.len() - StakingState::MAX_HISTORY;
+            stake_state.history.drain(..cut);
+        }
+        Ok(stake_state)
+    }
+
+    pub fn store(&self, info: &AccountInfo) -> ProgramResult{
+        self.check_size()?;
+        let mut data = info.try_borrow_mut_data()?;
+        let dst: &mut [u8] = &mut data;
+        let mut cursor = std::io::Cursor::new(dst);
+        self.try_serialize(&mut cursor)
+    }
+
+    pub fn check_size(&self) -> Result<()>{
+        let size = self.try_to_vec().map_err(|_| StakingErrors::StakingStateTooLarge)?.len() + 8;
+        if size > StakingState::SPACE {
+            return Err(StakingErrors::StakingStateTooLarge.into());
+        }
+        Ok(())
+    }
+
+    pub fn address(staking_account: &Pubkey, owner: &Pubkey) -> Pubkey
+    {
+        let (address, _bump) = Pubkey::find_program_address(
+            &[STAKER_PDA_SEED, staking_account.as_ref(), owner.as_ref()], &crate::ID);
+        address
+    }
+
+    pub fn vesting_address(stake_state_account: &Pubkey) -> Pubkey
+    {
+        let (address, _bump) = Pubkey::find_program_address(
+            &[STAKING_VESTING_PDA_SEED, stake_state_account.as_ref()], &crate::ID);
+        address
+    }
+
+    //unlocked and not withdrawn yet
+    pub fn withdrawable_vested(&self, now_ts: u64) -> Result<u64>
+    {
+        let mut withdrawable = self.vested_amount;
+        for tranche in self.vesting_tranches.iter() {
+            let unlocked = calculate_vested(tranche.amount, tranche.start, tranche.cliff_end, tranche.end, now_ts)?;
+            withdrawable = withdrawable + unlocked - tranche.released;
+        }
+        let unlocked = calculate_vested(self.vesting_amount, self.vesting_start, self.vesting_cliff_end, self.vesting_end, now_ts)?;
+        Ok(withdrawable + unlocked - self.vesting_released)
+    }
+
+    //tranches which reached their end move what is left of them to vested_amount
+    fn fold_vested_tranches(&mut self, now_ts: u64)
+    {
+        let ended: u64 = self.vesting_tranches.iter().filter(|tranche| tranche.end <= now_ts)
+            .map(|tranche| tranche.amount - tranche.released).sum();
+        self.vesting_tranches.retain(|tranche| tranche.end > now_ts);
+        self.vested_amount = self.vested_amount + ended;
+    }
+
+    //the claimed amount starts its own schedule, the running one keeps its end in vesting_tranches
+    pub fn add_vesting(&mut self, amount: u64, vesting_duration: u64, vesting_cliff: u64, now_ts: u64) -> Result<()>
+    {
+        self.fold_vested_tranches(now_ts);
+        if self.vesting_end <= now_ts {
+            self.vested_amount = self.vested_amount + self.vesting_amount - self.vesting_released;
+        } else {
+            if self.vesting_tranches.len() >= StakingState::MAX_VESTING_TRANCHES {
+                return Err(StakingErrors::ReachedMaxVestingTranches.into());
+            }
+            self.vesting_tranches.push(VestingTranche{
+                amount: self.vesting_amount,
+                start: self.vesting_start,
+                cliff_end: self.vesting_cliff_end,
+                end: self.vesting_end,
+                released: self.vesting_released,
+            });
+        }
+        self.vesting_amount = amount;
+        self.vesting_start = now_ts;
+        self.vesting_cliff_end = now_ts + vesting_cliff;
+        self.vesting_end = now_ts + vesting_duration;
+        self.vesting_released = 0;
+        Ok(())
+    }
+
+    pub fn release_vested(&mut self, now_ts: u64) -> Result<u64>
+    {
+        let amount = self.withdrawable_vested(now_ts)?;
+        for tranche in self.vesting_tranches.iter_mut() {
+            tranche.released = calculate_vested(tranche.amount, tranche.start, tranche.cliff_end, tranche.end, now_ts)?;
+        }
+        self.vesting_released = calculate_vested(self.vesting_amount, self.vesting_start, self.vesting_cliff_end, self.vesting_end, now_ts)?;
+        self.vested_amount = 0;
+        self.fold_vested_tranches(now_ts);
+        Ok(amount)
+    }
+
+    pub fn add_history(&mut self, time: u64, action: u8, amount: u64)-> ()
+    {
+        while self.history.len() >= StakingState::MAX_HISTORY {
+            self.history.remove(0);
+        }
+        self.history.push(StakerHistoryEntry{
+            time: time, action: action, amount: amount
+        });
+    }
+
+    pub fn effective_staked(&self) -> u64
+    {
+        self.total_staked + self.lock_boost
+    }
+
+    pub fn unlocked_amount(&self, now_ts: u64) -> u64
+    {
+        if now_ts >= self.lock_end {
+            return self.total_staked;
+        }
+        self.total_staked - self.locked_amount
+    }
+
+    pub fn lock_expired(&self, now_ts: u64) -> bool
+    {
+        self.lock_end != 0 && now_ts >= self.lock_end
+    }
+
+    //drops an expired lock, returns the boost to take off the pool,
+    //the position has to be settled first since it earned with the boost until lock_end
+    pub fn expire_lock(&mut self, now_ts: u64) -> u64
+    {
+        if !self.lock_expired(now_ts) {
+            return 0;
+        }
+        let boost = self.lock_boost;
+        self.locked_amount = 0;
+        self.lock_end = 0;
+        self.lock_boost = 0;
+        boost
+    }
+
+    pub fn stream_reward_per_token_paid(&self, index: u8) -> u128
+    {
+        match self.stream_positions.get(index as usize) {
+            Some(position) => position.reward_per_token_paid,
+            None => 0,
+        }
+    }
+
+    //a missing position was never settled, so it starts from a zero checkpoint like the stream itself
+    pub fn stream_position(&mut self, index: u8) -> usize
+    {
+        while self.stream_positions.len() <= index as usize {
+            self.stream_positions.push(StreamPosition::default());
+        }
+        while self.stream_settlements.len() <= index as usize {
+            self.stream_settlements.push(StreamSettlement::default());
+        }
+        index as usize
+    }
+
+    //unbonding from before the requests were kept is a single request
+    fn load_unbonding_requests(&mut self)
+    {
+        if self.unbonding_requests.is_empty() && self.unbonding_amount > 0 {
+            self.unbonding_requests.push(UnbondingRequest{
+                amount: self.unbonding_amount, end: self.unbonding_end
+            });
+        }
+    }
+
+    pub fn add_unbonding(&mut self, amount: u64, end: u64) -> Result<()>
+    {
+        self.load_unbonding_requests();
+        if self.unbonding_requests.len() >= StakingState::MAX_UNBONDING_REQUESTS {
+            return Err(StakingErrors::ReachedMaxUnbondingRequests.into());
+        }
+        self.unbonding_requests.push(UnbondingRequest{
+            amount: amount, end: end
+        });
+        self.unbonding_amount = self.unbonding_amount.checked_add(amount).ok_or(StakingErrors::MathOverflow)?;
+        self.unbonding_end = end;
+        Ok(())
+    }
+
+    //positions from before the receipt account was recorded got their receipts in the associated token account
+    pub fn owner_receipt_account(&self, receipt_mint: &Pubkey) -> Pubkey
+    {
+        if self.receipt_account != Pubkey::default() {
+            return self.receipt_account;
+        }
+        get_associated_token_address(&self.onwer_address, receipt_mint)
+    }
+
+    //a position keeps its receipts in one account, the first one they go to
+    pub fn record_receipt_account(&mut self, receipt_account: &Pubkey, receipt_mint: &Pubkey) -> Result<()>
+    {
+        if self.receipt_account == Pubkey::default() && self.receipt_staked == 0 {
+            self.receipt_account = *receipt_account;
+        }
+        if self.owner_receipt_account(receipt_mint) != *receipt_account {
+            return Err(StakingErrors::InvalidReceiptAccount.into());
+        }
+        Ok(())
+    }
+
+    //part of a settled reward earned by the receipt-backed part of the position
+    fn receipt_share(&self, reward: u64) -> Result<u64>
+    {
+        if self.total_staked == 0 || self.receipt_staked == 0 {
+            return Ok(0);
+        }
+        Ok(mul_div(reward as u128, self.receipt_staked.min(self.total_staked) as u128, self.total_staked as u128)? as u64)
+    }
+
+    //settled pool reward, the receipt-backed part waits in receipt_reward for the receipt holders
+    pub fn add_settled_reward(&mut self, gained: u64) -> Result<()>
+    {
+        let receipt_part = self.receipt_share(gained)?;
+        self.receipt_reward = self.receipt_reward.checked_add(receipt_part).ok_or(StakingErrors::MathOverflow)?;
+        self.gained_reward = self.gained_reward.checked_add(gained - receipt_part).ok_or(StakingErrors::MathOverflow)?;
+        Ok(())
+    }
+
+    pub fn add_settled_stream_reward(&mut self, position: usize, gained: u64) -> Result<()>
+    {
+        let receipt_part = self.receipt_share(gained)?;
+        let settlement = &mut self.stream_settlements[position];
+        settlement.receipt_reward = settlement.receipt_reward.checked_add(receipt_part).ok_or(StakingErrors::MathOverflow)?;
+        let stream_position = &mut self.stream_positions[position];
+        stream_position.gained_reward = stream_position.gained_reward.checked_add(gained - receipt_part).ok_or(StakingErrors::MathOverflow)?;
+        Ok(())
+    }
+
+    //the owner holds held of the receipts: it takes their share of receipt_reward and the away reward of the ones
+    //it got back, the rest is owed to the receipts it doesn't hold, in the pool and every stream.
+    //has to run before receipt_staked changes, receipts can move with plain token transfers in between
+    pub fn checkpoint_receipts(&mut self, held: u64) -> Result<()>
+    {
+        let away = self.receipt_staked - held.min(self.receipt_staked);
+        let (owner_part, away_reward) = split_receipt_reward(self.receipt_reward, self.away_receipt_reward,
+            held, self.receipt_staked, away, self.away_receipts)?;
+        self.gained_reward = self.gained_reward.checked_add(owner_part).ok_or(StakingErrors::MathOverflow)?;
+        self.receipt_reward = 0;
+        self.away_receipt_reward = away_reward;
+
+        for index in 0..self.stream_settlements.len() {
+            let settlement = self.stream_settlements[index].clone();
+            let (owner_part, away_reward) = split_receipt_reward(settlement.receipt_reward, settlement.away_receipt_reward,
+                held, self.receipt_staked, away, self.away_receipts)?;
+            let position = self.stream_position(index as u8);
+            self.stream_positions[position].gained_reward = self.stream_positions[position].gained_reward.checked_add(owner_part).ok_or(StakingErrors::MathOverflow)?;
+            self.stream_settlements[position].receipt_reward = 0;
+            self.stream_settlements[position].away_receipt_reward = away_reward;
+        }
+        self.away_receipts = away;
+        Ok(())
+    }
+
+    //the share of the away reward owed to amount of the receipts the owner doesn't hold, in the pool and every stream,
+    //runs after checkpoint_receipts
+    pub fn take_away_receipt_reward(&mut self, amount: u64) -> Result<(u64, Vec<u64>)>
+    {
+        if self.away_receipts == 0 {
+            return Ok((0, vec![]));
+        }
+        let amount = amount.min(self.away_receipts);
+        let taken = mul_div(self.away_receipt_reward as u128, amount as u128, self.away_receipts as u128)? as u64;
+        self.away_receipt_reward = self.away_receipt_reward - taken;
+
+        let mut stream_taken = vec![];
+        for settlement in self.stream_settlements.iter_mut() {
+            let moved = mul_div(settlement.away_receipt_reward as u128, amount as u128, self.away_receipts as u128)? as u64;
+            settlement.away_receipt_reward = settlement.away_receipt_reward - moved;
+            stream_taken.push(moved);
+        }
+        self.away_receipts = self.away_receipts - amount;
+        Ok((taken, stream_taken))
+    }
+
+    //away reward taken by the holder of the receipts becomes gained reward of its position
+    pub fn add_gained_reward(&mut self, reward: &(u64, Vec<u64>)) -> Result<()>
+    {
+        self.gained_reward = self.gained_reward.checked_add(reward.0).ok_or(StakingErrors::MathOverflow)?;
+        for (index, amount) in reward.1.iter().enumerate() {
+            let position = self.stream_position(index as u8);
+            self.stream_positions[position].gained_reward = self.stream_positions[position].gained_reward.checked_add(*amount).ok_or(StakingErrors::MathOverflow)?;
+        }
+        Ok(())
+    }
+
+    //the share of the reward still pending inside min stake period goes along with amount, in the pool and every stream,
+    //has to run before total_staked changes
+    pub fn move_pending_share(&mut self, to: &mut StakingState, amount: u64) -> Result<()>
+    {
+        if self.total_staked == 0 {
+            return Ok(());
+        }
+        let moved = mul_div(self.pending_reward as u128, amount as u128, self.total_staked as u128)? as u64;
+        self.pending_reward = self.pending_reward - moved;
+        to.pending_reward = to.pending_reward.checked_add(moved).ok_or(StakingErrors::MathOverflow)?;
+
+        for index in 0..self.stream_settlements.len() {
+            let pending = self.stream_settlements[index].pending_reward;
+            let stream_moved = mul_div(pending as u128, amount as u128, self.total_staked as u128)? as u64;
+            self.stream_settlements[index].pending_reward = pending - stream_moved;
+            let position = to.stream_position(index as u8);
+            to.stream_settlements[position].pending_reward = to.stream_settlements[position].pending_reward.checked_add(stream_moved).ok_or(StakingErrors::MathOverflow)?;
+        }
+        Ok(())
+    }
+
+    //the cap headroom accrued by amount of the position leaves with it, in the pool and every stream,
+    //to the position it moves to or dropped when it is unstaked. has to run before total_staked changes
+    pub fn move_cap_headroom(&mut self, mut to: Option<&mut StakingState>, amount: u64) -> Result<()>
+    {
+        if self.total_staked == 0 {
+            return Ok(());
+        }
+        let amount = amount.min(self.total_staked);
+        let moved = mul_div(self.cap_headroom, amount as u128, self.total_staked as u128)?;
+        self.cap_headroom = self.cap_headroom - moved;
+        if let Some(to) = to.as_mut() {
+            to.cap_headroom = to.cap_headroom.checked_add(moved).ok_or(StakingErrors::MathOverflow)?;
+        }
+
+        for index in 0..self.stream_settlements.len() {
+            let cap_headroom = self.stream_settlements[index].cap_headroom;
+            let stream_moved = mul_div(cap_headroom, amount as u128, self.total_staked as u128)?;
+            self.stream_settlements[index].cap_headroom = cap_headroom - stream_moved;
+            if let Some(to) = to.as_mut() {
+                let position = to.stream_position(index as u8);
+                to.stream_settlements[position].cap_headroom = to.stream_settlements[position].cap_headroom.checked_add(stream_moved).ok_or(StakingErrors::MathOverflow)?;
+            }
+        }
+        Ok(())
+    }
+
+    //takes out every request which reached its end, the later ones keep waiting
+    pub fn release_unbonded(&mut self, now_ts: u64) -> u64
+    {
+        self.load_unbonding_requests();
+        let matured: u64 = self.unbonding_requests.iter().filter(|request| request.end <= now_ts).map(|request| request.amount).sum();
+        self.unbonding_requests.retain(|request| request.end > now_ts);
+        self.unbonding_amount = self.unbonding_amount - matured;
+        if self.unbonding_requests.is_empty() {
+            self.unbonding_end = 0;
+        }
+        matured
+    }
+
+    pub fn cancel_unbonding(&mut self) -> u64
+    {
+        let amount = self.unbonding_amount;
+        self.unbonding_requests = vec![];
+        self.unbonding_amount = 0;
+        self.unbonding_end = 0;
+        amount
+    }
+
+    //part of total_staked still inside penalty_period and its start,
+    //positions staked before the penalty clock was kept apart count it from staked_time
+    pub fn penalty_tranche(&self, penalty_period: u64, now_ts: u64) -> (u64, u64)
+    {
+        let (amount, start) = if self.penalty_start == 0 {
+            (self.total_staked, self.staked_time)
+        } else {
+            (self.penalty_amount, self.penalty_start)
+        };
+        if now_ts >= start.saturating_add(penalty_period) {
+            return (0, start);
+        }
+        (amount.min(self.total_staked), start)
+    }
+
+    //amount staked at now_ts starts on the penalty clock, has to run before total_staked grows
+    pub fn add_penalty_tranche(&mut self, amount: u64, penalty_period: u64, now_ts: u64) -> Result<()>
+    {
+        let (tranche, start) = self.penalty_tranche(penalty_period, now_ts);
+        self.penalty_start = calculate_weighted_time(tranche, start, amount, now_ts)?;
+        self.penalty_amount = tranche.checked_add(amount).ok_or(StakingErrors::MathOverflow)?;
+        Ok(())
+    }
+
+    //amount leaves the position, matured principal goes first, returns the part of amount
+    //still inside penalty_period and its start, has to run before total_staked shrinks
+    pub fn take_penalized(&mut self, amount: u64, penalty_period: u64, now_ts: u64) -> (u64, u64)
+    {
+        let (tranche, start) = self.penalty_tranche(penalty_period, now_ts);
+        let penalized = amount.saturating_sub(self.total_staked - tranche);
+        self.penalty_amount = tranche - penalized;
+        self.penalty_start = start;
+        (penalized, start)
+    }
+
+    pub fn passed_min_stake_period(&self, min_stake_period: u64, now_ts: u64) -> bool
+    {
+        now_ts >= self.staked_time + min_stake_period
+    }
+
+    //amount joins the position at now_ts, the staked time of what was there already still counts
+    pub fn add_staked(&mut self, amount: u64, now_ts: u64) -> Result<()>
+    {
+        self.staked_time = calculate_weighted_time(self.total_staked, self.staked_time, amount, now_ts)?;
+        self.total_staked = self.total_staked.checked_add(amount).ok_or(StakingErrors::MathOverflow)?;
+        Ok(())
+    }
+}
+
+
+#[derive(Accounts)]
+pub struct InitializeStakeState<'info> {
+    pub staking_data: ProgramAccount<'info, StakingData>,
+
+    #[account(init, 
+        seeds = [STAKER_PDA_SEED, staking_data.to_account_info().key.as_ref(), authority.key.as_ref()],
+        bump,
+        payer = authority, 
+        //space = 8 + 32 + 4 + 32 + 32 + 8 + 8 + 8 + 8
+        space = 10240
+    )]
+    pub stake_state_account: ProgramAccount<'info, StakingState>,
+
+    #[account(mut, signer)]
+    pub authority: AccountInfo<'info>,
+
+    system_program: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+
+#[derive(Accounts)]
+pub struct Staking<'info> {
+    #[account(mut)]
+    pub staking_data: ProgramAccount<'info, StakingData>,
+
+    #[account(mut,
+        constraint = *staking_data.to_account_info().key == stake_state_account.staking_account,
+        constraint = *stake_state_account.to_account_info().key == StakingState::address(staking_data.to_account_info().key, authority.key),
+    )]
+    pub stake_state_account: ProgramAccount<'info, StakingState>,
+
+    #[account(mut,
+        constraint = staking_data.escrow_account == *escrow_account.to_account_info().key,
+    )]
+    pub escrow_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut,
+        constraint = *staker_account.to_account_info().owner == *token_program.key,
+        constraint = staker_account.mint == staking_data.mint_address,
+        constraint = staker_account.owner == *authority.key,
+    )]
+    pub staker_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    //receipt mint and the staker receipt account, unused when the pool has no receipts
+    #[account(mut,
+        constraint = !staking_data.receipt_enabled() || staking_data.receipt_mint == *receipt_mint.key,
+    )]
+    pub receipt_mint: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub receipt_account: AccountInfo<'info>,
+            
+    #[account(mut, signer,
+        constraint = stake_state_account.onwer_address == *authority.key,
+    )]
+    pub authority: AccountInfo<'info>,
+
+    pub staking_authority: AccountInfo<'info>,
+
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+impl<'info> Staking<'info> {
+    pub fn into_transfer_to_escrow_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.staker_account.to_account_info().clone(),
+            to: self.escrow_account.to_account_info().clone(),
+            authority: self.authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+    pub fn into_mint_receipt_context(&self) -> CpiContext<'_, '_, '_, 'info, MintTo<'info>> {
+        let cpi_accounts = MintTo {
+            mint: self.receipt_mint.clone(),
+            to: self.receipt_account.clone(),
+            authority: self.staking_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+
+#[derive(Accounts)]
+pub struct Funding<'info> {
+    #[account(mut)]
+    pub staking_data: ProgramAccount<'info, StakingData>,
+
+    #[account(mut,
+        constraint = staking_data.rewarder_account == *rewarder_account.to_account_info().key,
+    )]
+    pub rewarder_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut,
+        constraint = *funder_account.to_account_info().owner == *token_program.key,
+        constraint = funder_account.mint == staking_data.reward_mint(),
+        constraint = funder_account.owner == *authority.key,
+    )]
+    pub funder_account: Account<'info, anchor_spl::token::TokenAccount>,
+            
+    #[account(mut, 
+        signer,
+        constraint = staking_data.funder_authority == *authority.key,
+    )]
+    pub authority: AccountInfo<'info>,
+
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+impl<'info> Funding<'info> {
+    pub fn into_transfer_to_rewarder_context(
+        &self,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.funder_account.to_account_info().clone(),
+            to: self.rewarder_account.to_account_info().clone(),
+            authority: self.authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+
+#[derive(Accounts)]
+pub struct Unstaking<'info> {
+    #[account(mut)]
+    pub staking_data: ProgramAccount<'info, StakingData>,
+
+    #[account(mut,
+        constraint = *staking_data.to_account_info().key == stake_state_account.staking_account,
+        constraint = *stake_state_account.to_account_info().key == StakingState::address(staking_data.to_account_info().key, authority.key),
+    )]
+    pub stake_state_account: ProgramAccount<'info, StakingState>,
+
+    #[account(mut,
+        constraint = staking_data.escrow_account == *escrow_account.to_account_info().key,        
+    )]
+    pub escrow_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut,
+        constraint = staking_data.rewarder_account == *rewarder_account.to_account_info().key,
+    )]
+    pub rewarder_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut,
+        constraint = *reclaimer.to_account_info().owner == *token_program.key,
+        constraint = reclaimer.mint == staking_data.mint_address,
+        constraint = reclaimer.owner == *authority.key,
+    )]
+    pub reclaimer: Account<'info, anchor_spl::token::TokenAccount>,
+
+    //receives the reward paid out on a full unstake
+    #[account(mut,
+        constraint = *reward_account.to_account_info().owner == *token_program.key,
+        constraint = reward_account.mint == staking_data.reward_mint(),
+        constraint = reward_account.owner == *authority.key,
+    )]
+    pub reward_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut,
+        constraint = staking_data.mint_address == *mint_address.key,
+    )]
+    pub mint_address: AccountInfo<'info>,
+
+    //receives the penalty when penalty_mode is treasury, unused otherwise
+    #[account(mut,
+        constraint = staking_data.penalty_mode != PENALTY_MODE_TREASURY || staking_data.penalty_treasury == *treasury_account.key,
+    )]
+    pub treasury_account: AccountInfo<'info>,
+
+    //receipt mint and the staker receipt account, unused when the pool has no receipts
+    #[account(mut,
+        constraint = !staking_data.receipt_enabled() || staking_data.receipt_mint == *receipt_mint.key,
+    )]
+    pub receipt_mint: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub receipt_account: AccountInfo<'info>,
+
+    #[account(mut, signer,
+        constraint = stake_state_account.onwer_address == *authority.key,
+    )]
+    pub authority: AccountInfo<'info>,
+
+    pub staking_authority: AccountInfo<'info>,    
+
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+impl<'info> Unstaking<'info> {
+    pub fn into_burn_receipt_context(
+        &self,
+    ) -> CpiContext<'_, '_, '_, 'info, Burn<'info>> {
+        let cpi_accounts = Burn {
+            mint: self.receipt_mint.clone(),
+            to: self.receipt_account.clone(),
+            authority: self.authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+    pub fn into_transfer_penalty_context(
+        &self,
+        to: AccountInfo<'info>,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.escrow_account.to_account_info().clone(),
+            to: to,
+            authority: self.staking_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+    pub fn into_burn_penalty_context(
+        &self,
+    ) -> CpiContext<'_, '_, '_, 'info, Burn<'info>> {
+        let cpi_accounts = Burn {
+            mint: self.mint_address.clone(),
+            to: self.escrow_account.to_account_info().clone(),
+            authority: self.staking_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+    pub fn into_transfer_to_staker_context(
+        &self,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.escrow_account.to_account_info().clone(),
+            to: self.reclaimer.to_account_info().clone(),
+            authority: self.staking_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+    pub fn into_transfer_from_rewarder_to_staker_context(
+        &self,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.rewarder_account.to_account_info().clone(),
+            to: self.reward_account.to_account_info().clone(),
+            authority: self.staking_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+
+#[derive(Accounts)]
+pub struct Claiming<'info> {
+    #[account(mut)]
+    pub staking_data: ProgramAccount<'info, StakingData>,
+
+    #[account(mut,
+        constraint = *staking_data.to_account_info().key == stake_state_account.staking_account,
+        constraint = *stake_state_account.to_account_info().key == StakingState::address(staking_data.to_account_info().key, authority.key),
+    )]
+    pub stake_state_account: ProgramAccount<'info, StakingState>,
+
+    #[account(mut,
+        constraint = staking_data.rewarder_account == *rewarder_account.to_account_info().key,
+    )]
+    pub rewarder_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut,
+        constraint = *claimer.to_account_info().owner == *token_program.key,
+        constraint = claimer.mint == staking_data.reward_mint(),
+        constraint = claimer.owner == *authority.key,
+    )]
+    pub claimer: Account<'info, anchor_spl::token::TokenAccount>,
+
+    //receives the claim when the pool has vesting, unused otherwise
+    #[account(mut,
+        constraint = staking_data.vesting_duration == 0 || *vesting_account.key == StakingState::vesting_address(stake_state_account.to_account_info().key),
+    )]
+    pub vesting_account: AccountInfo<'info>,
+
+    //receipts of the position the owner still holds, checked by checkpoint_receipts, unused on pools without receipts
+    pub owner_receipt_account: AccountInfo<'info>,
+
+    #[account(mut, signer,
+        constraint = stake_state_account.onwer_address == *authority.key,
+    )]
+    pub authority: AccountInfo<'info>,
+
+    pub staking_authority: AccountInfo<'info>,    
+
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+impl<'info> Claiming<'info> {
+    pub fn into_transfer_to_claimer_context(
+        &self,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.rewarder_account.to_account_info().clone(),
+            to: self.claimer.to_account_info().clone(),
+            authority: self.staking_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+    pub fn into_transfer_to_vesting_context(
+        &self,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.rewarder_account.to_account_info().clone(),
+            to: self.vesting_account.clone(),
+            authority: self.staking_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+#[derive(Accounts)]
+pub struct WithdrawUnbonded<'info> {
+    #[account(mut)]
+    pub staking_data: ProgramAccount<'info, StakingData>,
+
+    #[account(mut,
+        constraint = *staking_data.to_account_info().key == stake_state_account.staking_account,
+        constraint = *stake_state_account.to_account_info().key == StakingState::address(staking_data.to_account_info().key, authority.key),
+    )]
+    pub stake_state_account: ProgramAccount<'info, StakingState>,
+
+    #[account(mut,
+        constraint = staking_data.escrow_account == *escrow_account.to_account_info().key,
+    )]
+    pub escrow_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut,
+        constraint = *reclaimer.to_account_info().owner == *token_program.key,
+        constraint = reclaimer.mint == staking_data.mint_address,
+        constraint = reclaimer.owner == *authority.key,
+    )]
+    pub reclaimer: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut, signer,
+        constraint = stake_state_account.onwer_address == *authority.key,
+    )]
+    pub authority: AccountInfo<'info>,
+
+    pub staking_authority: AccountInfo<'info>,
+
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+impl<'info> WithdrawUnbonded<'info> {
+    pub fn into_transfer_to_staker_context(
+        &self,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.escrow_account.to_account_info().clone(),
+            to: self.reclaimer.to_account_info().clone(),
+            authority: self.staking_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+#[derive(Accounts)]
+pub struct CancelUnbonding<'info> {
+    #[account(mut)]
+    pub staking_data: ProgramAccount<'info, StakingData>,
+
+    #[account(mut,
+        constraint = *staking_data.to_account_info().key == stake_state_account.staking_account,
+        constraint = *stake_state_account.to_account_info().key == StakingState::address(staking_data.to_account_info().key, authority.key),
+    )]
+    pub stake_state_account: ProgramAccount<'info, StakingState>,
+
+    #[account(signer,
+        constraint = stake_state_account.onwer_address == *authority.key,
+    )]
+    pub authority: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateStaker<'info> {
+    //loaded by migrate_staker, a legacy stakers list may not fit next to the appended fields
+    #[account(mut)]
+    pub staking_data: AccountInfo<'info>,
+
+    //loaded by load_stake_state, a legacy history may not fit next to the appended fields either
+    #[account(mut)]
+    pub stake_state_account: AccountInfo<'info>,
+
+    #[account(signer)]
+    pub authority: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ChnageSetting<'info> {
+    #[account(mut)]
+    pub staking_data: ProgramAccount<'info, StakingData>,
+
+    #[account(mut, signer,
+        constraint = staking_data.initializer == *authority.key,
+    )]
+    pub authority: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPenalty<'info> {
+    #[account(mut)]
+    pub staking_data: ProgramAccount<'info, StakingData>,
+
+    //penalty_treasury token account, unused unless the penalty goes to the treasury
+    pub treasury_account: AccountInfo<'info>,
+
+    #[account(mut, signer,
+        constraint = staking_data.initializer == *authority.key,
+    )]
+    pub authority: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AddRewardStream<'info> {
+    #[account(mut)]
+    pub staking_data: ProgramAccount<'info, StakingData>,
+
+    #[account(init,
+        seeds = [STAKING_STREAM_PDA_SEED, staking_data.to_account_info().key.as_ref(), &[staking_data.reward_streams]],
+        bump,
+        payer = authority,
+        space = 512)]
+    pub reward_stream: ProgramAccount<'info, RewardStream>,
+
+    #[account(
+        init,
+        seeds = [STAKING_REWARDER_PDA_SEED, reward_stream.to_account_info().key.as_ref()],
+        bump,
+        payer = authority,
+        token::mint = reward_mint,
+        token::authority = authority,
+    )]
+    pub rewarder_account: Account<'info, TokenAccount>,
+
+    pub funder_authority: AccountInfo<'info>,
+
+    pub reward_mint: Account<'info, Mint>,
+
+    #[account(mut, signer,
+        constraint = staking_data.initializer == *authority.key,
+    )]
+    pub authority: AccountInfo<'info>,
+
+    pub system_program: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+impl<'info> AddRewardStream<'info> {
+    pub fn into_set_rewarder_authority_context(&self) -> CpiContext<'_, '_, '_, 'info, SetAuthority<'info>> {
+        let cpi_accounts = SetAuthority {
+            account_or_mint: self.rewarder_account.to_account_info().clone(),
+            current_authority: self.authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+#[derive(Accounts)]
+pub struct FundRewardStream<'info> {
+    #[account(mut)]
+    pub staking_data: ProgramAccount<'info, StakingData>,
+
+    #[account(mut,
+        constraint = *staking_data.to_account_info().key == reward_stream.staking_account,
+    )]
+    pub reward_stream: ProgramAccount<'info, RewardStream>,
+
+    #[account(mut,
+        constraint = reward_stream.rewarder_account == *rewarder_account.to_account_info().key,
+    )]
+    pub rewarder_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut,
+        constraint = *funder_account.to_account_info().owner == *token_program.key,
+        constraint = funder_account.mint == reward_stream.reward_mint,
+        constraint = funder_account.owner == *authority.key,
+    )]
+    pub funder_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut,
+        signer,
+        constraint = reward_stream.funder_authority == *authority.key,
+    )]
+    pub authority: AccountInfo<'info>,
+
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+impl<'info> FundRewardStream<'info> {
+    pub fn into_transfer_to_rewarder_context(
+        &self,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.funder_account.to_account_info().clone(),
+            to: self.rewarder_account.to_account_info().clone(),
+            authority: self.authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+#[derive(Accounts)]
+pub struct ClaimingStream<'info> {
+    pub staking_data: ProgramAccount<'info, StakingData>,
+
+    #[account(mut,
+        constraint = *staking_data.to_account_info().key == stake_state_account.staking_account,
+        constraint = *stake_state_account.to_account_info().key == StakingState::address(staking_data.to_account_info().key, authority.key),
+    )]
+    pub stake_state_account: ProgramAccount<'info, StakingState>,
+
+    #[account(mut,
+        constraint = *staking_data.to_account_info().key == reward_stream.staking_account,
+    )]
+    pub reward_stream: ProgramAccount<'info, RewardStream>,
+
+    #[account(mut,
+        constraint = reward_stream.rewarder_account == *rewarder_account.to_account_info().key,
+    )]
+    pub rewarder_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut,
+        constraint = *claimer.to_account_info().owner == *token_program.key,
+        constraint = claimer.mint == reward_stream.reward_mint,
+        constraint = claimer.owner == *authority.key,
+    )]
+    pub claimer: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut, signer,
+        constraint = stake_state_account.onwer_address == *authority.key,
+    )]
+    pub authority: AccountInfo<'info>,
+
+    pub staking_authority: AccountInfo<'info>,
+
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+impl<'info> ClaimingStream<'info> {
+    pub fn into_transfer_to_claimer_context(
+        &self,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.rewarder_account.to_account_info().clone(),
+            to: self.claimer.to_account_info().clone(),
+            authority: self.staking_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+#[derive(Accounts)]
+pub struct AddFixedTermProduct<'info> {
+    #[account(mut)]
+    pub staking_data: ProgramAccount<'info, StakingData>,
+
+    #[account(init,
+        seeds = [STAKING_PRODUCT_PDA_SEED, staking_data.to_account_info().key.as_ref(), &[staking_data.fixed_term_products]],
+        bump,
+        payer = authority,
+        space = 256)]
+    pub product: ProgramAccount<'info, FixedTermProduct>,
+
+    #[account(mut, signer,
+        constraint = staking_data.initializer == *authority.key,
+    )]
+    pub authority: AccountInfo<'info>,
+
+    pub system_program: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct SetFixedTermProduct<'info> {
+    pub staking_data: ProgramAccount<'info, StakingData>,
+
+    #[account(mut,
+        constraint = *staking_data.to_account_info().key == product.staking_account,
+    )]
+    pub product: ProgramAccount<'info, FixedTermProduct>,
+
+    #[account(signer,
+        constraint = staking_data.initializer == *authority.key,
+    )]
+    pub authority: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, nonce: u64)]
+pub struct DepositFixedTerm<'info> {
+    #[account(mut)]
+    pub staking_data: ProgramAccount<'info, StakingData>,
+
+    #[account(mut,
+        constraint = *staking_data.to_account_info().key == product.staking_account,
+    )]
+    pub product: ProgramAccount<'info, FixedTermProduct>,
+
+    #[account(init,
+        seeds = [STAKING_DEPOSIT_PDA_SEED, product.to_account_info().key.as_ref(), authority.key.as_ref(), &nonce.to_le_bytes()],
+        bump,
+        payer = authority,
+        space = 256)]
+    pub deposit: ProgramAccount<'info, FixedTermDeposit>,
+
+    #[account(mut,
+        constraint = staking_data.escrow_account == *escrow_account.to_account_info().key,
+    )]
+    pub escrow_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(
+        constraint = staking_data.rewarder_account == *rewarder_account.to_account_info().key,
+    )]
+    pub rewarder_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut,
+        constraint = *staker_account.to_account_info().owner == *token_program.key,
+        constraint = staker_account.mint == staking_data.mint_address,
+        constraint = staker_account.owner == *authority.key,
+    )]
+    pub staker_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut, signer)]
+    pub authority: AccountInfo<'info>,
+
+    pub system_program: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+impl<'info> DepositFixedTerm<'info> {
+    pub fn into_transfer_to_escrow_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.staker_account.to_account_info().clone(),
+            to: self.escrow_account.to_account_info().clone(),
+            authority: self.authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+#[derive(Accounts)]
+pub struct RedeemFixedTerm<'info> {
+    #[account(mut)]
+    pub staking_data: ProgramAccount<'info, StakingData>,
+
+    #[account(mut,
+        constraint = *staking_data.to_account_info().key == product.staking_account,
+    )]
+    pub product: ProgramAccount<'info, FixedTermProduct>,
+
+    #[account(mut, close = authority,
+        constraint = deposit.product == *product.to_account_info().key,
+        constraint = deposit.owner == *authority.key,
+    )]
+    pub deposit: ProgramAccount<'info, FixedTermDeposit>,
+
+    #[account(mut,
+        constraint = staking_data.escrow_account == *escrow_account.to_account_info().key,
+    )]
+    pub escrow_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut,
+        constraint = staking_data.rewarder_account == *rewarder_account.to_account_info().key,
+    )]
+    pub rewarder_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut,
+        constraint = *reclaimer.to_account_info().owner == *token_program.key,
+        constraint = reclaimer.mint == staking_data.mint_address,
+        constraint = reclaimer.owner == *authority.key,
+    )]
+    pub reclaimer: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut,
+        constraint = *reward_account.to_account_info().owner == *token_program.key,
+        constraint = reward_account.mint == staking_data.reward_mint(),
+        constraint = reward_account.owner == *authority.key,
+    )]
+    pub reward_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut, signer)]
+    pub authority: AccountInfo<'info>,
+
+    pub staking_authority: AccountInfo<'info>,
+
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+impl<'info> RedeemFixedTerm<'info> {
+    pub fn into_transfer_to_staker_context(
+        &self,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.escrow_account.to_account_info().clone(),
+            to: self.reclaimer.to_account_info().clone(),
+            authority: self.staking_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+    pub fn into_transfer_from_rewarder_to_staker_context(
+        &self,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.rewarder_account.to_account_info().clone(),
+            to: self.reward_account.to_account_info().clone(),
+            authority: self.staking_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitVault<'info> {
+    #[account(mut)]
+    pub staking_data: ProgramAccount<'info, StakingData>,
+
+    #[account(init,
+        seeds = [STAKING_SHARE_MINT_PDA_SEED, staking_data.to_account_info().key.as_ref()],
+        bump,
+        payer = authority,
+        mint::decimals = mint_address.decimals,
+        mint::authority = staking_authority,
+    )]
+    pub share_mint: Account<'info, Mint>,
+
+    #[account(
+        constraint = staking_data.mint_address == *mint_address.to_account_info().key,
+    )]
+    pub mint_address: Account<'info, Mint>,
+
+    #[account(mut, signer,
+        constraint = staking_data.initializer == *authority.key,
+    )]
+    pub authority: AccountInfo<'info>,
+
+    #[account(
+        constraint = *staking_authority.key == StakingData::authority_address(staking_data.to_account_info().key),
+    )]
+    pub staking_authority: AccountInfo<'info>,
+
+    pub system_program: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Vault<'info> {
+    #[account(mut)]
+    pub staking_data: ProgramAccount<'info, StakingData>,
+
+    #[account(mut,
+        constraint = staking_data.escrow_account == *escrow_account.to_account_info().key,
+    )]
+    pub escrow_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut,
+        constraint = staking_data.rewarder_account == *rewarder_account.to_account_info().key,
+    )]
+    pub rewarder_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut,
+        constraint = staking_data.share_mint == *share_mint.to_account_info().key,
+    )]
+    pub share_mint: Account<'info, Mint>,
+
+    #[account(mut,
+        constraint = *staker_account.to_account_info().owner == *token_program.key,
+        constraint = staker_account.mint == staking_data.mint_address,
+        constraint = staker_account.owner == *authority.key,
+    )]
+    pub staker_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut,
+        constraint = *share_account.to_account_info().owner == *token_program.key,
+        constraint = share_account.mint == staking_data.share_mint,
+        constraint = share_account.owner == *authority.key,
+    )]
+    pub share_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(signer)]
+    pub authority: AccountInfo<'info>,
+
+    pub staking_authority: AccountInfo<'info>,
+
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+impl<'info> Vault<'info> {
+    pub fn into_transfer_to_escrow_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.staker_account.to_account_info().clone(),
+            to: self.escrow_account.to_account_info().clone(),
+            authority: self.authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+    pub fn into_transfer_to_staker_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.escrow_account.to_account_info().clone(),
+            to: self.staker_account.to_account_info().clone(),
+            authority: self.staking_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+    pub fn into_compound_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.rewarder_account.to_account_info().clone(),
+            to: self.escrow_account.to_account_info().clone(),
+            authority: self.staking_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+    pub fn into_mint_shares_context(&self) -> CpiContext<'_, '_, '_, 'info, MintTo<'info>> {
+        let cpi_accounts = MintTo {
+            mint: self.share_mint.to_account_info().clone(),
+            to: self.share_account.to_account_info().clone(),
+            authority: self.staking_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+    pub fn into_burn_shares_context(&self) -> CpiContext<'_, '_, '_, 'info, Burn<'info>> {
+        let cpi_accounts = Burn {
+            mint: self.share_mint.to_account_info().clone(),
+            to: self.share_account.to_account_info().clone(),
+            authority: self.authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+#[derive(Accounts)]
+pub struct CompoundVault<'info> {
+    #[account(mut)]
+    pub staking_data: ProgramAccount<'info, StakingData>,
+
+    #[account(mut,
+        constraint = staking_data.escrow_account == *escrow_account.to_account_info().key,
+    )]
+    pub escrow_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut,
+        constraint = staking_data.rewarder_account == *rewarder_account.to_account_info().key,
+    )]
+    pub rewarder_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(
+        constraint = staking_data.share_mint == *share_mint.to_account_info().key,
+    )]
+    pub share_mint: Account<'info, Mint>,
+
+    pub staking_authority: AccountInfo<'info>,
+
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+impl<'info> CompoundVault<'info> {
+    pub fn into_compound_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.rewarder_account.to_account_info().clone(),
+            to: self.escrow_account.to_account_info().clone(),
+            authority: self.staking_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitReceipt<'info> {
+    #[account(mut)]
+    pub staking_data: ProgramAccount<'info, StakingData>,
+
+    #[account(init,
+        seeds = [STAKING_RECEIPT_MINT_PDA_SEED, staking_data.to_account_info().key.as_ref()],
+        bump,
+        payer = authority,
+        mint::decimals = mint_address.decimals,
+        mint::authority = staking_authority,
+    )]
+    pub receipt_mint: Account<'info, Mint>,
+
+    #[account(
+        constraint = staking_data.mint_address == *mint_address.to_account_info().key,
+    )]
+    pub mint_address: Account<'info, Mint>,
+
+    #[account(mut, signer,
+        constraint = staking_data.initializer == *authority.key,
+    )]
+    pub authority: AccountInfo<'info>,
+
+    #[account(
+        constraint = *staking_authority.key == StakingData::authority_address(staking_data.to_account_info().key),
+    )]
+    pub staking_authority: AccountInfo<'info>,
+
+    pub system_program: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TransferReceipt<'info> {
+    #[account(mut)]
+    pub staking_data: ProgramAccount<'info, StakingData>,
+
+    #[account(mut,
+        constraint = *staking_data.to_account_info().key == stake_state_account.staking_account,
+        constraint = *stake_state_account.to_account_info().key == StakingState::address(staking_data.to_account_info().key, authority.key),
+    )]
+    pub stake_state_account: ProgramAccount<'info, StakingState>,
+
+    #[account(mut,
+        constraint = *staking_data.to_account_info().key == to_stake_state_account.staking_account,
+        constraint = *to_stake_state_account.to_account_info().key == StakingState::address(staking_data.to_account_info().key, &to_stake_state_account.onwer_address),
+        constraint = *to_stake_state_account.to_account_info().key != *stake_state_account.to_account_info().key,
+    )]
+    pub to_stake_state_account: ProgramAccount<'info, StakingState>,
+
+    #[account(mut,
+        constraint = receipt_account.mint == staking_data.receipt_mint,
+        constraint = receipt_account.owner == *authority.key,
+    )]
+    pub receipt_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut,
+        constraint = to_receipt_account.mint == staking_data.receipt_mint,
+        constraint = to_receipt_account.owner == to_stake_state_account.onwer_address,
+    )]
+    pub to_receipt_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(signer,
+        constraint = stake_state_account.onwer_address == *authority.key,
+    )]
+    pub authority: AccountInfo<'info>,
+
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+impl<'info> TransferReceipt<'info> {
+    pub fn into_transfer_receipt_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.receipt_account.to_account_info().clone(),
+            to: self.to_receipt_account.to_account_info().clone(),
+            authority: self.authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+#[derive(Accounts)]
+pub struct RedeemReceipt<'info> {
+    #[account(mut)]
+    pub staking_data: ProgramAccount<'info, StakingData>,
+
+    //position redeemed against, owned by anybody
+    #[account(mut,
+        constraint = *staking_data.to_account_info().key == stake_state_account.staking_account,
+        constraint = *stake_state_account.to_account_info().key == StakingState::address(staking_data.to_account_info().key, &stake_state_account.onwer_address),
+    )]
+    pub stake_state_account: ProgramAccount<'info, StakingState>,
+
+    //receipts the position owner still holds, in the account they were minted to
+    #[account(
+        constraint = *owner_receipt_account.to_account_info().key == stake_state_account.owner_receipt_account(&staking_data.receipt_mint),
+    )]
+    pub owner_receipt_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    //position of the redeemer, the reward share and the unbonding follow the receipts there
+    #[account(mut,
+        constraint = *staking_data.to_account_info().key == to_stake_state_account.staking_account,
+        constraint = *to_stake_state_account.to_account_info().key == StakingState::address(staking_data.to_account_info().key, authority.key),
+        constraint = *to_stake_state_account.to_account_info().key != *stake_state_account.to_account_info().key,
+    )]
+    pub to_stake_state_account: ProgramAccount<'info, StakingState>,
+
+    #[account(mut,
+        constraint = staking_data.receipt_mint == *receipt_mint.to_account_info().key,
+    )]
+    pub receipt_mint: Account<'info, Mint>,
+
+    #[account(mut,
+        constraint = receipt_account.mint == staking_data.receipt_mint,
+        constraint = receipt_account.owner == *authority.key,
+    )]
+    pub receipt_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut,
+        constraint = staking_data.escrow_account == *escrow_account.to_account_info().key,
+    )]
+    pub escrow_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(mut,
+        constraint = *reclaimer.to_account_info().owner == *token_program.key,
+        constraint = reclaimer.mint == staking_data.mint_address,
+        constraint = reclaimer.owner == *authority.key,
+    )]
+    pub reclaimer: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(signer)]
     pub authority: AccountInfo<'info>,
 
-    pub mint_address: AccountInfo<'info>,
+    pub staking_authority: AccountInfo<'info>,
 
-    pub system_program: AccountInfo<'info>,
-    pub rent: Sysvar<'info, Rent>,
     #[account(address = anchor_spl::token::ID)]
     pub token_program: AccountInfo<'info>,
 }
 
-impl<'info> Initialize<'info> {
-    pub fn into_set_escrow_authority_context(&self) -> CpiContext<'_, '_, '_, 'info, SetAuthority<'info>> {
-        let cpi_accounts = SetAuthority {
-            account_or_mint: self.escrow_account.to_account_info().clone(),
-            current_authority: self.authority.clone(),
+impl<'info> RedeemReceipt<'info> {
+    pub fn into_burn_receipt_context(&self) -> CpiContext<'_, '_, '_, 'info, Burn<'info>> {
+        let cpi_accounts = Burn {
+            mint: self.receipt_mint.to_account_info().clone(),
+            to: self.receipt_account.to_account_info().clone(),
+            authority: self.authority.clone(),
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
     }
-    pub fn into_set_rewarder_authority_context(&self) -> CpiContext<'_, '_, '_, 'info, SetAuthority<'info>> {
-        let cpi_accounts = SetAuthority {
-            account_or_mint: self.rewarder_account.to_account_info().clone(),
-            current_authority: self.authority.clone(),
+    pub fn into_transfer_to_staker_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.escrow_account.to_account_info().clone(),
+            to: self.reclaimer.to_account_info().clone(),
+            authority: self.staking_authority.clone(),
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
     }
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
-pub struct StakerHistoryEntry {
-    pub time:   u64,
-    pub action: u8,  //0 staking, 1: unstaking, 2: claim rewarding
-    pub amount: u64,
-}
+#[derive(Accounts)]
+pub struct Compound<'info> {
+    #[account(mut)]
+    pub staking_data: ProgramAccount<'info, StakingData>,
 
-#[account]
-#[derive(Default)]
-pub struct StakingState {
-    pub staking_account: Pubkey,
-    pub my_crc: u32,
-    pub mint_address: Pubkey,    
-    pub onwer_address: Pubkey,
-    pub total_staked: u64,
-    pub total_rewarded: u64,
-    pub last_staked: u64,
-    pub last_rewarded: u64,
-    pub history: Vec<StakerHistoryEntry>
-}
+    //compounded by the owner, or by anybody when auto_compound is set
+    #[account(mut,
+        constraint = *staking_data.to_account_info().key == stake_state_account.staking_account,
+        constraint = *stake_state_account.to_account_info().key == StakingState::address(staking_data.to_account_info().key, &stake_state_account.onwer_address),
+    )]
+    pub stake_state_account: ProgramAccount<'info, StakingState>,
 
-impl StakingState{
-    pub const MAX_HISTORY: usize = 588;
+    #[account(mut,
+        constraint = staking_data.escrow_account == *escrow_account.to_account_info().key,
+    )]
+    pub escrow_account: Account<'info, anchor_spl::token::TokenAccount>,
 
-    pub fn add_history(&mut self, time: u64, action: u8, amount: u64)-> ()
-    {
-        if self.history.len() >= StakingState::MAX_HISTORY {
-            self.history.remove(0);
-        }
-        self.history.push(StakerHistoryEntry{
-            time: time, action: action, amount: amount
-        });
+    #[account(mut,
+        constraint = staking_data.rewarder_account == *rewarder_account.to_account_info().key,
+    )]
+    pub rewarder_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    //receipts of the position the owner still holds, checked by checkpoint_receipts, unused on pools without receipts
+    pub owner_receipt_account: AccountInfo<'info>,
+
+    #[account(signer)]
+    pub authority: AccountInfo<'info>,
+
+    pub staking_authority: AccountInfo<'info>,
+
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+impl<'info> Compound<'info> {
+    pub fn into_compound_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.rewarder_account.to_account_info().clone(),
+            to: self.escrow_account.to_account_info().clone(),
+            authority: self.staking_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
     }
 }
 
+#[derive(Accounts)]
+pub struct SetAutoCompound<'info> {
+    #[account(mut,
+        constraint = *stake_state_account.to_account_info().key == StakingState::address(&stake_state_account.staking_account, authority.key),
+    )]
+    pub stake_state_account: ProgramAccount<'info, StakingState>,
+
+    #[account(signer,
+        constraint = stake_state_account.onwer_address == *authority.key,
+    )]
+    pub authority: AccountInfo<'info>,
+}
 
 #[derive(Accounts)]
-pub struct InitializeStakeState<'info> {
+pub struct InitializeVesting<'info> {
     pub staking_data: ProgramAccount<'info, StakingData>,
 
-    #[account(init, 
-        seeds = [STAKER_PDA_SEED, staking_data.to_account_info().key.as_ref(), authority.key.as_ref()],
-        bump,
-        payer = authority, 
-        //space = 8 + 32 + 4 + 32 + 32 + 8 + 8 + 8 + 8
-        space = 10240
+    #[account(
+        constraint = *staking_data.to_account_info().key == stake_state_account.staking_account,
+        constraint = *stake_state_account.to_account_info().key == StakingState::address(staking_data.to_account_info().key, authority.key),
     )]
     pub stake_state_account: ProgramAccount<'info, StakingState>,
 
+    #[account(init,
+        seeds = [STAKING_VESTING_PDA_SEED, stake_state_account.to_account_info().key.as_ref()],
+        bump,
+        payer = authority,
+        token::mint = reward_mint,
+        token::authority = staking_authority,
+    )]
+    pub vesting_account: Account<'info, TokenAccount>,
+
+    #[account(
+        constraint = staking_data.reward_mint() == *reward_mint.to_account_info().key,
+    )]
+    pub reward_mint: Account<'info, Mint>,
+
     #[account(mut, signer)]
     pub authority: AccountInfo<'info>,
 
-    system_program: AccountInfo<'info>,
+    #[account(
+        constraint = *staking_authority.key == StakingData::authority_address(staking_data.to_account_info().key),
+    )]
+    pub staking_authority: AccountInfo<'info>,
+
+    pub system_program: AccountInfo<'info>,
     pub rent: Sysvar<'info, Rent>,
     #[account(address = anchor_spl::token::ID)]
     pub token_program: AccountInfo<'info>,
 }
 
-
 #[derive(Accounts)]
-pub struct Staking<'info> {
-    #[account(mut)]
+pub struct WithdrawVested<'info> {
     pub staking_data: ProgramAccount<'info, StakingData>,
 
     #[account(mut,
         constraint = *staking_data.to_account_info().key == stake_state_account.staking_account,
+        constraint = *stake_state_account.to_account_info().key == StakingState::address(staking_data.to_account_info().key, authority.key),
     )]
     pub stake_state_account: ProgramAccount<'info, StakingState>,
 
     #[account(mut,
-        constraint = staking_data.escrow_account == *escrow_account.to_account_info().key,
+        constraint = *vesting_account.to_account_info().key == StakingState::vesting_address(stake_state_account.to_account_info().key),
     )]
-    pub escrow_account: Account<'info, anchor_spl::token::TokenAccount>,
+    pub vesting_account: Account<'info, TokenAccount>,
 
     #[account(mut,
-        constraint = *staker_account.to_account_info().owner == *token_program.key,
-        constraint = staker_account.mint == staking_data.mint_address,
-        constraint = staker_account.owner == *authority.key,
+        constraint = *claimer.to_account_info().owner == *token_program.key,
+        constraint = claimer.mint == vesting_account.mint,
+        constraint = claimer.owner == *authority.key,
     )]
-    pub staker_account: Account<'info, anchor_spl::token::TokenAccount>,
-            
-    #[account(mut, signer,
+    pub claimer: Account<'info, TokenAccount>,
+
+    #[account(signer,
         constraint = stake_state_account.onwer_address == *authority.key,
     )]
     pub authority: AccountInfo<'info>,
 
+    pub staking_authority: AccountInfo<'info>,
+
     #[account(address = anchor_spl::token::ID)]
     pub token_program: AccountInfo<'info>,
 }
 
-impl<'info> Staking<'info> {
-    pub fn into_transfer_to_escrow_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+impl<'info> WithdrawVested<'info> {
+    pub fn into_transfer_to_claimer_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
         let cpi_accounts = Transfer {
-            from: self.staker_account.to_account_info().clone(),
-            to: self.escrow_account.to_account_info().clone(),
-            authority: self.authority.clone(),
+            from: self.vesting_account.to_account_info().clone(),
+            to: self.claimer.to_account_info().clone(),
+            authority: self.staking_authority.clone(),
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
     }
 }
 
-
 #[derive(Accounts)]
-pub struct Funding<'info> {
+pub struct WithdrawUnallocated<'info> {
     #[account(mut)]
     pub staking_data: ProgramAccount<'info, StakingData>,
 
@@ -231,49 +2529,55 @@ pub struct Funding<'info> {
 
     #[account(mut,
         constraint = *funder_account.to_account_info().owner == *token_program.key,
-        constraint = funder_account.mint == staking_data.mint_address,
+        constraint = funder_account.mint == staking_data.reward_mint(),
         constraint = funder_account.owner == *authority.key,
     )]
     pub funder_account: Account<'info, anchor_spl::token::TokenAccount>,
-            
-    #[account(mut, 
-        signer,
+
+    #[account(signer,
         constraint = staking_data.funder_authority == *authority.key,
     )]
     pub authority: AccountInfo<'info>,
 
+    pub staking_authority: AccountInfo<'info>,
+
     #[account(address = anchor_spl::token::ID)]
     pub token_program: AccountInfo<'info>,
 }
 
-impl<'info> Funding<'info> {
-    pub fn into_transfer_to_rewarder_context(
-        &self,
-    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+impl<'info> WithdrawUnallocated<'info> {
+    pub fn into_transfer_to_funder_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
         let cpi_accounts = Transfer {
-            from: self.funder_account.to_account_info().clone(),
-            to: self.rewarder_account.to_account_info().clone(),
-            authority: self.authority.clone(),
+            from: self.rewarder_account.to_account_info().clone(),
+            to: self.funder_account.to_account_info().clone(),
+            authority: self.staking_authority.clone(),
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
     }
 }
 
-
 #[derive(Accounts)]
-pub struct Unstaking<'info> {
-    #[account(mut)]
+pub struct InitializeDonor<'info> {
     pub staking_data: ProgramAccount<'info, StakingData>,
 
-    #[account(mut,
-        constraint = *staking_data.to_account_info().key == stake_state_account.staking_account,
-    )]
-    pub stake_state_account: ProgramAccount<'info, StakingState>,
+    #[account(init,
+        seeds = [STAKING_DONOR_PDA_SEED, staking_data.to_account_info().key.as_ref(), authority.key.as_ref()],
+        bump,
+        payer = authority,
+        space = 256)]
+    pub donor_record: ProgramAccount<'info, DonorRecord>,
 
-    #[account(mut,
-        constraint = staking_data.escrow_account == *escrow_account.to_account_info().key,        
-    )]
-    pub escrow_account: Account<'info, anchor_spl::token::TokenAccount>,
+    #[account(mut, signer)]
+    pub authority: AccountInfo<'info>,
+
+    pub system_program: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct Donate<'info> {
+    #[account(mut)]
+    pub staking_data: ProgramAccount<'info, StakingData>,
 
     #[account(mut,
         constraint = staking_data.rewarder_account == *rewarder_account.to_account_info().key,
@@ -281,100 +2585,230 @@ pub struct Unstaking<'info> {
     pub rewarder_account: Account<'info, anchor_spl::token::TokenAccount>,
 
     #[account(mut,
-        constraint = *reclaimer.to_account_info().owner == *token_program.key,
-        constraint = reclaimer.mint == staking_data.mint_address,
-        constraint = reclaimer.owner == *authority.key,
+        constraint = *donor_account.to_account_info().owner == *token_program.key,
+        constraint = donor_account.mint == staking_data.reward_mint(),
+        constraint = donor_account.owner == *authority.key,
     )]
-    pub reclaimer: Account<'info, anchor_spl::token::TokenAccount>,
+    pub donor_account: Account<'info, anchor_spl::token::TokenAccount>,
 
-    #[account(mut, signer,
-        constraint = stake_state_account.onwer_address == *authority.key,
+    #[account(mut,
+        constraint = *donor_record.to_account_info().key == DonorRecord::address(staking_data.to_account_info().key, authority.key),
     )]
-    pub authority: AccountInfo<'info>,
+    pub donor_record: ProgramAccount<'info, DonorRecord>,
 
-    pub staking_authority: AccountInfo<'info>,    
+    #[account(signer)]
+    pub authority: AccountInfo<'info>,
 
     #[account(address = anchor_spl::token::ID)]
     pub token_program: AccountInfo<'info>,
 }
 
-impl<'info> Unstaking<'info> {
-    pub fn into_transfer_to_staker_context(
-        &self,
-    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
-        let cpi_accounts = Transfer {
-            from: self.escrow_account.to_account_info().clone(),
-            to: self.reclaimer.to_account_info().clone(),
-            authority: self.staking_authority.clone(),
-        };
-        CpiContext::new(self.token_program.clone(), cpi_accounts)
-    }
-    pub fn into_transfer_from_rewarder_to_staker_context(
+impl<'info> Donate<'info> {
+    pub fn into_transfer_to_rewarder_context(
         &self,
     ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
         let cpi_accounts = Transfer {
-            from: self.rewarder_account.to_account_info().clone(),
-            to: self.reclaimer.to_account_info().clone(),
-            authority: self.staking_authority.clone(),
+            from: self.donor_account.to_account_info().clone(),
+            to: self.rewarder_account.to_account_info().clone(),
+            authority: self.authority.clone(),
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
     }
 }
 
-
 #[derive(Accounts)]
-pub struct Claiming<'info> {
+pub struct Settle<'info> {
     #[account(mut)]
     pub staking_data: ProgramAccount<'info, StakingData>,
 
-    #[account(mut,
-        constraint = *staking_data.to_account_info().key == stake_state_account.staking_account,
-    )]
-    pub stake_state_account: ProgramAccount<'info, StakingState>,
-
     #[account(mut,
         constraint = staking_data.rewarder_account == *rewarder_account.to_account_info().key,
     )]
     pub rewarder_account: Account<'info, anchor_spl::token::TokenAccount>,
 
     #[account(mut,
-        constraint = *claimer.to_account_info().owner == *token_program.key,
-        constraint = claimer.mint == staking_data.mint_address,
-        constraint = claimer.owner == *authority.key,
+        constraint = *keeper_account.to_account_info().owner == *token_program.key,
+        constraint = keeper_account.mint == staking_data.reward_mint(),
     )]
-    pub claimer: Account<'info, anchor_spl::token::TokenAccount>,
+    pub keeper_account: Account<'info, anchor_spl::token::TokenAccount>,
 
-    #[account(mut, signer,
-        constraint = stake_state_account.onwer_address == *authority.key,
-    )]
+    #[account(signer)]
     pub authority: AccountInfo<'info>,
 
-    pub staking_authority: AccountInfo<'info>,    
+    pub staking_authority: AccountInfo<'info>,
 
     #[account(address = anchor_spl::token::ID)]
     pub token_program: AccountInfo<'info>,
 }
 
-impl<'info> Claiming<'info> {
-    pub fn into_transfer_to_claimer_context(
-        &self,
-    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+impl<'info> Settle<'info> {
+    pub fn into_transfer_to_keeper_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
         let cpi_accounts = Transfer {
             from: self.rewarder_account.to_account_info().clone(),
-            to: self.claimer.to_account_info().clone(),
+            to: self.keeper_account.to_account_info().clone(),
             authority: self.staking_authority.clone(),
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
     }
 }
 
-#[derive(Accounts)]
-pub struct ChnageSetting<'info> {
-    #[account(mut)]
-    pub staking_data: ProgramAccount<'info, StakingData>,
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    #[account(mut, signer,
-        constraint = staking_data.initializer == *authority.key,
-    )]
-    pub authority: AccountInfo<'info>,
+    fn legacy_staker(staker_crc: u32, staked_time: u64, staked_amount: u64, gained_reward: u64) -> StakerState {
+        StakerState { staker_crc, staked_time, staked_amount, gained_reward }
+    }
+
+    fn legacy_state(my_crc: u32, total_staked: u64) -> StakingState {
+        let mut stake_state = StakingState::default();
+        stake_state.my_crc = my_crc;
+        stake_state.total_staked = total_staked;
+        stake_state
+    }
+
+    #[test]
+    fn crc_collision_is_resolved_by_the_named_entry() {
+        let mut staking_data = StakingData::default();
+        staking_data.stakers = vec![
+            legacy_staker(7, 100, 1000, 10),
+            legacy_staker(9, 150, 500, 5),
+            legacy_staker(7, 200, 1000, 20),
+        ];
+        let mut first = legacy_state(7, 1000);
+        let mut second = legacy_state(7, 1000);
+
+        assert!(staking_data.legacy_staker_index(7).is_err());
+        assert!(staking_data.resolve_legacy_staker(0, 1000, 200, &mut second).is_err());
+        assert!(staking_data.resolve_legacy_staker(1, 500, 150, &mut second).is_err());
+
+        staking_data.resolve_legacy_staker(2, 1000, 200, &mut second).unwrap();
+        assert_eq!(second.staked_time, 200);
+        assert_eq!(second.gained_reward, 20);
+        assert_eq!(second.my_crc, 0);
+
+        //the other state is no longer ambiguous
+        let index = staking_data.legacy_staker_index(7).unwrap();
+        staking_data.migrate_staker(index, &mut first).unwrap();
+        assert_eq!(first.staked_time, 100);
+        assert_eq!(first.gained_reward, 10);
+        assert_eq!(staking_data.stakers.len(), 1);
+        assert_eq!(staking_data.total_reward_accrued, 30);
+        assert_eq!(staking_data.legacy_gained_reward().unwrap(), 5);
+    }
+
+    //StakingState as it was laid out before the fields after history were added
+    #[derive(AnchorSerialize)]
+    struct LegacyStakingState {
+        staking_account: Pubkey,
+        my_crc: u32,
+        mint_address: Pubkey,
+        onwer_address: Pubkey,
+        total_staked: u64,
+        total_rewarded: u64,
+        last_staked: u64,
+        last_rewarded: u64,
+        history: Vec<StakerHistoryEntry>,
+    }
+
+    #[test]
+    fn legacy_state_with_full_history_is_cut_to_fit() {
+        let legacy = LegacyStakingState {
+            staking_account: Pubkey::new_unique(),
+            my_crc: 7,
+            mint_address: Pubkey::new_unique(),
+            onwer_address: Pubkey::new_unique(),
+            total_staked: 1000,
+            total_rewarded: 0,
+            last_staked: 588,
+            last_rewarded: 0,
+            history: (1..=588).map(|time| StakerHistoryEntry { time, action: 0, amount: 1 }).collect(),
+        };
+        let mut discriminator = Vec::new();
+        StakingState::default().try_serialize(&mut discriminator).unwrap();
+        let mut data = discriminator[..8].to_vec();
+        data.extend(legacy.try_to_vec().unwrap());
+        assert!(data.len() <= StakingState::SPACE);
+        data.resize(StakingState::SPACE, 0);
+
+        //the appended fields don't fit after 588 entries
+        assert!(StakingState::try_deserialize(&mut &data[..]).is_err());
+
+        let stake_state = StakingState::decode_unchecked(&data).unwrap();
+        assert_eq!(stake_state.my_crc, 7);
+        assert_eq!(stake_state.total_staked, 1000);
+        assert_eq!(stake_state.history.len(), StakingState::MAX_HISTORY);
+        assert_eq!(stake_state.history[0].time, 588 - StakingState::MAX_HISTORY as u64 + 1);
+        assert_eq!(stake_state.history[StakingState::MAX_HISTORY - 1].time, 588);
+        assert_eq!(stake_state.gained_reward, 0);
+        assert!(stake_state.stream_positions.is_empty());
+        stake_state.check_size().unwrap();
+
+        //once stored it loads as any other state
+        let mut stored = vec![0u8; StakingState::SPACE];
+        stake_state.try_serialize(&mut &mut stored[..]).unwrap();
+        let reloaded = StakingState::try_deserialize(&mut &stored[..]).unwrap();
+        assert_eq!(reloaded.history.len(), StakingState::MAX_HISTORY);
+        assert_eq!(reloaded.onwer_address, legacy.onwer_address);
+    }
+
+    #[test]
+    fn legacy_pool_keeps_the_percent_cap() {
+        let mut staking_data = StakingData::default();
+        staking_data.apy_max = 110;
+        staking_data.total_reward_accrued = 1000;
+
+        //10% of staked on each settle, whatever time it was earned over
+        assert_eq!(staking_data.accrue_cap_headroom(0, 1000, 0, 10).unwrap(), 0);
+        assert_eq!(staking_data.credit_reward(500, 1000, 0).unwrap(), (100, 0));
+        assert_eq!(staking_data.capped_reward, 400);
+
+        //110 bps a year on 1000 staked doesn't reach a token in 10 seconds
+        staking_data.apy_max_in_bps = true;
+        let cap_headroom = staking_data.accrue_cap_headroom(0, 1000, 0, 10).unwrap();
+        assert_eq!(cap_headroom, 1_100_000);
+        assert_eq!(staking_data.credit_reward(500, 1000, cap_headroom).unwrap(), (0, cap_headroom));
+        assert_eq!(staking_data.capped_reward, 900);
+    }
+
+    #[test]
+    fn receipt_reward_follows_the_holder() {
+        let mut stake_state = StakingState::default();
+        stake_state.total_staked = 1000;
+        stake_state.receipt_staked = 1000;
+
+        //300 receipts went away before the owner claims
+        stake_state.add_settled_reward(100).unwrap();
+        stake_state.checkpoint_receipts(700).unwrap();
+        assert_eq!(stake_state.gained_reward, 70);
+        assert_eq!(stake_state.away_receipts, 300);
+        assert_eq!(stake_state.away_receipt_reward, 30);
+
+        //reward settled later is shared by every receipt again
+        stake_state.add_settled_reward(100).unwrap();
+        stake_state.checkpoint_receipts(700).unwrap();
+        assert_eq!(stake_state.gained_reward, 140);
+        assert_eq!(stake_state.away_receipt_reward, 60);
+
+        //the holder of the 300 redeems what they earned since they left
+        assert_eq!(stake_state.take_away_receipt_reward(300).unwrap().0, 60);
+        assert_eq!(stake_state.away_receipts, 0);
+        assert_eq!(stake_state.away_receipt_reward, 0);
+    }
+
+    #[test]
+    fn receipts_back_with_the_owner_return_their_reward() {
+        let mut stake_state = StakingState::default();
+        stake_state.total_staked = 1000;
+        stake_state.receipt_staked = 1000;
+        stake_state.add_settled_reward(100).unwrap();
+        stake_state.checkpoint_receipts(600).unwrap();
+        assert_eq!(stake_state.away_receipt_reward, 40);
+
+        //half of the away receipts came back
+        stake_state.checkpoint_receipts(800).unwrap();
+        assert_eq!(stake_state.gained_reward, 80);
+        assert_eq!(stake_state.away_receipts, 200);
+        assert_eq!(stake_state.away_receipt_reward, 20);
+    }
 }
@@ -1,61 +1,352 @@
-// pub fn calculate_reward(apy_max: u64, pool_staked: u64, pool_reward: u64, 
-//     time_frame_start: u64, time_frame_end: u64, staked: u64, stake_start_time: u64, min_stake_period: u64, now_ts: u64) -> u64
-// {
-//     if staked == 0 || stake_start_time >= time_frame_end{
-//         return 0;
-//     }
+use anchor_lang::prelude::*;
+use crate::error::*;
 
-//     let mut seconds = now_ts.checked_sub(stake_start_time).unwrap();
+// fixed-point scale of reward_per_token
+pub const REWARD_PER_TOKEN_PRECISION: u128 = 1_000_000_000_000;
 
-//     let frame_seconds = time_frame_end.checked_sub(time_frame_start).unwrap();
-//     if seconds > frame_seconds {
-//         seconds = frame_seconds;
-//     }
+// (a * b) / c in u128 with checked operations, rounded down.
+pub fn mul_div(a: u128, b: u128, c: u128) -> Result<u128>
+{
+    if c == 0 {
+        return Err(StakingErrors::DivideByZero.into());
+    }
+    let product = a.checked_mul(b).ok_or(StakingErrors::MathOverflow)?;
+    Ok(product / c)
+}
 
-//     if seconds < min_stake_period {
-//         return 0;
-//     }
+fn to_u64(value: u128) -> Result<u64>
+{
+    if value > u64::MAX as u128 {
+        return Err(StakingErrors::MathOverflow.into());
+    }
+    Ok(value as u64)
+}
+
+// Advances the accumulator from last_update_time to now_ts, every step in integers rounded down:
+//
+//   from       = max(last_update_time, time_frame_start)
+//   to         = min(now_ts, time_frame_end)
+//   reward_per_token = reward_per_token_stored
+//       + pool_reward * (to - from) * PRECISION / ((time_frame_end - time_frame_start) * pool_staked)
+//
+// Returns the new reward_per_token and the reward accrued to the pool over the interval
+// (rounded up so the sum of every staker's share never exceeds it).
+pub fn calculate_reward_per_token(reward_per_token_stored: u128, pool_reward: u64, time_frame_start: u64, time_frame_end: u64,
+    last_update_time: u64, pool_staked: u64, now_ts: u64) -> Result<(u128, u64)>
+{
+    let frame_seconds = time_frame_end.checked_sub(time_frame_start).ok_or(StakingErrors::InvalidTimeframe)?;
+    if frame_seconds == 0 || pool_staked == 0 {
+        return Ok((reward_per_token_stored, 0));
+    }
+
+    let from = last_update_time.max(time_frame_start);
+    let to = now_ts.min(time_frame_end);
+    if to <= from {
+        return Ok((reward_per_token_stored, 0));
+    }
 
-//     let days: f64 = (seconds as f64)/ ((3600 * 24) as f64);
-//     let frame_days: f64 = frame_seconds as f64 / ((3600 * 24) as f64);
-//     let gained_total = (pool_reward as f64 * days * (staked as f64))/ (frame_days * (pool_staked as f64));
-//     let gained_per_day = gained_total / days;
-//     let staked_per_day = staked as f64 / days;
-//     let mut gained_percent_per_day = gained_per_day * 100.00 / staked_per_day;
-//     let apd_max = apy_max as f64 / 365.50;
+    let emitted = (pool_reward as u128).checked_mul((to - from) as u128).ok_or(StakingErrors::MathOverflow)?;
+    let pool_frame = (frame_seconds as u128).checked_mul(pool_staked as u128).ok_or(StakingErrors::MathOverflow)?;
+    let delta = mul_div(emitted, REWARD_PER_TOKEN_PRECISION, pool_frame)?;
 
-//     if gained_percent_per_day > apd_max{
-//         gained_percent_per_day = apd_max;
-//     }
+    let accrued = delta.checked_mul(pool_staked as u128).ok_or(StakingErrors::MathOverflow)?
+        .checked_add(REWARD_PER_TOKEN_PRECISION - 1).ok_or(StakingErrors::MathOverflow)?
+        / REWARD_PER_TOKEN_PRECISION;
 
-//     let gained = gained_percent_per_day * staked_per_day * days / 100.00;
-//     return gained as u64;
-// }
+    let reward_per_token = reward_per_token_stored.checked_add(delta).ok_or(StakingErrors::MathOverflow)?;
+    Ok((reward_per_token, to_u64(accrued)?))
+}
+
+// Pool reward of a timeframe which emits remaining from now_ts to time_frame_end at a constant rate,
+// the accumulator only uses it from now_ts on so the rate before now_ts doesn't matter:
+//
+//   remaining * (time_frame_end - time_frame_start) / (time_frame_end - now_ts)
+pub fn calculate_top_up_pool_reward(remaining: u64, time_frame_start: u64, time_frame_end: u64, now_ts: u64) -> Result<u64>
+{
+    let rest_seconds = time_frame_end.checked_sub(now_ts).ok_or(StakingErrors::InvalidTimeframe)?;
+    let frame_seconds = time_frame_end.checked_sub(time_frame_start).ok_or(StakingErrors::InvalidTimeframe)?;
+    to_u64(mul_div(remaining as u128, frame_seconds as u128, rest_seconds as u128)?)
+}
+
+// earned = staked * (reward_per_token - reward_per_token_paid) / PRECISION
+pub fn calculate_earned(reward_per_token: u128, reward_per_token_paid: u128, staked: u64) -> Result<u64>
+{
+    let delta = reward_per_token.checked_sub(reward_per_token_paid).ok_or(StakingErrors::MathOverflow)?;
+    to_u64(mul_div(staked as u128, delta, REWARD_PER_TOKEN_PRECISION)?)
+}
+
+// Reward a position earned since its checkpoint taken at settled_time, its lock boost only earns until lock_end:
+//
+//   earned(staked) + earned(lock_boost)                                                 while now_ts <= lock_end,
+//   earned(staked) + earned(lock_boost) * (lock_end - settled_time) / (now_ts - settled_time)  otherwise,
+//
+// the boost is taken to have earned at an even rate since settled_time.
+// Returns the earned reward and the boost part earned after lock_end.
+pub fn calculate_position_earned(reward_per_token: u128, reward_per_token_paid: u128, staked: u64, lock_boost: u64,
+    lock_end: u64, settled_time: u64, now_ts: u64) -> Result<(u64, u64)>
+{
+    let earned = calculate_earned(reward_per_token, reward_per_token_paid, staked)?;
+    let boost_earned = calculate_earned(reward_per_token, reward_per_token_paid, lock_boost)?;
+    let kept = if now_ts <= lock_end {
+        boost_earned
+    } else if settled_time >= lock_end {
+        0
+    } else {
+        to_u64(mul_div(boost_earned as u128, (lock_end - settled_time) as u128, (now_ts - settled_time) as u128)?)?
+    };
+    let total = earned.checked_add(kept).ok_or(StakingErrors::MathOverflow)?;
+    Ok((total, boost_earned - kept))
+}
 
-pub fn calculate_reward(apy_max: u64, pool_staked: u64, pool_reward: u64, 
-    time_frame_start: u64, time_frame_end: u64, staked: u64, stake_start_time: u64, min_stake_period: u64, now_ts: u64) -> u64
+// amount * 10^to_decimals / 10^from_decimals
+pub fn convert_decimals(amount: u64, from_decimals: u8, to_decimals: u8) -> Result<u64>
 {
-    if staked == 0 || stake_start_time >= time_frame_end{
-        return 0;
+    if from_decimals == to_decimals {
+        return Ok(amount);
     }
+    let from_scale = 10u128.checked_pow(from_decimals as u32).ok_or(StakingErrors::MathOverflow)?;
+    let to_scale = 10u128.checked_pow(to_decimals as u32).ok_or(StakingErrors::MathOverflow)?;
+    to_u64(mul_div(amount as u128, to_scale, from_scale)?)
+}
+
+pub const SECONDS_PER_YEAR: u64 = 365 * 24 * 60 * 60;
+
+// highest APR cap in basis points, 10000% a year
+pub const MAX_APY_MAX_BPS: u32 = 1_000_000;
+
+// apy_max of initialize, add_reward_stream and change_setting: basis points up to MAX_APY_MAX_BPS with 0 for no cap,
+// pools still on the legacy percent cap keep its bounds, 100 to 9999
+pub fn check_apy_max(apy_max: u32, apy_max_in_bps: bool) -> Result<()>
+{
+    if apy_max_in_bps {
+        if apy_max > MAX_APY_MAX_BPS {
+            return Err(StakingErrors::ApyMaxBpsTooLarge.into());
+        }
+        return Ok(());
+    }
+    if apy_max < 100 {
+        return Err(StakingErrors::ApyMaxMustBigThan100.into());
+    }
+    if apy_max >= 10000 {
+        return Err(StakingErrors::ApyMaxMustLessThan10000.into());
+    }
+    Ok(())
+}
+
+// scale of a cap headroom, one reward token unit of cap
+pub const CAP_HEADROOM_SCALE: u128 = 10000 * SECONDS_PER_YEAR as u128;
 
-    let mut staked_in_seconds = now_ts.checked_sub(stake_start_time).unwrap();
+// apy_max is the APR cap in basis points, 0 leaves the reward uncapped.
+//
+// Cap a position accrues while staked, with staked converted to reward token units first:
+//
+//   staked * apy_max * seconds
+//
+// in CAP_HEADROOM_SCALE units, so the cap of a period is the same however often the position is settled in it.
+pub fn calculate_cap_allowance(apy_max: u64, staked: u64, mint_decimals: u8, reward_decimals: u8, seconds: u64) -> Result<u128>
+{
+    if apy_max == 0 || staked == 0 {
+        return Ok(0);
+    }
+    let staked = convert_decimals(staked, mint_decimals, reward_decimals)?;
+    (staked as u128).checked_mul(apy_max as u128).ok_or(StakingErrors::MathOverflow)?
+        .checked_mul(seconds as u128).ok_or(StakingErrors::MathOverflow.into())
+}
 
-    let frame_seconds = time_frame_end.checked_sub(time_frame_start).unwrap();
-    if staked_in_seconds > frame_seconds {
-        staked_in_seconds = frame_seconds;
+// Reward credited out of earned and the cap headroom left of the position:
+//
+//   earned, 0                                                  when apy_max is 0,
+//   gained = min(earned, headroom / CAP_HEADROOM_SCALE),
+//   headroom - gained * CAP_HEADROOM_SCALE                     otherwise
+pub fn calculate_reward(apy_max: u64, earned: u64, cap_headroom: u128) -> Result<(u64, u128)>
+{
+    if apy_max == 0 {
+        return Ok((earned, 0));
     }
+    let gained = to_u64((cap_headroom / CAP_HEADROOM_SCALE).min(earned as u128))?;
+    Ok((gained, cap_headroom - gained as u128 * CAP_HEADROOM_SCALE))
+}
 
-    if staked_in_seconds < min_stake_period {
-        return 0;
+// Cap of pools initialized while apy_max was a percent of staked counting the stake itself, on each settled reward:
+//
+//   min(earned, staked * apy_max / 100 - staked)
+pub fn calculate_legacy_reward(apy_max: u64, earned: u64, staked: u64, mint_decimals: u8, reward_decimals: u8) -> Result<u64>
+{
+    if staked == 0 || earned == 0 {
+        return Ok(0);
     }
+    let staked = convert_decimals(staked, mint_decimals, reward_decimals)?;
+    let cap = mul_div(staked as u128, apy_max as u128, 100)?.saturating_sub(staked as u128);
+    to_u64(cap.min(earned as u128))
+}
 
-    let reward_per_second=((staked as f64) /(pool_staked as f64)) * (pool_reward as f64) / (frame_seconds as f64);
-    let mut reward = reward_per_second* (staked_in_seconds as f64);
-    let apy = (reward / (staked as f64)) * 100.00;
-    if apy > (apy_max as f64){
-        reward = ((apy_max as f64 /100.00) * (staked as f64)) - (staked as f64);
+// staked_time of a position after amount joins it at now_ts, each part keeps the time it was staked for:
+//
+//   (staked * staked_time + amount * now_ts) / (staked + amount)
+pub fn calculate_weighted_time(staked: u64, staked_time: u64, amount: u64, now_ts: u64) -> Result<u64>
+{
+    if staked == 0 {
+        return Ok(now_ts);
     }
-    return reward as u64;
+    let total = (staked as u128).checked_add(amount as u128).ok_or(StakingErrors::MathOverflow)?;
+    let weighted = (staked as u128).checked_mul(staked_time as u128).ok_or(StakingErrors::MathOverflow)?
+        .checked_add((amount as u128).checked_mul(now_ts as u128).ok_or(StakingErrors::MathOverflow)?).ok_or(StakingErrors::MathOverflow)?;
+    to_u64(weighted / total)
 }
 
+// boost = amount * (multiplier_bps - 10000) / 10000, the part of the effective stake above the amount itself
+pub fn calculate_lock_boost(amount: u64, multiplier_bps: u32) -> Result<u64>
+{
+    let extra_bps = multiplier_bps.checked_sub(10000).ok_or(StakingErrors::LockMultiplierMustBigThan10000)?;
+    to_u64(mul_div(amount as u128, extra_bps as u128, 10000)?)
+}
+
+// Interest of a fixed term deposit, in reward token units:
+//
+//   interest = principal * apr_bps * term / (10000 * SECONDS_PER_YEAR)
+pub fn calculate_fixed_term_interest(principal: u64, apr_bps: u32, term: u64, mint_decimals: u8, reward_decimals: u8) -> Result<u64>
+{
+    let principal = convert_decimals(principal, mint_decimals, reward_decimals)?;
+    let yearly = (principal as u128).checked_mul(apr_bps as u128).ok_or(StakingErrors::MathOverflow)?;
+    let year_bps = 10000u128 * SECONDS_PER_YEAR as u128;
+    to_u64(mul_div(yearly, term as u128, year_bps)?)
+}
+
+// Vault shares minted for a deposit:
+//
+//   amount                                 when the vault is empty,
+//   amount * share_supply / vault_assets   otherwise.
+pub fn calculate_shares(amount: u64, vault_assets: u64, share_supply: u64) -> Result<u64>
+{
+    if vault_assets == 0 || share_supply == 0 {
+        return Ok(amount);
+    }
+    to_u64(mul_div(amount as u128, share_supply as u128, vault_assets as u128)?)
+}
+
+// amount = shares * vault_assets / share_supply
+pub fn calculate_redeem_amount(shares: u64, vault_assets: u64, share_supply: u64) -> Result<u64>
+{
+    to_u64(mul_div(shares as u128, vault_assets as u128, share_supply as u128)?)
+}
+
+// Part of a vesting schedule unlocked at now_ts:
+//
+//   0                                          before cliff_end,
+//   amount                                     from end on,
+//   amount * (now_ts - start) / (end - start)  otherwise.
+pub fn calculate_vested(amount: u64, start: u64, cliff_end: u64, end: u64, now_ts: u64) -> Result<u64>
+{
+    if now_ts < cliff_end {
+        return Ok(0);
+    }
+    if now_ts >= end {
+        return Ok(amount);
+    }
+    to_u64(mul_div(amount as u128, (now_ts - start) as u128, (end - start) as u128)?)
+}
+
+// penalty = amount * penalty_bps / 10000 while now_ts - stake_start_time < penalty_period, otherwise 0
+pub fn calculate_penalty(amount: u64, penalty_bps: u16, penalty_period: u64, stake_start_time: u64, now_ts: u64) -> Result<u64>
+{
+    if penalty_bps == 0 || now_ts >= stake_start_time.saturating_add(penalty_period) {
+        return Ok(0);
+    }
+    to_u64(mul_div(amount as u128, penalty_bps as u128, 10000)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uncapped_reward_is_earned() {
+        assert_eq!(calculate_reward(0, 123_456, 0).unwrap(), (123_456, 0));
+        assert_eq!(calculate_cap_allowance(0, 1_000_000, 9, 9, 10).unwrap(), 0);
+    }
+
+    #[test]
+    fn capped_reward_is_prorated_apr() {
+        //10% a year on 1_000_000 staked for half a year
+        let headroom = calculate_cap_allowance(1000, 1_000_000, 9, 9, SECONDS_PER_YEAR / 2).unwrap();
+        assert_eq!(calculate_reward(1000, 80_000, headroom).unwrap(), (50_000, 0));
+        //under the cap the earned reward is kept with the rest of the headroom
+        assert_eq!(calculate_reward(1000, 20_000, headroom).unwrap(), (20_000, headroom - 20_000 * CAP_HEADROOM_SCALE));
+    }
+
+    #[test]
+    fn cap_does_not_depend_on_settle_frequency() {
+        let staked = 1_000_000_000_000;
+        let once = calculate_reward(1000, 1_000_000, calculate_cap_allowance(1000, staked, 9, 9, 100).unwrap()).unwrap();
+
+        let mut headroom = 0;
+        let mut gained = 0;
+        for _ in 0..100 {
+            headroom = headroom + calculate_cap_allowance(1000, staked, 9, 9, 1).unwrap();
+            let (settled, left) = calculate_reward(1000, 10_000, headroom).unwrap();
+            gained = gained + settled;
+            headroom = left;
+        }
+        assert_eq!(gained, once.0);
+        assert_eq!(headroom, once.1);
+    }
+
+    #[test]
+    fn apy_max_bounds_follow_the_pool_unit() {
+        //basis points take any rate up to MAX_APY_MAX_BPS, 0 is no cap
+        assert!(check_apy_max(0, true).is_ok());
+        assert!(check_apy_max(1, true).is_ok());
+        assert!(check_apy_max(20_000, true).is_ok());
+        assert!(check_apy_max(MAX_APY_MAX_BPS, true).is_ok());
+        assert!(check_apy_max(MAX_APY_MAX_BPS + 1, true).is_err());
+        //legacy percent pools keep 100 to 9999
+        assert!(check_apy_max(0, false).is_err());
+        assert!(check_apy_max(100, false).is_ok());
+        assert!(check_apy_max(10000, false).is_err());
+    }
+
+    #[test]
+    fn capped_reward_converts_staked_to_reward_decimals() {
+        //1 token of 6 decimals staked is 1_000_000_000 units of a 9 decimals reward token
+        assert_eq!(calculate_cap_allowance(10000, 1_000_000, 6, 9, SECONDS_PER_YEAR).unwrap() / CAP_HEADROOM_SCALE, 1_000_000_000);
+        assert_eq!(calculate_cap_allowance(10000, 1_000_000_000, 9, 6, SECONDS_PER_YEAR).unwrap() / CAP_HEADROOM_SCALE, 1_000_000);
+        assert_eq!(convert_decimals(1_234_567, 9, 6).unwrap(), 1_234);
+    }
+
+    #[test]
+    fn reward_per_token_emits_over_the_timeframe() {
+        //1000 over 100 seconds to 500 staked, 10 seconds emit 100
+        let (reward_per_token, accrued) = calculate_reward_per_token(0, 1000, 0, 100, 0, 500, 10).unwrap();
+        assert_eq!(reward_per_token, REWARD_PER_TOKEN_PRECISION / 5);
+        assert_eq!(accrued, 100);
+        assert_eq!(calculate_earned(reward_per_token, 0, 500).unwrap(), 100);
+
+        //nothing emits past the timeframe end
+        let (reward_per_token, accrued) = calculate_reward_per_token(reward_per_token, 1000, 0, 100, 90, 500, 150).unwrap();
+        assert_eq!(reward_per_token, REWARD_PER_TOKEN_PRECISION * 2 / 5);
+        assert_eq!(accrued, 100);
+
+        //nor to an empty pool
+        assert_eq!(calculate_reward_per_token(7, 1000, 0, 100, 0, 0, 50).unwrap(), (7, 0));
+    }
+
+    #[test]
+    fn reward_per_token_accrual_rounds_up() {
+        //1 over 3 seconds to 3 staked, a second emits a third of a token
+        let (reward_per_token, accrued) = calculate_reward_per_token(0, 1, 0, 3, 0, 3, 1).unwrap();
+        assert_eq!(reward_per_token, REWARD_PER_TOKEN_PRECISION / 9);
+        assert_eq!(accrued, 1);
+        assert_eq!(calculate_earned(reward_per_token, 0, 3).unwrap(), 0);
+    }
+
+    #[test]
+    fn position_earns_its_boost_until_lock_end() {
+        let reward_per_token = REWARD_PER_TOKEN_PRECISION;
+        //lock still running, the whole boost earns
+        assert_eq!(calculate_position_earned(reward_per_token, 0, 100, 50, 200, 100, 150).unwrap(), (150, 0));
+        //lock ended halfway since the checkpoint, half of the boost earns
+        assert_eq!(calculate_position_earned(reward_per_token, 0, 100, 50, 150, 100, 200).unwrap(), (125, 25));
+        //lock ended before the checkpoint, none of it
+        assert_eq!(calculate_position_earned(reward_per_token, 0, 100, 50, 90, 100, 200).unwrap(), (100, 50));
+    }
+}
@@ -59,4 +59,178 @@ pub enum StakingErrors {
     #[msg("min stake period must big than zero")]
     MinStakePeriodMustBigThanZero,
 
+    #[msg("math overflow")]
+    MathOverflow,
+
+    #[msg("divide by zero")]
+    DivideByZero,
+
+    #[msg("invalid pool staked amount")]
+    InvalidPoolStaked,
+
+    #[msg("invalid stake time")]
+    InvalidStakeTime,
+
+    #[msg("invalid timeframe")]
+    InvalidTimeframe,
+
+    #[msg("staker must be migrated first")]
+    StakerNotMigrated,
+
+    #[msg("legacy staker entry is ambiguous")]
+    AmbiguousLegacyStaker,
+
+    #[msg("no unbonding amount")]
+    NoUnbondingAmount,
+
+    #[msg("unbonding period isn`t finished")]
+    UnbondingNotFinished,
+
+    #[msg("penalty must less than 10000")]
+    PenaltyMustLessThan10000,
+
+    #[msg("invalid penalty mode")]
+    InvalidPenaltyMode,
+
+    #[msg("penalty can`t be redistributed in a different reward mint")]
+    PenaltyMintMismatch,
+
+    #[msg("invalid reward streams")]
+    InvalidRewardStreams,
+
+    #[msg("reached max reward streams")]
+    ReachedMaxRewardStreams,
+
+    #[msg("invalid lock tier")]
+    InvalidLockTier,
+
+    #[msg("lock multiplier must big than 10000")]
+    LockMultiplierMustBigThan10000,
+
+    #[msg("reached max lock tiers")]
+    ReachedMaxLockTiers,
+
+    #[msg("staked amount is locked")]
+    StakeIsLocked,
+
+    #[msg("term must big than zero")]
+    TermMustBigThanZero,
+
+    #[msg("reached max fixed term products")]
+    ReachedMaxFixedTermProducts,
+
+    #[msg("fixed term product is closed")]
+    FixedTermProductClosed,
+
+    #[msg("fixed term product capacity exceeded")]
+    FixedTermCapacityExceeded,
+
+    #[msg("rewarder can`t cover the fixed term reward")]
+    InSufficientRewardForFixedTerm,
+
+    #[msg("fixed term isn`t matured")]
+    FixedTermNotMatured,
+
+    #[msg("vault isn`t enabled")]
+    VaultNotEnabled,
+
+    #[msg("vault is already enabled")]
+    VaultAlreadyEnabled,
+
+    #[msg("vault needs the reward mint to be the staking mint")]
+    VaultMintMismatch,
+
+    #[msg("receipt isn`t enabled")]
+    ReceiptNotEnabled,
+
+    #[msg("receipt is already enabled")]
+    ReceiptAlreadyEnabled,
+
+    #[msg("invalid receipt account")]
+    InvalidReceiptAccount,
+
+    #[msg("receipt can`t be redeemed against this position")]
+    ReceiptNotRedeemable,
+
+    #[msg("compound needs the reward mint to be the staking mint")]
+    CompoundMintMismatch,
+
+    #[msg("auto compound isn`t enabled")]
+    AutoCompoundNotEnabled,
+
+    #[msg("vesting cliff must less than vesting duration")]
+    VestingCliffMustLessThanDuration,
+
+    #[msg("invalid vesting account")]
+    InvalidVestingAccount,
+
+    #[msg("nothing is vested")]
+    NothingVested,
+
+    #[msg("invalid rollover mode")]
+    InvalidRolloverMode,
+
+    #[msg("amount exceeds the unallocated reward")]
+    InSufficientUnallocatedReward,
+
+    #[msg("timeframe is ended")]
+    TimeframeEnded,
+
+    #[msg("invalid emission epoch")]
+    InvalidEmissionEpoch,
+
+    #[msg("reached max emission epochs")]
+    ReachedMaxEmissionEpochs,
+
+    #[msg("emission epochs are queued")]
+    EmissionEpochsQueued,
+
+    #[msg("donations aren`t enabled")]
+    DonationsNotEnabled,
+
+    #[msg("no emission window to donate into")]
+    NoEmissionWindow,
+
+    #[msg("invalid settle page")]
+    InvalidSettlePage,
+
+    #[msg("rewarder holds less than the reserved and unallocated reward")]
+    RewarderUnderfunded,
+
+    #[msg("staking data doesn`t fit its account")]
+    StakingDataTooLarge,
+
+    #[msg("signer isn`t the initializer")]
+    InvalidInitializer,
+
+    #[msg("invalid migrate page")]
+    InvalidMigratePage,
+
+    #[msg("reached max unbonding requests")]
+    ReachedMaxUnbondingRequests,
+
+    #[msg("penalty treasury must be a token account of the staking mint")]
+    InvalidPenaltyTreasury,
+
+    #[msg("vault can`t run with unbonding, penalty or min stake period")]
+    VaultExitRestricted,
+
+    #[msg("receipt can`t move while its stake is inside penalty period")]
+    ReceiptUnderPenalty,
+
+    #[msg("reward vests, it can`t be compounded")]
+    CompoundWithVesting,
+
+    #[msg("reached max vesting tranches")]
+    ReachedMaxVestingTranches,
+
+    #[msg("signer is neither the initializer nor the staker")]
+    InvalidMigrateAuthority,
+
+    #[msg("staking state doesn`t fit its account")]
+    StakingStateTooLarge,
+
+    #[msg("apy max must be at most 1000000 bps")]
+    ApyMaxBpsTooLarge,
+
 }
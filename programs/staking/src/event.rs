@@ -1,6 +1,334 @@
 use anchor_lang::prelude::*;
+use crate::account::{LockTier, EmissionEpoch};
 
 #[event]
-pub struct EventInitialize {
-    pub xx: u32,
+pub struct PoolInitialized {
+    pub pool: Pubkey,
+    pub initializer: Pubkey,
+    pub funder_authority: Pubkey,
+    pub mint_address: Pubkey,
+    pub reward_mint: Pubkey,
+    pub apy_max: u32,
+    pub min_timeframe_in_second: u64,
+    pub min_stake_period: u64,
+    pub time: u64,
+}
+
+#[event]
+pub struct Staked {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub user_staked: u64,
+    pub total_staked: u64,
+    pub lock_end: u64,
+    pub time: u64,
+}
+
+#[event]
+pub struct Unstaked {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub penalty: u64,
+    pub reward_paid: u64,
+    pub user_staked: u64,
+    pub total_staked: u64,
+    pub time: u64,
+}
+
+#[event]
+pub struct RewardClaimed {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub user_gained_reward: u64,
+    pub total_reward_paid: u64,
+    pub time: u64,
+}
+
+#[event]
+pub struct PoolFunded {
+    pub pool: Pubkey,
+    pub funder: Pubkey,
+    pub amount: u64,
+    pub pool_reward: u64,
+    pub timeframe_in_second: u64,
+    pub total_funded: u64,
+    pub capped_reward: u64,
+    pub carried_reward: u64,
+    pub redistributed_reward: u64,
+    pub unallocated_reward: u64,
+    pub rewarder_amount: u64,
+    pub time: u64,
+}
+
+#[event]
+pub struct SettingsChanged {
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub apy_max: u32,
+    pub min_stake_period: u64,
+    pub vesting_duration: u64,
+    pub vesting_cliff: u64,
+    pub time: u64,
+}
+
+#[event]
+pub struct UnbondingWithdrawn {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub total_unbonding: u64,
+    pub time: u64,
+}
+
+#[event]
+pub struct UnbondingPeriodChanged {
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub unbonding_period: u64,
+    pub time: u64,
+}
+
+#[event]
+pub struct PenaltyChanged {
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub penalty_bps: u16,
+    pub penalty_period: u64,
+    pub penalty_mode: u8,
+    pub penalty_treasury: Pubkey,
+    pub time: u64,
+}
+
+#[event]
+pub struct RewardStreamAdded {
+    pub pool: Pubkey,
+    pub stream: Pubkey,
+    pub index: u8,
+    pub funder_authority: Pubkey,
+    pub reward_mint: Pubkey,
+    pub apy_max: u32,
+    pub min_timeframe_in_second: u64,
+    pub time: u64,
+}
+
+#[event]
+pub struct RewardStreamFunded {
+    pub pool: Pubkey,
+    pub stream: Pubkey,
+    pub funder: Pubkey,
+    pub amount: u64,
+    pub pool_reward: u64,
+    pub timeframe_in_second: u64,
+    pub total_funded: u64,
+    pub time: u64,
+}
+
+#[event]
+pub struct StreamRewardClaimed {
+    pub pool: Pubkey,
+    pub stream: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub user_gained_reward: u64,
+    pub total_reward_paid: u64,
+    pub time: u64,
+}
+
+#[event]
+pub struct LockTiersChanged {
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub lock_tiers: Vec<LockTier>,
+    pub time: u64,
+}
+
+#[event]
+pub struct FixedTermProductChanged {
+    pub pool: Pubkey,
+    pub product: Pubkey,
+    pub term: u64,
+    pub apr_bps: u32,
+    pub capacity: u64,
+    pub enabled: bool,
+    pub time: u64,
+}
+
+#[event]
+pub struct FixedTermDeposited {
+    pub pool: Pubkey,
+    pub product: Pubkey,
+    pub user: Pubkey,
+    pub principal: u64,
+    pub interest: u64,
+    pub maturity_time: u64,
+    pub total_reserved_reward: u64,
+    pub time: u64,
+}
+
+#[event]
+pub struct FixedTermRedeemed {
+    pub pool: Pubkey,
+    pub product: Pubkey,
+    pub user: Pubkey,
+    pub principal: u64,
+    pub interest: u64,
+    pub total_reserved_reward: u64,
+    pub time: u64,
+}
+
+#[event]
+pub struct VaultChanged {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub deposited: u64,
+    pub redeemed: u64,
+    pub shares: u64,
+    pub compounded: u64,
+    pub vault_staked: u64,
+    pub share_supply: u64,
+    pub time: u64,
+}
+
+#[event]
+pub struct ReceiptEnabled {
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub receipt_mint: Pubkey,
+    pub time: u64,
+}
+
+#[event]
+pub struct ReceiptTransferred {
+    pub pool: Pubkey,
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub amount: u64,
+    pub time: u64,
+}
+
+#[event]
+pub struct ReceiptRedeemed {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub position_owner: Pubkey,
+    pub amount: u64,
+    pub total_staked: u64,
+    pub time: u64,
+}
+
+#[event]
+pub struct Compounded {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub caller: Pubkey,
+    pub amount: u64,
+    pub user_staked: u64,
+    pub total_staked: u64,
+    pub time: u64,
+}
+
+#[event]
+pub struct AutoCompoundChanged {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub auto_compound: bool,
+    pub time: u64,
+}
+
+#[event]
+pub struct RewardVested {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub vesting_amount: u64,
+    pub vesting_end: u64,
+    pub time: u64,
+}
+
+#[event]
+pub struct VestedWithdrawn {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub time: u64,
+}
+
+#[event]
+pub struct RolloverModeChanged {
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub rollover_mode: u8,
+    pub time: u64,
+}
+
+#[event]
+pub struct UnallocatedWithdrawn {
+    pub pool: Pubkey,
+    pub funder: Pubkey,
+    pub amount: u64,
+    pub unallocated_reward: u64,
+    pub time: u64,
+}
+
+#[event]
+pub struct PoolToppedUp {
+    pub pool: Pubkey,
+    pub funder: Pubkey,
+    pub amount: u64,
+    pub pool_reward: u64,
+    pub timeframe_in_second: u64,
+    pub total_funded: u64,
+    pub time: u64,
+}
+
+#[event]
+pub struct EmissionEpochsQueued {
+    pub pool: Pubkey,
+    pub funder: Pubkey,
+    pub epochs: Vec<EmissionEpoch>,
+    pub amount: u64,
+    pub queued_epochs: u8,
+    pub total_funded: u64,
+    pub time: u64,
+}
+
+#[event]
+pub struct DonationsChanged {
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub donations_enabled: bool,
+    pub time: u64,
+}
+
+#[event]
+pub struct Donated {
+    pub pool: Pubkey,
+    pub donor: Pubkey,
+    pub amount: u64,
+    pub donor_total: u64,
+    pub pool_reward: u64,
+    pub total_donated: u64,
+    pub time: u64,
+}
+
+#[event]
+pub struct KeeperTipChanged {
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub keeper_tip: u64,
+    pub time: u64,
+}
+
+#[event]
+pub struct PositionsSettled {
+    pub pool: Pubkey,
+    pub keeper: Pubkey,
+    pub positions: u64,
+    pub settled_reward: u64,
+    pub tip: u64,
+    pub total_settled_positions: u64,
+    pub time: u64,
 }
@@ -1,9 +1,8 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
-    token::{self},
+    token::{self, TokenAccount},
 };
 use spl_token::instruction::AuthorityType;
-use crc::crc32;
 
 
 pub mod account;
@@ -13,6 +12,7 @@ pub mod calculate;
 
 use crate::account::*;
 use crate::error::*;
+use crate::event::*;
 use crate::calculate::*;
 
 declare_id!("HohQ7VZFqDDn785ukULBKpNRKHsZXQPtCeUJ9PzYxgZ");
@@ -28,19 +28,13 @@ mod staking {
         min_stake_period: u64,
     ) -> ProgramResult {
 
-        if apy_max < 100{
-            return Err(StakingErrors::ApyMaxMustBigThan100.into());
-        }
-        if apy_max >= 10000{
-            return Err(StakingErrors::ApyMaxMustLessThan10000.into());
-        }
+        //apy_max is the APR cap in basis points, 0 for no cap
+        check_apy_max(apy_max, true)?;
 
+        //min_stake_period can be 0, a vault pool has to run without one
         if min_timeframe_in_second < 1{
             return Err(StakingErrors::MinTimeFrameMustBigThanZero.into());
         }
-        if min_stake_period < 1{
-            return Err(StakingErrors::MinStakePeriodMustBigThanZero.into());
-        }
 
         let staking_data = &mut ctx.accounts.staking_data;
 
@@ -48,7 +42,10 @@ mod staking {
         staking_data.funder_authority = *ctx.accounts.funder_authority.key;
         staking_data.escrow_account = *ctx.accounts.escrow_account.to_account_info().key;
         staking_data.rewarder_account = *ctx.accounts.rewarder_account.to_account_info().key;
-        staking_data.mint_address = *ctx.accounts.mint_address.key;
+        staking_data.mint_address = *ctx.accounts.mint_address.to_account_info().key;
+        staking_data.reward_mint = *ctx.accounts.reward_mint.to_account_info().key;
+        staking_data.mint_decimals = ctx.accounts.mint_address.decimals;
+        staking_data.reward_decimals = ctx.accounts.reward_mint.decimals;
 
         staking_data.total_funded = 0;
         staking_data.total_reward_paid = 0;
@@ -58,10 +55,60 @@ mod staking {
         staking_data.timeframe_started = 0;
         staking_data.pool_reward = 0;
         staking_data.apy_max = apy_max;    
+        staking_data.apy_max_in_bps = true;
         staking_data.min_stake_period = min_stake_period;
 
         staking_data.stakers = Vec::new();
 
+        staking_data.reward_per_token_stored = 0;
+        staking_data.last_update_time = 0;
+        staking_data.total_reward_accrued = 0;
+
+        staking_data.unbonding_period = 0;
+        staking_data.total_unbonding = 0;
+
+        staking_data.penalty_bps = 0;
+        staking_data.penalty_period = 0;
+        staking_data.penalty_mode = PENALTY_MODE_REDISTRIBUTE;
+        staking_data.penalty_treasury = Pubkey::default();
+        staking_data.total_penalty = 0;
+
+        staking_data.reward_streams = 0;
+
+        staking_data.lock_tiers = Vec::new();
+        staking_data.total_lock_boost = 0;
+
+        staking_data.fixed_term_products = 0;
+        staking_data.total_fixed_term_principal = 0;
+        staking_data.total_reserved_reward = 0;
+
+        staking_data.share_mint = Pubkey::default();
+        staking_data.vault_staked = 0;
+        staking_data.vault_reward_per_token_paid = 0;
+
+        staking_data.receipt_mint = Pubkey::default();
+
+        staking_data.vesting_duration = 0;
+        staking_data.vesting_cliff = 0;
+
+        staking_data.capped_reward = 0;
+        staking_data.vault_settled_time = 0;
+
+        staking_data.rollover_mode = ROLLOVER_MODE_CARRY;
+        staking_data.unallocated_reward = 0;
+
+        staking_data.emission_epochs = Vec::new();
+
+        staking_data.donations_enabled = false;
+        staking_data.total_donated = 0;
+
+        staking_data.keeper_tip = 0;
+        staking_data.last_settle_time = 0;
+        staking_data.total_settled_positions = 0;
+
+        staking_data.pending_redistribution = 0;
+        staking_data.vault_cap_headroom = 0;
+
         let (authority, authority_bump) =
             Pubkey::find_program_address(&[STAKING_AUTH_PDA_SEED, staking_data.to_account_info().key.as_ref()], ctx.program_id);
         staking_data.bump_auth = authority_bump;
@@ -76,6 +123,17 @@ mod staking {
             AuthorityType::AccountOwner, Some(authority),
         )?;       
 
+        emit!(PoolInitialized {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            initializer: *ctx.accounts.authority.key,
+            funder_authority: *ctx.accounts.funder_authority.key,
+            mint_address: *ctx.accounts.mint_address.to_account_info().key,
+            reward_mint: *ctx.accounts.reward_mint.to_account_info().key,
+            apy_max: apy_max,
+            min_timeframe_in_second: min_timeframe_in_second,
+            min_stake_period: min_stake_period,
+            time: Clock::get()?.unix_timestamp as u64,
+        });
         Ok(())
     }
 
@@ -88,7 +146,7 @@ mod staking {
 
         
         stake_state_account.staking_account = *ctx.accounts.staking_data.to_account_info().key;
-        stake_state_account.my_crc = crc32::checksum_ieee(stake_state_account.to_account_info().key.as_ref());
+        stake_state_account.my_crc = 0;
         stake_state_account.mint_address = staking_data.mint_address;
         stake_state_account.onwer_address = *ctx.accounts.authority.key;
         stake_state_account.total_staked = 0;
@@ -96,52 +154,113 @@ mod staking {
         stake_state_account.last_staked = 0;
         stake_state_account.last_rewarded = 0;
         stake_state_account.history = vec![];
+        stake_state_account.reward_per_token_paid = 0;
+        stake_state_account.staked_time = 0;
+        stake_state_account.gained_reward = 0;
+        stake_state_account.unbonding_amount = 0;
+        stake_state_account.unbonding_end = 0;
+        stake_state_account.stream_positions = vec![];
+        stake_state_account.locked_amount = 0;
+        stake_state_account.lock_end = 0;
+        stake_state_account.lock_boost = 0;
+        stake_state_account.receipt_staked = 0;
+        stake_state_account.auto_compound = false;
+        stake_state_account.vesting_amount = 0;
+        stake_state_account.vesting_start = 0;
+        stake_state_account.vesting_cliff_end = 0;
+        stake_state_account.vesting_end = 0;
+        stake_state_account.vesting_released = 0;
+        stake_state_account.vested_amount = 0;
+        stake_state_account.settled_time = 0;
+        stake_state_account.pending_reward = 0;
+        stake_state_account.stream_settlements = vec![];
+        stake_state_account.unbonding_requests = vec![];
+        stake_state_account.penalty_amount = 0;
+        stake_state_account.penalty_start = 0;
+        stake_state_account.receipt_account = Pubkey::default();
+        stake_state_account.vesting_tranches = vec![];
+        stake_state_account.receipt_reward = 0;
+        stake_state_account.away_receipts = 0;
+        stake_state_account.away_receipt_reward = 0;
+        stake_state_account.cap_headroom = 0;
         Ok(())
     }
 
-    pub fn staking(ctx: Context<Staking>,amount: u64) -> ProgramResult {
+    pub fn staking(ctx: Context<Staking>,amount: u64, lock_tier: u8) -> ProgramResult {
 
         if amount == 0{
             return Err(StakingErrors::AmountMustBigThanZero.into());
         }
 
+        if lock_tier as usize > ctx.accounts.staking_data.lock_tiers.len() {
+            return Err(StakingErrors::InvalidLockTier.into());
+        }
+
         if amount > ctx.accounts.staker_account.amount {
             return Err(StakingErrors::InSufficientBalance.into());             
         }
 
-        let staker_index = ctx.accounts.staking_data.index_of_staker(ctx.accounts.stake_state_account.my_crc);
-
-        if staker_index < 0 && ctx.accounts.staking_data.stakers.len() >= StakingData::MAX_STAKERS {
-            return Err(StakingErrors::ReachedMaxStakers.into());
+        if ctx.accounts.staking_data.index_of_staker(ctx.accounts.stake_state_account.my_crc) >= 0 {
+            return Err(StakingErrors::StakerNotMigrated.into());
         }
 
         token::transfer(
             ctx.accounts.into_transfer_to_escrow_context(),
             amount,
         )?;
-        
+
+        let now_ts = Clock::get()?.unix_timestamp as u64;
+
+        //settle the current position before the top-up joins it
+        let mut streams = load_reward_streams(ctx.program_id, &ctx.accounts.staking_data, ctx.remaining_accounts)?;
+        settle_position(&mut ctx.accounts.staking_data, &mut streams, &mut ctx.accounts.stake_state_account, now_ts)?;
+        exit_reward_streams(&streams, ctx.program_id)?;
+
+        //the top-up is locked on its own boost, a running lock only gets longer
+        if lock_tier > 0 {
+            let tier = ctx.accounts.staking_data.lock_tiers[lock_tier as usize - 1].clone();
+            let boost = calculate_lock_boost(amount, tier.multiplier_bps)?;
+            ctx.accounts.staking_data.total_lock_boost = ctx.accounts.staking_data.total_lock_boost + boost;
+            ctx.accounts.stake_state_account.lock_boost = ctx.accounts.stake_state_account.lock_boost + boost;
+            ctx.accounts.stake_state_account.locked_amount = ctx.accounts.stake_state_account.locked_amount + amount;
+            ctx.accounts.stake_state_account.lock_end = ctx.accounts.stake_state_account.lock_end.max(now_ts + tier.duration);
+        }
+
+        if ctx.accounts.staking_data.receipt_enabled() {
+            check_receipt_account(&ctx.accounts.staking_data, &ctx.accounts.receipt_account, ctx.accounts.authority.key)?;
+            let receipt_mint = ctx.accounts.staking_data.receipt_mint;
+            ctx.accounts.stake_state_account.record_receipt_account(ctx.accounts.receipt_account.key, &receipt_mint)?;
+            //the receipts out so far keep what they earned apart from the ones minted now
+            checkpoint_receipts(&ctx.accounts.staking_data, &mut ctx.accounts.stake_state_account, &ctx.accounts.receipt_account)?;
+            let authority_seeds = &[&STAKING_AUTH_PDA_SEED[..], ctx.accounts.staking_data.to_account_info().key.as_ref(), &[ctx.accounts.staking_data.bump_auth]];
+            token::mint_to(
+                ctx.accounts
+                    .into_mint_receipt_context()
+                    .with_signer(&[&authority_seeds[..]]),
+                    amount,
+            )?;
+            ctx.accounts.stake_state_account.receipt_staked = ctx.accounts.stake_state_account.receipt_staked + amount;
+        }
+
         //update staking data
         ctx.accounts.staking_data.total_staked = ctx.accounts.staking_data.total_staked + amount;
 
         //update staking state
-        let now_ts = Clock::get()?.unix_timestamp as u64;
-        ctx.accounts.stake_state_account.total_staked = ctx.accounts.stake_state_account.total_staked + amount;
+        let penalty_period = ctx.accounts.staking_data.penalty_period;
+        ctx.accounts.stake_state_account.add_penalty_tranche(amount, penalty_period, now_ts)?;
+        ctx.accounts.stake_state_account.add_staked(amount, now_ts)?;
         ctx.accounts.stake_state_account.last_staked = now_ts;
         ctx.accounts.stake_state_account.add_history(now_ts, 0, amount);
 
-        if staker_index < 0{            
-            let new_staker = StakerState {
-                staker_crc: ctx.accounts.stake_state_account.my_crc,
-                staked_time: now_ts,
-                staked_amount: ctx.accounts.stake_state_account.total_staked,
-                gained_reward: 0
-            };
-            ctx.accounts.staking_data.stakers.push(new_staker);
-        }else {
-            let staker = ctx.accounts.staking_data.stakers.get_mut(staker_index as usize).unwrap();
-            staker.staked_time = now_ts;
-            staker.staked_amount = ctx.accounts.stake_state_account.total_staked;
-        }
+        emit!(Staked {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            user: *ctx.accounts.authority.key,
+            amount: amount,
+            user_staked: ctx.accounts.stake_state_account.total_staked,
+            total_staked: ctx.accounts.staking_data.total_staked,
+            lock_end: ctx.accounts.stake_state_account.lock_end,
+            time: now_ts,
+        });
         Ok(())
     }
 
@@ -151,9 +270,8 @@ mod staking {
             return Err(StakingErrors::AmountMustBigThanZero.into());
         }
 
-        let staker_index = ctx.accounts.staking_data.index_of_staker(ctx.accounts.stake_state_account.my_crc);
-        if staker_index < 0 {
-            return Err(StakingErrors::InvalidStakingStateAccountCantFindEntry.into());
+        if ctx.accounts.staking_data.index_of_staker(ctx.accounts.stake_state_account.my_crc) >= 0 {
+            return Err(StakingErrors::StakerNotMigrated.into());
         }
 
         if amount > ctx.accounts.stake_state_account.total_staked {
@@ -164,83 +282,274 @@ mod staking {
             return Err(StakingErrors::InSufficientEscrowBalance.into());
         }        
 
-        let total_staked = ctx.accounts.staking_data.total_staked;
-        let apy_max = ctx.accounts.staking_data.apy_max;
-        let min_stake_period = ctx.accounts.staking_data.min_stake_period;
-        let timeframe_started = ctx.accounts.staking_data.timeframe_started;
-        let timeframe = ctx.accounts.staking_data.timeframe_in_second;
-        let pool_reward = ctx.accounts.staking_data.pool_reward;
+        let staked_amount = ctx.accounts.stake_state_account.total_staked;
+
+        let now_ts = Clock::get()?.unix_timestamp as u64;
+        if amount > ctx.accounts.stake_state_account.unlocked_amount(now_ts) {
+            return Err(StakingErrors::StakeIsLocked.into());
+        }
+        let unbonding_period = ctx.accounts.staking_data.unbonding_period;
+
+        //matured principal leaves first, only the rest of amount pays the penalty
+        let (penalized, penalty_start) = ctx.accounts.stake_state_account.take_penalized(amount, ctx.accounts.staking_data.penalty_period, now_ts);
+        let penalty = calculate_penalty(penalized, ctx.accounts.staking_data.penalty_bps,
+            ctx.accounts.staking_data.penalty_period, penalty_start, now_ts)?;
+        let penalty_mode = ctx.accounts.staking_data.penalty_mode;
 
-        let staker0 = ctx.accounts.staking_data.stakers.get(staker_index as usize).unwrap();
-        let staked_amount = staker0.staked_amount;
-        let staked_time = staker0.staked_time;
-        let gained_reward = staker0.gained_reward;
+        //the part staked without receipts goes first, the rest needs its receipts back
+        let receipt_staked = ctx.accounts.stake_state_account.receipt_staked;
+        let receipt_burned = amount.saturating_sub(staked_amount - receipt_staked);
+        if receipt_burned > 0 {
+            check_receipt_account(&ctx.accounts.staking_data, &ctx.accounts.receipt_account, ctx.accounts.authority.key)?;
+        }
 
         let authority_seeds = &[&STAKING_AUTH_PDA_SEED[..], ctx.accounts.staking_data.to_account_info().key.as_ref(), &[ctx.accounts.staking_data.bump_auth]];
-        token::transfer(
-            ctx.accounts
-                .into_transfer_to_staker_context()
-                .with_signer(&[&authority_seeds[..]]),
-                amount,
-        )?;
+        if penalty > 0 {
+            if penalty_mode == PENALTY_MODE_BURN {
+                token::burn(
+                    ctx.accounts
+                        .into_burn_penalty_context()
+                        .with_signer(&[&authority_seeds[..]]),
+                        penalty,
+                )?;
+            }else{
+                let to = if penalty_mode == PENALTY_MODE_TREASURY {
+                    ctx.accounts.treasury_account.clone()
+                }else{
+                    ctx.accounts.rewarder_account.to_account_info()
+                };
+                token::transfer(
+                    ctx.accounts
+                        .into_transfer_penalty_context(to)
+                        .with_signer(&[&authority_seeds[..]]),
+                        penalty,
+                )?;
+            }
+        }
 
-        let now_ts = Clock::get()?.unix_timestamp as u64;
-        //calculate rewarding for only unstaking amount
-        let gained = calculate_reward(apy_max as u64, total_staked, pool_reward, 
-            timeframe_started, timeframe_started + timeframe, amount,
-            staked_time, min_stake_period, now_ts);
+        if unbonding_period == 0 && amount > penalty {
+            token::transfer(
+                ctx.accounts
+                    .into_transfer_to_staker_context()
+                    .with_signer(&[&authority_seeds[..]]),
+                    amount - penalty,
+            )?;
+        }
+
+        //settle the whole position before the staked amount changes,
+        //stream rewards stay claimable with claim_stream_reward after a full unstake
+        let mut streams = load_reward_streams(ctx.program_id, &ctx.accounts.staking_data, ctx.remaining_accounts)?;
+        settle_position(&mut ctx.accounts.staking_data, &mut streams, &mut ctx.accounts.stake_state_account, now_ts)?;
+        forfeit_pending_reward(&mut ctx.accounts.staking_data, &mut streams, &mut ctx.accounts.stake_state_account, amount)?;
+        ctx.accounts.stake_state_account.move_cap_headroom(None, amount)?;
+        exit_reward_streams(&streams, ctx.program_id)?;
+
+        //the owner takes the reward of the receipts it holds before it burns some of them
+        if receipt_burned > 0 {
+            checkpoint_receipts(&ctx.accounts.staking_data, &mut ctx.accounts.stake_state_account, &ctx.accounts.receipt_account)?;
+            token::burn(
+                ctx.accounts.into_burn_receipt_context(),
+                receipt_burned,
+            )?;
+            ctx.accounts.stake_state_account.receipt_staked = receipt_staked - receipt_burned;
+        }
+
+        //redistributed penalty is spread over the rest of the running timeframe, otherwise it waits for the next one
+        if penalty > 0 && penalty_mode == PENALTY_MODE_REDISTRIBUTE {
+            let staking_data = &mut ctx.accounts.staking_data;
+            if now_ts < staking_data.timeframe_started + staking_data.timeframe_in_second {
+                staking_data.top_up_timeframe(penalty, 0, now_ts)?;
+            }else{
+                staking_data.pending_redistribution = staking_data.pending_redistribution.checked_add(penalty).ok_or(StakingErrors::MathOverflow)?;
+            }
+        }
+        ctx.accounts.staking_data.total_penalty = ctx.accounts.staking_data.total_penalty + penalty;
 
         //if unstake full amount we payout total reward
         //else we ony add gained reward to staker
-        if amount == staked_amount{
-            if (gained_reward + gained) > 0{
+        //with vesting the reward stays for claim_reward which vests it
+        let mut reward_paid = 0;
+        if amount == staked_amount && ctx.accounts.staking_data.vesting_duration == 0 {
+            let gained_reward = ctx.accounts.stake_state_account.gained_reward;
+            reward_paid = gained_reward;
+            if gained_reward > 0{
                 token::transfer(
                     ctx.accounts
                         .into_transfer_from_rewarder_to_staker_context()
                         .with_signer(&[&authority_seeds[..]]),
-                        gained_reward + gained,
+                        gained_reward,
                 )?;
             }
-            ctx.accounts.staking_data.total_reward_paid = ctx.accounts.staking_data.total_reward_paid + gained_reward + gained;
+            ctx.accounts.staking_data.total_reward_paid = ctx.accounts.staking_data.total_reward_paid + gained_reward;
+            ctx.accounts.stake_state_account.total_rewarded = ctx.accounts.stake_state_account.total_rewarded + gained_reward;
+            ctx.accounts.stake_state_account.gained_reward = 0;
         }
 
         //update staking data
         ctx.accounts.staking_data.total_staked = ctx.accounts.staking_data.total_staked - amount;   
 
-        let staker = ctx.accounts.staking_data.stakers.get_mut(staker_index as usize).unwrap();
-        staker.gained_reward = staker.gained_reward + gained;
-
-        if staker.staked_amount == amount{ 
-            //unstaking all, remove entry            
-            ctx.accounts.staking_data.stakers.remove(staker_index as usize);
-        }else{
-            staker.staked_amount = staker.staked_amount - amount;
-        }
-
         //update staking state
         ctx.accounts.stake_state_account.total_staked = ctx.accounts.stake_state_account.total_staked - amount;        
         ctx.accounts.stake_state_account.add_history(now_ts, 1, amount);
+        if penalty > 0 {
+            ctx.accounts.stake_state_account.add_history(now_ts, 5, penalty);
+        }
+
+        //with unbonding the amount stays in escrow without earning until withdraw_unbonded
+        if unbonding_period > 0 {
+            ctx.accounts.staking_data.total_unbonding = ctx.accounts.staking_data.total_unbonding + amount - penalty;
+            ctx.accounts.stake_state_account.add_unbonding(amount - penalty, now_ts + unbonding_period)?;
+        }
+
+        emit!(Unstaked {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            user: *ctx.accounts.authority.key,
+            amount: amount,
+            penalty: penalty,
+            reward_paid: reward_paid,
+            user_staked: ctx.accounts.stake_state_account.total_staked,
+            total_staked: ctx.accounts.staking_data.total_staked,
+            time: now_ts,
+        });
         Ok(())
     }
 
     pub fn claim_reward(ctx: Context<Claiming>, amount: u64) -> ProgramResult {
 
-        if amount == 0{
+        //amount can be 0 to claim only the reward streams
+        if amount == 0 && ctx.remaining_accounts.len() <= ctx.accounts.staking_data.reward_streams as usize {
             return Err(StakingErrors::AmountMustBigThanZero.into());
         }
 
-        let staker_index = ctx.accounts.staking_data.index_of_staker(ctx.accounts.stake_state_account.my_crc);
-
-        if staker_index < 0 {
-            return Err(StakingErrors::InvalidStakingStateAccountCantFindEntry.into());
+        if ctx.accounts.staking_data.index_of_staker(ctx.accounts.stake_state_account.my_crc) >= 0 {
+            return Err(StakingErrors::StakerNotMigrated.into());
         }
 
-        let staker_data = ctx.accounts.staking_data.stakers.get(staker_index as usize).unwrap();
+        //every stream is settled so an expired lock can be dropped off the pool
+        let now_ts = Clock::get()?.unix_timestamp as u64;
+        let mut streams = load_reward_streams(ctx.program_id, &ctx.accounts.staking_data, ctx.remaining_accounts)?;
+        settle_position(&mut ctx.accounts.staking_data, &mut streams, &mut ctx.accounts.stake_state_account, now_ts)?;
+        checkpoint_receipts(&ctx.accounts.staking_data, &mut ctx.accounts.stake_state_account, &ctx.accounts.owner_receipt_account)?;
 
-        if amount > staker_data.gained_reward {
+        if amount > ctx.accounts.stake_state_account.gained_reward {
             return Err(StakingErrors::InSufficientGainedReward.into());            
         }
 
+        let authority_seeds = &[&STAKING_AUTH_PDA_SEED[..], ctx.accounts.staking_data.to_account_info().key.as_ref(), &[ctx.accounts.staking_data.bump_auth]];
+        if amount > 0 {
+            let vesting_duration = ctx.accounts.staking_data.vesting_duration;
+            if vesting_duration > 0 {
+                //vests in the staker vesting account until withdraw_vested
+                token::transfer(
+                    ctx.accounts
+                        .into_transfer_to_vesting_context()
+                        .with_signer(&[&authority_seeds[..]]),
+                        amount,
+                )?;
+                let vesting_cliff = ctx.accounts.staking_data.vesting_cliff;
+                ctx.accounts.stake_state_account.add_vesting(amount, vesting_duration, vesting_cliff, now_ts)?;
+
+                emit!(RewardVested {
+                    pool: *ctx.accounts.staking_data.to_account_info().key,
+                    user: *ctx.accounts.authority.key,
+                    amount: amount,
+                    vesting_amount: ctx.accounts.stake_state_account.vesting_amount,
+                    vesting_end: ctx.accounts.stake_state_account.vesting_end,
+                    time: now_ts,
+                });
+            }else{
+                token::transfer(
+                    ctx.accounts
+                        .into_transfer_to_claimer_context()
+                        .with_signer(&[&authority_seeds[..]]),
+                        amount,
+                )?;
+            }
+
+            //update staking data
+            ctx.accounts.staking_data.total_reward_paid = ctx.accounts.staking_data.total_reward_paid + amount;
+
+            //update staking state
+            ctx.accounts.stake_state_account.gained_reward = ctx.accounts.stake_state_account.gained_reward - amount;
+            ctx.accounts.stake_state_account.total_rewarded = ctx.accounts.stake_state_account.total_rewarded + amount;
+            ctx.accounts.stake_state_account.last_rewarded = now_ts;
+            ctx.accounts.stake_state_account.add_history(now_ts, 2, amount);
+
+            emit!(RewardClaimed {
+                pool: *ctx.accounts.staking_data.to_account_info().key,
+                user: *ctx.accounts.authority.key,
+                amount: amount,
+                user_gained_reward: ctx.accounts.stake_state_account.gained_reward,
+                total_reward_paid: ctx.accounts.staking_data.total_reward_paid,
+                time: now_ts,
+            });
+        }
+
+        //every gained stream reward is claimed too, for each [rewarder_account, claimer] after the reward streams in remaining_accounts
+        let claims = &ctx.remaining_accounts[streams.len()..];
+        if claims.len() % 2 != 0 {
+            return Err(StakingErrors::InvalidRewardStreams.into());
+        }
+        for accounts in claims.chunks(2) {
+            let stream = match streams.iter_mut().find(|stream| stream.rewarder_account == *accounts[0].key) {
+                Some(stream) => stream,
+                None => return Err(StakingErrors::InvalidRewardStreams.into()),
+            };
+            let claimer = Account::<TokenAccount>::try_from(&accounts[1])?;
+            if claimer.mint != stream.reward_mint || claimer.owner != *ctx.accounts.authority.key {
+                return Err(StakingErrors::InvalidRewardStreams.into());
+            }
+
+            let position = ctx.accounts.stake_state_account.stream_position(stream.index);
+            let stream_amount = ctx.accounts.stake_state_account.stream_positions[position].gained_reward;
+            if stream_amount > 0 {
+                token::transfer(
+                    CpiContext::new(ctx.accounts.token_program.clone(), token::Transfer {
+                        from: accounts[0].clone(),
+                        to: accounts[1].clone(),
+                        authority: ctx.accounts.staking_authority.clone(),
+                    }).with_signer(&[&authority_seeds[..]]),
+                    stream_amount,
+                )?;
+                stream.total_reward_paid = stream.total_reward_paid + stream_amount;
+                ctx.accounts.stake_state_account.stream_positions[position].gained_reward = 0;
+                ctx.accounts.stake_state_account.last_rewarded = now_ts;
+                ctx.accounts.stake_state_account.add_history(now_ts, 6, stream_amount);
+
+                emit!(StreamRewardClaimed {
+                    pool: *ctx.accounts.staking_data.to_account_info().key,
+                    stream: *stream.to_account_info().key,
+                    user: *ctx.accounts.authority.key,
+                    amount: stream_amount,
+                    user_gained_reward: 0,
+                    total_reward_paid: stream.total_reward_paid,
+                    time: now_ts,
+                });
+            }
+        }
+        exit_reward_streams(&streams, ctx.program_id)?;
+        Ok(())
+    }
+
+    pub fn claim_stream_reward(ctx: Context<ClaimingStream>, amount: u64) -> ProgramResult {
+        if amount == 0{
+            return Err(StakingErrors::AmountMustBigThanZero.into());
+        }
+
+        if ctx.accounts.staking_data.index_of_staker(ctx.accounts.stake_state_account.my_crc) >= 0 {
+            return Err(StakingErrors::StakerNotMigrated.into());
+        }
+
+        //only this stream is settled so an expired lock stays until a path settling every stream drops it,
+        //its boost already stops earning at lock_end
+        let now_ts = Clock::get()?.unix_timestamp as u64;
+        let position = ctx.accounts.reward_stream.settle_position(&ctx.accounts.staking_data,
+            &mut ctx.accounts.stake_state_account, now_ts)?;
+
+        if amount > ctx.accounts.stake_state_account.stream_positions[position].gained_reward {
+            return Err(StakingErrors::InSufficientGainedReward.into());
+        }
+
         let authority_seeds = &[&STAKING_AUTH_PDA_SEED[..], ctx.accounts.staking_data.to_account_info().key.as_ref(), &[ctx.accounts.staking_data.bump_auth]];
         token::transfer(
             ctx.accounts
@@ -249,32 +558,171 @@ mod staking {
                 amount,
         )?;
 
-        //update staking data
-        //let staking_data = &mut ctx.accounts.staking_data;
-        ctx.accounts.staking_data.total_reward_paid = ctx.accounts.staking_data.total_reward_paid + amount;
+        //update reward stream
+        ctx.accounts.reward_stream.total_reward_paid = ctx.accounts.reward_stream.total_reward_paid + amount;
+
+        //update staking state
+        ctx.accounts.stake_state_account.stream_positions[position].gained_reward = ctx.accounts.stake_state_account.stream_positions[position].gained_reward - amount;
+        ctx.accounts.stake_state_account.last_rewarded = now_ts;
+        ctx.accounts.stake_state_account.add_history(now_ts, 6, amount);
+
+        emit!(StreamRewardClaimed {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            stream: *ctx.accounts.reward_stream.to_account_info().key,
+            user: *ctx.accounts.authority.key,
+            amount: amount,
+            user_gained_reward: ctx.accounts.stake_state_account.stream_positions[position].gained_reward,
+            total_reward_paid: ctx.accounts.reward_stream.total_reward_paid,
+            time: now_ts,
+        });
+        Ok(())
+    }
+
+    //withdraws every unbonding request which reached its end
+    pub fn withdraw_unbonded(ctx: Context<WithdrawUnbonded>) -> ProgramResult {
+        if ctx.accounts.stake_state_account.unbonding_amount == 0 {
+            return Err(StakingErrors::NoUnbondingAmount.into());
+        }
+
+        let now_ts = Clock::get()?.unix_timestamp as u64;
+        let amount = ctx.accounts.stake_state_account.release_unbonded(now_ts);
+        if amount == 0 {
+            return Err(StakingErrors::UnbondingNotFinished.into());
+        }
+
+        let authority_seeds = &[&STAKING_AUTH_PDA_SEED[..], ctx.accounts.staking_data.to_account_info().key.as_ref(), &[ctx.accounts.staking_data.bump_auth]];
+        token::transfer(
+            ctx.accounts
+                .into_transfer_to_staker_context()
+                .with_signer(&[&authority_seeds[..]]),
+                amount,
+        )?;
 
-        //update staker state        
-        let staker = ctx.accounts.staking_data.stakers.get_mut(staker_index as usize).unwrap();        
-        staker.gained_reward = staker.gained_reward - amount;                
+        //update staking data
+        ctx.accounts.staking_data.total_unbonding = ctx.accounts.staking_data.total_unbonding - amount;
 
         //update staking state
-        ctx.accounts.stake_state_account.total_rewarded = ctx.accounts.stake_state_account.total_rewarded + amount;
+        ctx.accounts.stake_state_account.add_history(now_ts, 3, amount);
+
+        emit!(UnbondingWithdrawn {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            user: *ctx.accounts.authority.key,
+            amount: amount,
+            total_unbonding: ctx.accounts.staking_data.total_unbonding,
+            time: now_ts,
+        });
+        Ok(())
+    }
+
+    //puts the unbonding amount back into the position
+    pub fn cancel_unbonding(ctx: Context<CancelUnbonding>) -> ProgramResult {
+        let amount = ctx.accounts.stake_state_account.unbonding_amount;
+        if amount == 0 {
+            return Err(StakingErrors::NoUnbondingAmount.into());
+        }
+
         let now_ts = Clock::get()?.unix_timestamp as u64;
-        ctx.accounts.stake_state_account.last_rewarded = now_ts;
-        ctx.accounts.stake_state_account.add_history(now_ts, 2, amount);
+
+        //settle the current position before the amount joins it, same as a top-up
+        let mut streams = load_reward_streams(ctx.program_id, &ctx.accounts.staking_data, ctx.remaining_accounts)?;
+        settle_position(&mut ctx.accounts.staking_data, &mut streams, &mut ctx.accounts.stake_state_account, now_ts)?;
+        exit_reward_streams(&streams, ctx.program_id)?;
+
+        //update staking data
+        ctx.accounts.staking_data.total_unbonding = ctx.accounts.staking_data.total_unbonding - amount;
+        ctx.accounts.staking_data.total_staked = ctx.accounts.staking_data.total_staked + amount;
+
+        //update staking state
+        ctx.accounts.stake_state_account.cancel_unbonding();
+        ctx.accounts.stake_state_account.add_staked(amount, now_ts)?;
+        ctx.accounts.stake_state_account.last_staked = now_ts;
+        ctx.accounts.stake_state_account.add_history(now_ts, 4, amount);
+
+        emit!(Staked {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            user: *ctx.accounts.authority.key,
+            amount: amount,
+            user_staked: ctx.accounts.stake_state_account.total_staked,
+            total_staked: ctx.accounts.staking_data.total_staked,
+            lock_end: ctx.accounts.stake_state_account.lock_end,
+            time: now_ts,
+        });
         Ok(())
     }
 
+    //moves a legacy stakers entry into its StakingState, entries are only known by crc
+    //so only an entry whose crc matches no other one is migrated, a colliding one goes through resolve_legacy_staker
+    //the owner migrates its own state, the initializer migrates stake_state_account and the staker states
+    //in remaining_accounts, a legacy pool whose stakers list doesn't fit next to the appended fields
+    //has to drop enough entries in one call
+    pub fn migrate_staker(ctx: Context<MigrateStaker>) -> ProgramResult {
+        if ctx.accounts.staking_data.owner != ctx.program_id {
+            return Err(StakingErrors::InvalidStakingAccount.into());
+        }
+        let mut staking_data = StakingData::load_unchecked(&ctx.accounts.staking_data)?;
+        let staking_account = *ctx.accounts.staking_data.key;
+        let mut stake_state = load_stake_state(ctx.program_id, &staking_account, &ctx.accounts.stake_state_account)?;
+        let by_initializer = staking_data.initializer == *ctx.accounts.authority.key;
+        if !by_initializer && stake_state.onwer_address != *ctx.accounts.authority.key {
+            return Err(StakingErrors::InvalidMigrateAuthority.into());
+        }
+        if !by_initializer && !ctx.remaining_accounts.is_empty() {
+            return Err(StakingErrors::InvalidInitializer.into());
+        }
+        if ctx.remaining_accounts.len() >= StakingData::MAX_MIGRATE_PAGE {
+            return Err(StakingErrors::InvalidMigratePage.into());
+        }
+
+        let staker_index = staking_data.legacy_staker_index(stake_state.my_crc)?;
+        staking_data.migrate_staker(staker_index, &mut stake_state)?;
+        stake_state.store(&ctx.accounts.stake_state_account)?;
+
+        for info in ctx.remaining_accounts.iter() {
+            let mut stake_state = load_stake_state(ctx.program_id, &staking_account, info)?;
+            let staker_index = staking_data.legacy_staker_index(stake_state.my_crc)?;
+            staking_data.migrate_staker(staker_index, &mut stake_state)?;
+            stake_state.store(info)?;
+        }
+
+        staking_data.store(&ctx.accounts.staking_data)
+    }
+
+    //staker states whose crc collides can't be migrated by it, the initializer names their entries
+    pub fn resolve_legacy_staker(ctx: Context<MigrateStaker>, entry_index: u32, staked_amount: u64, staked_time: u64) -> ProgramResult {
+        if ctx.accounts.staking_data.owner != ctx.program_id {
+            return Err(StakingErrors::InvalidStakingAccount.into());
+        }
+        let mut staking_data = StakingData::load_unchecked(&ctx.accounts.staking_data)?;
+        if staking_data.initializer != *ctx.accounts.authority.key {
+            return Err(StakingErrors::InvalidInitializer.into());
+        }
+        let mut stake_state = load_stake_state(ctx.program_id, ctx.accounts.staking_data.key, &ctx.accounts.stake_state_account)?;
+
+        staking_data.resolve_legacy_staker(entry_index as usize, staked_amount, staked_time, &mut stake_state)?;
+        stake_state.store(&ctx.accounts.stake_state_account)?;
+        staking_data.store(&ctx.accounts.staking_data)
+    }
+
+    //a legacy state without a stakers entry left has nothing to migrate, this only cuts its history
+    //so it loads on the other instructions again
+    pub fn compact_stake_state(ctx: Context<MigrateStaker>) -> ProgramResult {
+        if ctx.accounts.staking_data.owner != ctx.program_id {
+            return Err(StakingErrors::InvalidStakingAccount.into());
+        }
+        let staking_data = StakingData::load_unchecked(&ctx.accounts.staking_data)?;
+        let stake_state = load_stake_state(ctx.program_id, ctx.accounts.staking_data.key, &ctx.accounts.stake_state_account)?;
+        if staking_data.initializer != *ctx.accounts.authority.key && stake_state.onwer_address != *ctx.accounts.authority.key {
+            return Err(StakingErrors::InvalidMigrateAuthority.into());
+        }
+        stake_state.store(&ctx.accounts.stake_state_account)
+    }
+
 
+    //starts a new timeframe in constant compute: stakers aren't walked, every position settles against
+    //the accumulator on its own instructions or through settle, so the timeframe takes effect at once
+    //and there is no per-staker settlement to split into phases, however many positions the pool has
     pub fn funding(ctx: Context<Funding>, amount: u64, timeframe_in_second: u64) -> ProgramResult {
-        let total_staked = ctx.accounts.staking_data.total_staked;
-        let apy_max = ctx.accounts.staking_data.apy_max;
-        let min_stake_period = ctx.accounts.staking_data.min_stake_period;
         let now_ts = Clock::get()?.unix_timestamp as u64;
-        let mut total_reward_in_pending: u64 = 0;
-        let timeframe_started = ctx.accounts.staking_data.timeframe_started;
-        let timeframe = ctx.accounts.staking_data.timeframe_in_second;
-        let pool_reward = ctx.accounts.staking_data.pool_reward;
 
         if amount == 0{
             return Err(StakingErrors::AmountMustBigThanZero.into());
@@ -288,57 +736,1198 @@ mod staking {
             return Err(StakingErrors::TimeframeMustBigThanMinStakePeriod.into());
         }
 
-        //calc reward
-        if timeframe > 0{
-            let mut time_frame_end = timeframe_started + timeframe;
-            if now_ts < time_frame_end {
-                time_frame_end = now_ts;
-            }
-            
-            for i in 0..ctx.accounts.staking_data.stakers.len(){
-                let staker = ctx.accounts.staking_data.stakers.get_mut(i).unwrap();
-                let gained = calculate_reward(apy_max as u64, total_staked, pool_reward, 
-                    timeframe_started, time_frame_end, staker.staked_amount, 
-                    staker.staked_time, min_stake_period, now_ts);
-
-                if gained != 0{
-                    staker.gained_reward = staker.gained_reward + gained;
-                    staker.staked_time = now_ts;    
-                }
-                total_reward_in_pending = total_reward_in_pending + staker.gained_reward;                
-            }
+        //close the current timeframe in the accumulator
+        ctx.accounts.staking_data.update_reward_per_token(now_ts)?;
+        //a new timeframe would overlap the queued epochs
+        if ctx.accounts.staking_data.emission_epochs.len() > 0 {
+            return Err(StakingErrors::EmissionEpochsQueued.into());
         }
+        let leftover = ctx.accounts.staking_data.leftover_reward(ctx.accounts.rewarder_account.amount)?;
 
-        let rewarder_rest_amount = ctx.accounts.rewarder_account.amount - total_reward_in_pending;
-        let real_fund_amount = amount - rewarder_rest_amount;
-        if real_fund_amount > ctx.accounts.funder_account.amount {
+        if amount > ctx.accounts.funder_account.amount {
             return Err(StakingErrors::InSufficientBalance.into());
         }
+        let rewarder_amount = ctx.accounts.rewarder_account.amount.checked_add(amount).ok_or(StakingErrors::MathOverflow)?;
 
         token::transfer(
             ctx.accounts.into_transfer_to_rewarder_context(),
             amount,
         )?;
-        ctx.accounts.staking_data.pool_reward = rewarder_rest_amount + real_fund_amount;
-        ctx.accounts.staking_data.total_funded = ctx.accounts.staking_data.total_funded + real_fund_amount;
+
+        //the left over reward either carries into the new timeframe or is set aside for the funder, so the rewarder
+        //reconciles as pending + reserved + unallocated_reward + pool_reward, short of the accumulator round-up
+        let mut carried_reward = 0;
+        if ctx.accounts.staking_data.rollover_mode == ROLLOVER_MODE_CARRY {
+            carried_reward = leftover;
+        }else{
+            ctx.accounts.staking_data.unallocated_reward = ctx.accounts.staking_data.unallocated_reward.checked_add(leftover).ok_or(StakingErrors::MathOverflow)?;
+        }
+        let capped_reward = ctx.accounts.staking_data.capped_reward;
+        ctx.accounts.staking_data.capped_reward = 0;
+        //penalty redistributed since the last timeframe ended joins the new one whatever the rollover mode
+        let redistributed_reward = ctx.accounts.staking_data.pending_redistribution;
+        ctx.accounts.staking_data.pending_redistribution = 0;
+
+        ctx.accounts.staking_data.pool_reward = amount.checked_add(carried_reward).ok_or(StakingErrors::MathOverflow)?
+            .checked_add(redistributed_reward).ok_or(StakingErrors::MathOverflow)?;
+        ctx.accounts.staking_data.total_funded = ctx.accounts.staking_data.total_funded.checked_add(amount).ok_or(StakingErrors::MathOverflow)?;
         ctx.accounts.staking_data.timeframe_in_second = timeframe_in_second;
         ctx.accounts.staking_data.timeframe_started = now_ts;
+
+        emit!(PoolFunded {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            funder: *ctx.accounts.authority.key,
+            amount: amount,
+            pool_reward: ctx.accounts.staking_data.pool_reward,
+            timeframe_in_second: timeframe_in_second,
+            total_funded: ctx.accounts.staking_data.total_funded,
+            capped_reward: capped_reward,
+            carried_reward: carried_reward,
+            redistributed_reward: redistributed_reward,
+            unallocated_reward: ctx.accounts.staking_data.unallocated_reward,
+            rewarder_amount: rewarder_amount,
+            time: now_ts,
+        });
         Ok(())
     }
 
-    pub fn change_setting(ctx: Context<ChnageSetting>, apy_max: u32, min_stake_period: u64) -> ProgramResult {
-        if ctx.accounts.staking_data.timeframe_in_second > 0 && min_stake_period > ctx.accounts.staking_data.timeframe_in_second {
-            return Err(StakingErrors::MinStakePeriodMustBeLessThanCurrentTimeFrame.into());
-        }    
+    //adds to the running timeframe without restarting it, the end can move out by extend_in_second
+    pub fn top_up(ctx: Context<Funding>, amount: u64, extend_in_second: u64) -> ProgramResult {
+        let now_ts = Clock::get()?.unix_timestamp as u64;
 
-        if apy_max < 100{
-            return Err(StakingErrors::ApyMaxMustBigThan100.into());
+        if amount == 0{
+            return Err(StakingErrors::AmountMustBigThanZero.into());
         }
-        if apy_max >= 10000{
-            return Err(StakingErrors::ApyMaxMustLessThan10000.into());
+
+        //accrue at the current rate up to now before it changes, a queued epoch may become the timeframe
+        ctx.accounts.staking_data.update_reward_per_token(now_ts)?;
+
+        let time_frame_start = ctx.accounts.staking_data.timeframe_started;
+        let time_frame_end = time_frame_start + ctx.accounts.staking_data.timeframe_in_second;
+        if ctx.accounts.staking_data.timeframe_in_second == 0 || now_ts >= time_frame_end {
+            return Err(StakingErrors::TimeframeEnded.into());
         }
-        ctx.accounts.staking_data.apy_max = apy_max;
-        ctx.accounts.staking_data.min_stake_period = min_stake_period;
+
+        if amount > ctx.accounts.funder_account.amount {
+            return Err(StakingErrors::InSufficientBalance.into());
+        }
+
+        let new_time_frame_end = time_frame_end.checked_add(extend_in_second).ok_or(StakingErrors::MathOverflow)?;
+        if let Some(epoch) = ctx.accounts.staking_data.emission_epochs.first() {
+            if new_time_frame_end > epoch.start {
+                return Err(StakingErrors::InvalidEmissionEpoch.into());
+            }
+        }
+
+        token::transfer(
+            ctx.accounts.into_transfer_to_rewarder_context(),
+            amount,
+        )?;
+
+        ctx.accounts.staking_data.top_up_timeframe(amount, extend_in_second, now_ts)?;
+        ctx.accounts.staking_data.total_funded = ctx.accounts.staking_data.total_funded.checked_add(amount).ok_or(StakingErrors::MathOverflow)?;
+
+        emit!(PoolToppedUp {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            funder: *ctx.accounts.authority.key,
+            amount: amount,
+            pool_reward: ctx.accounts.staking_data.pool_reward,
+            timeframe_in_second: ctx.accounts.staking_data.timeframe_in_second,
+            total_funded: ctx.accounts.staking_data.total_funded,
+            time: now_ts,
+        });
+        Ok(())
+    }
+
+    //queues epochs after the running timeframe, each one is funded now and becomes the timeframe at its start
+    pub fn queue_emission_epochs(ctx: Context<Funding>, epochs: Vec<EmissionEpoch>) -> ProgramResult {
+        let now_ts = Clock::get()?.unix_timestamp as u64;
+
+        ctx.accounts.staking_data.update_reward_per_token(now_ts)?;
+
+        if epochs.len() == 0 {
+            return Err(StakingErrors::InvalidEmissionEpoch.into());
+        }
+        if ctx.accounts.staking_data.emission_epochs.len() + epochs.len() > StakingData::MAX_EMISSION_EPOCHS {
+            return Err(StakingErrors::ReachedMaxEmissionEpochs.into());
+        }
+
+        let mut epoch_start = ctx.accounts.staking_data.emission_end().max(now_ts);
+        let mut amount: u64 = 0;
+        for epoch in epochs.iter() {
+            if epoch.amount == 0 {
+                return Err(StakingErrors::AmountMustBigThanZero.into());
+            }
+            if epoch.start < epoch_start || epoch.end <= epoch.start {
+                return Err(StakingErrors::InvalidEmissionEpoch.into());
+            }
+            if epoch.end - epoch.start < ctx.accounts.staking_data.min_timeframe_in_second {
+                return Err(StakingErrors::TimeframeMustBigThanMin.into());
+            }
+            if epoch.end - epoch.start < ctx.accounts.staking_data.min_stake_period {
+                return Err(StakingErrors::TimeframeMustBigThanMinStakePeriod.into());
+            }
+            epoch_start = epoch.end;
+            amount = amount.checked_add(epoch.amount).ok_or(StakingErrors::MathOverflow)?;
+        }
+
+        if amount > ctx.accounts.funder_account.amount {
+            return Err(StakingErrors::InSufficientBalance.into());
+        }
+
+        token::transfer(
+            ctx.accounts.into_transfer_to_rewarder_context(),
+            amount,
+        )?;
+
+        ctx.accounts.staking_data.emission_epochs.extend(epochs.iter().cloned());
+        ctx.accounts.staking_data.check_size()?;
+        ctx.accounts.staking_data.total_funded = ctx.accounts.staking_data.total_funded.checked_add(amount).ok_or(StakingErrors::MathOverflow)?;
+
+        emit!(EmissionEpochsQueued {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            funder: *ctx.accounts.authority.key,
+            epochs: epochs,
+            amount: amount,
+            queued_epochs: ctx.accounts.staking_data.emission_epochs.len() as u8,
+            total_funded: ctx.accounts.staking_data.total_funded,
+            time: now_ts,
+        });
+        Ok(())
+    }
+
+    pub fn initialize_donor(ctx: Context<InitializeDonor>) -> ProgramResult {
+        let donor_record = &mut ctx.accounts.donor_record;
+        donor_record.staking_account = *ctx.accounts.staking_data.to_account_info().key;
+        donor_record.donor = *ctx.accounts.authority.key;
+        donor_record.total_donated = 0;
+        donor_record.last_donated_time = 0;
+        Ok(())
+    }
+
+    //adds to the running timeframe with its end kept, or to the next queued epoch
+    pub fn donate(ctx: Context<Donate>, amount: u64) -> ProgramResult {
+        let now_ts = Clock::get()?.unix_timestamp as u64;
+
+        if !ctx.accounts.staking_data.donations_enabled {
+            return Err(StakingErrors::DonationsNotEnabled.into());
+        }
+
+        if amount == 0{
+            return Err(StakingErrors::AmountMustBigThanZero.into());
+        }
+
+        if amount > ctx.accounts.donor_account.amount {
+            return Err(StakingErrors::InSufficientBalance.into());
+        }
+
+        ctx.accounts.staking_data.update_reward_per_token(now_ts)?;
+        let time_frame_end = ctx.accounts.staking_data.timeframe_started + ctx.accounts.staking_data.timeframe_in_second;
+        let running = ctx.accounts.staking_data.timeframe_in_second > 0 && now_ts < time_frame_end;
+        if !running && ctx.accounts.staking_data.emission_epochs.len() == 0 {
+            return Err(StakingErrors::NoEmissionWindow.into());
+        }
+
+        token::transfer(
+            ctx.accounts.into_transfer_to_rewarder_context(),
+            amount,
+        )?;
+
+        let staking_data = &mut ctx.accounts.staking_data;
+        if running {
+            staking_data.top_up_timeframe(amount, 0, now_ts)?;
+        }else{
+            staking_data.emission_epochs[0].amount = staking_data.emission_epochs[0].amount.checked_add(amount).ok_or(StakingErrors::MathOverflow)?;
+        }
+        staking_data.total_funded = staking_data.total_funded.checked_add(amount).ok_or(StakingErrors::MathOverflow)?;
+        staking_data.total_donated = staking_data.total_donated.checked_add(amount).ok_or(StakingErrors::MathOverflow)?;
+
+        let donor_record = &mut ctx.accounts.donor_record;
+        donor_record.total_donated = donor_record.total_donated.checked_add(amount).ok_or(StakingErrors::MathOverflow)?;
+        donor_record.last_donated_time = now_ts;
+
+        emit!(Donated {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            donor: *ctx.accounts.authority.key,
+            amount: amount,
+            donor_total: ctx.accounts.donor_record.total_donated,
+            pool_reward: ctx.accounts.staking_data.pool_reward,
+            total_donated: ctx.accounts.staking_data.total_donated,
+            time: now_ts,
+        });
+        Ok(())
+    }
+
+    pub fn add_reward_stream(
+        ctx: Context<AddRewardStream>,
+        apy_max: u32,
+        min_timeframe_in_second: u64,
+    ) -> ProgramResult {
+        if ctx.accounts.staking_data.reward_streams >= StakingData::MAX_REWARD_STREAMS {
+            return Err(StakingErrors::ReachedMaxRewardStreams.into());
+        }
+
+        //streams cap in basis points, 0 for no cap
+        check_apy_max(apy_max, true)?;
+
+        if min_timeframe_in_second < 1{
+            return Err(StakingErrors::MinTimeFrameMustBigThanZero.into());
+        }
+
+        let now_ts = Clock::get()?.unix_timestamp as u64;
+        let index = ctx.accounts.staking_data.reward_streams;
+        let reward_stream = &mut ctx.accounts.reward_stream;
+
+        reward_stream.staking_account = *ctx.accounts.staking_data.to_account_info().key;
+        reward_stream.index = index;
+        reward_stream.funder_authority = *ctx.accounts.funder_authority.key;
+        reward_stream.reward_mint = *ctx.accounts.reward_mint.to_account_info().key;
+        reward_stream.reward_decimals = ctx.accounts.reward_mint.decimals;
+        reward_stream.rewarder_account = *ctx.accounts.rewarder_account.to_account_info().key;
+        reward_stream.total_funded = 0;
+        reward_stream.total_reward_paid = 0;
+
+        reward_stream.min_timeframe_in_second = min_timeframe_in_second;
+        reward_stream.timeframe_in_second = 0;
+        reward_stream.timeframe_started = 0;
+        reward_stream.pool_reward = 0;
+        reward_stream.apy_max = apy_max;
+
+        reward_stream.reward_per_token_stored = 0;
+        reward_stream.last_update_time = now_ts;
+        reward_stream.total_reward_accrued = 0;
+
+        ctx.accounts.staking_data.reward_streams = index + 1;
+
+        let (authority, _authority_bump) =
+            Pubkey::find_program_address(&[STAKING_AUTH_PDA_SEED, ctx.accounts.staking_data.to_account_info().key.as_ref()], ctx.program_id);
+        token::set_authority(
+            ctx.accounts.into_set_rewarder_authority_context(),
+            AuthorityType::AccountOwner, Some(authority),
+        )?;
+
+        emit!(RewardStreamAdded {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            stream: *ctx.accounts.reward_stream.to_account_info().key,
+            index: index,
+            funder_authority: *ctx.accounts.funder_authority.key,
+            reward_mint: *ctx.accounts.reward_mint.to_account_info().key,
+            apy_max: apy_max,
+            min_timeframe_in_second: min_timeframe_in_second,
+            time: now_ts,
+        });
+        Ok(())
+    }
+
+    pub fn fund_reward_stream(ctx: Context<FundRewardStream>, amount: u64, timeframe_in_second: u64) -> ProgramResult {
+        let now_ts = Clock::get()?.unix_timestamp as u64;
+
+        if amount == 0{
+            return Err(StakingErrors::AmountMustBigThanZero.into());
+        }
+
+        if timeframe_in_second < ctx.accounts.reward_stream.min_timeframe_in_second{
+            return Err(StakingErrors::TimeframeMustBigThanMin.into());
+        }
+
+        if timeframe_in_second < ctx.accounts.staking_data.min_stake_period{
+            return Err(StakingErrors::TimeframeMustBigThanMinStakePeriod.into());
+        }
+
+        if amount > ctx.accounts.funder_account.amount {
+            return Err(StakingErrors::InSufficientBalance.into());
+        }
+
+        //close the current timeframe in the accumulator
+        let stream_staked = ctx.accounts.staking_data.stream_staked();
+        ctx.accounts.reward_stream.update_reward_per_token(stream_staked, now_ts)?;
+
+        //whatever the rewarder holds beyond the pending rewards rolls into the new timeframe
+        let rewarder_rest_amount = ctx.accounts.rewarder_account.amount.saturating_sub(ctx.accounts.reward_stream.total_reward_in_pending());
+
+        token::transfer(
+            ctx.accounts.into_transfer_to_rewarder_context(),
+            amount,
+        )?;
+        ctx.accounts.reward_stream.pool_reward = rewarder_rest_amount.checked_add(amount).ok_or(StakingErrors::MathOverflow)?;
+        ctx.accounts.reward_stream.total_funded = ctx.accounts.reward_stream.total_funded.checked_add(amount).ok_or(StakingErrors::MathOverflow)?;
+        ctx.accounts.reward_stream.timeframe_in_second = timeframe_in_second;
+        ctx.accounts.reward_stream.timeframe_started = now_ts;
+
+        emit!(RewardStreamFunded {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            stream: *ctx.accounts.reward_stream.to_account_info().key,
+            funder: *ctx.accounts.authority.key,
+            amount: amount,
+            pool_reward: ctx.accounts.reward_stream.pool_reward,
+            timeframe_in_second: timeframe_in_second,
+            total_funded: ctx.accounts.reward_stream.total_funded,
+            time: now_ts,
+        });
+        Ok(())
+    }
+
+    pub fn change_setting(ctx: Context<ChnageSetting>, apy_max: u32, min_stake_period: u64, vesting_duration: u64, vesting_cliff: u64) -> ProgramResult {
+        if ctx.accounts.staking_data.timeframe_in_second > 0 && min_stake_period > ctx.accounts.staking_data.timeframe_in_second {
+            return Err(StakingErrors::MinStakePeriodMustBeLessThanCurrentTimeFrame.into());
+        }    
+
+        //apy_max keeps the unit of the pool, legacy pools stay on the percent cap
+        check_apy_max(apy_max, ctx.accounts.staking_data.apy_max_in_bps)?;
+        if vesting_cliff > vesting_duration {
+            return Err(StakingErrors::VestingCliffMustLessThanDuration.into());
+        }
+        ctx.accounts.staking_data.apy_max = apy_max;
+        ctx.accounts.staking_data.min_stake_period = min_stake_period;
+        ctx.accounts.staking_data.vesting_duration = vesting_duration;
+        ctx.accounts.staking_data.vesting_cliff = vesting_cliff;
+        ctx.accounts.staking_data.check_vault_exit()?;
+
+        emit!(SettingsChanged {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            authority: *ctx.accounts.authority.key,
+            apy_max: apy_max,
+            min_stake_period: min_stake_period,
+            vesting_duration: vesting_duration,
+            vesting_cliff: vesting_cliff,
+            time: Clock::get()?.unix_timestamp as u64,
+        });
+        Ok(())
+    }
+
+    pub fn set_unbonding_period(ctx: Context<ChnageSetting>, unbonding_period: u64) -> ProgramResult {
+        ctx.accounts.staking_data.unbonding_period = unbonding_period;
+        ctx.accounts.staking_data.check_vault_exit()?;
+
+        emit!(UnbondingPeriodChanged {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            authority: *ctx.accounts.authority.key,
+            unbonding_period: unbonding_period,
+            time: Clock::get()?.unix_timestamp as u64,
+        });
+        Ok(())
+    }
+
+    pub fn set_penalty(ctx: Context<SetPenalty>, penalty_bps: u16, penalty_period: u64, penalty_mode: u8, penalty_treasury: Pubkey) -> ProgramResult {
+        if penalty_bps > 10000 {
+            return Err(StakingErrors::PenaltyMustLessThan10000.into());
+        }
+        if penalty_mode > PENALTY_MODE_BURN {
+            return Err(StakingErrors::InvalidPenaltyMode.into());
+        }
+        //penalty is paid in the staking token, the rewarder only holds the reward token
+        if penalty_mode == PENALTY_MODE_REDISTRIBUTE && ctx.accounts.staking_data.reward_mint() != ctx.accounts.staking_data.mint_address {
+            return Err(StakingErrors::PenaltyMintMismatch.into());
+        }
+        if penalty_mode == PENALTY_MODE_TREASURY {
+            let treasury = Account::<TokenAccount>::try_from(&ctx.accounts.treasury_account)?;
+            if *ctx.accounts.treasury_account.key != penalty_treasury || treasury.mint != ctx.accounts.staking_data.mint_address {
+                return Err(StakingErrors::InvalidPenaltyTreasury.into());
+            }
+        }
+
+        ctx.accounts.staking_data.penalty_bps = penalty_bps;
+        ctx.accounts.staking_data.penalty_period = penalty_period;
+        ctx.accounts.staking_data.penalty_mode = penalty_mode;
+        ctx.accounts.staking_data.penalty_treasury = penalty_treasury;
+        ctx.accounts.staking_data.check_vault_exit()?;
+
+        emit!(PenaltyChanged {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            authority: *ctx.accounts.authority.key,
+            penalty_bps: penalty_bps,
+            penalty_period: penalty_period,
+            penalty_mode: penalty_mode,
+            penalty_treasury: penalty_treasury,
+            time: Clock::get()?.unix_timestamp as u64,
+        });
+        Ok(())
+    }
+    //only new locks use the table, running locks keep their boost
+    pub fn set_lock_tiers(ctx: Context<ChnageSetting>, lock_tiers: Vec<LockTier>) -> ProgramResult {
+        if lock_tiers.len() > StakingData::MAX_LOCK_TIERS {
+            return Err(StakingErrors::ReachedMaxLockTiers.into());
+        }
+        for tier in lock_tiers.iter() {
+            if tier.multiplier_bps < 10000 {
+                return Err(StakingErrors::LockMultiplierMustBigThan10000.into());
+            }
+        }
+        ctx.accounts.staking_data.lock_tiers = lock_tiers.clone();
+        ctx.accounts.staking_data.check_size()?;
+
+        emit!(LockTiersChanged {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            authority: *ctx.accounts.authority.key,
+            lock_tiers: lock_tiers,
+            time: Clock::get()?.unix_timestamp as u64,
+        });
+        Ok(())
+    }
+
+    pub fn add_fixed_term_product(ctx: Context<AddFixedTermProduct>, term: u64, apr_bps: u32, capacity: u64) -> ProgramResult {
+        if ctx.accounts.staking_data.fixed_term_products == u8::MAX {
+            return Err(StakingErrors::ReachedMaxFixedTermProducts.into());
+        }
+        if term == 0 {
+            return Err(StakingErrors::TermMustBigThanZero.into());
+        }
+
+        let index = ctx.accounts.staking_data.fixed_term_products;
+        let product = &mut ctx.accounts.product;
+        product.staking_account = *ctx.accounts.staking_data.to_account_info().key;
+        product.index = index;
+        product.term = term;
+        product.apr_bps = apr_bps;
+        product.capacity = capacity;
+        product.enabled = true;
+        product.total_deposited = 0;
+        product.total_reserved_reward = 0;
+
+        ctx.accounts.staking_data.fixed_term_products = index + 1;
+
+        emit!(FixedTermProductChanged {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            product: *ctx.accounts.product.to_account_info().key,
+            term: term,
+            apr_bps: apr_bps,
+            capacity: capacity,
+            enabled: true,
+            time: Clock::get()?.unix_timestamp as u64,
+        });
+        Ok(())
+    }
+
+    //closes or reopens a product to new deposits, running deposits keep their terms
+    pub fn set_fixed_term_product(ctx: Context<SetFixedTermProduct>, enabled: bool) -> ProgramResult {
+        ctx.accounts.product.enabled = enabled;
+
+        emit!(FixedTermProductChanged {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            product: *ctx.accounts.product.to_account_info().key,
+            term: ctx.accounts.product.term,
+            apr_bps: ctx.accounts.product.apr_bps,
+            capacity: ctx.accounts.product.capacity,
+            enabled: enabled,
+            time: Clock::get()?.unix_timestamp as u64,
+        });
+        Ok(())
+    }
+
+    pub fn deposit_fixed_term(ctx: Context<DepositFixedTerm>, amount: u64, nonce: u64) -> ProgramResult {
+        if amount == 0{
+            return Err(StakingErrors::AmountMustBigThanZero.into());
+        }
+
+        if amount > ctx.accounts.staker_account.amount {
+            return Err(StakingErrors::InSufficientBalance.into());
+        }
+
+        if !ctx.accounts.product.enabled {
+            return Err(StakingErrors::FixedTermProductClosed.into());
+        }
+
+        let total_deposited = ctx.accounts.product.total_deposited.checked_add(amount).ok_or(StakingErrors::MathOverflow)?;
+        if ctx.accounts.product.capacity > 0 && total_deposited > ctx.accounts.product.capacity {
+            return Err(StakingErrors::FixedTermCapacityExceeded.into());
+        }
+
+        let now_ts = Clock::get()?.unix_timestamp as u64;
+        let interest = calculate_fixed_term_interest(amount, ctx.accounts.product.apr_bps, ctx.accounts.product.term,
+            ctx.accounts.staking_data.mint_decimals, ctx.accounts.staking_data.reward_decimals)?;
+
+        //the interest is reserved out of what the rewarder doesn't owe yet
+        ctx.accounts.staking_data.update_reward_per_token(now_ts)?;
+        if interest > ctx.accounts.staking_data.free_reward(ctx.accounts.rewarder_account.amount, now_ts)? {
+            return Err(StakingErrors::InSufficientRewardForFixedTerm.into());
+        }
+
+        token::transfer(
+            ctx.accounts.into_transfer_to_escrow_context(),
+            amount,
+        )?;
+
+        let maturity_time = now_ts + ctx.accounts.product.term;
+        let deposit = &mut ctx.accounts.deposit;
+        deposit.product = *ctx.accounts.product.to_account_info().key;
+        deposit.owner = *ctx.accounts.authority.key;
+        deposit.nonce = nonce;
+        deposit.principal = amount;
+        deposit.interest = interest;
+        deposit.start_time = now_ts;
+        deposit.maturity_time = maturity_time;
+
+        //update product
+        ctx.accounts.product.total_deposited = total_deposited;
+        ctx.accounts.product.total_reserved_reward = ctx.accounts.product.total_reserved_reward + interest;
+
+        //update staking data
+        ctx.accounts.staking_data.total_fixed_term_principal = ctx.accounts.staking_data.total_fixed_term_principal + amount;
+        ctx.accounts.staking_data.total_reserved_reward = ctx.accounts.staking_data.total_reserved_reward + interest;
+
+        emit!(FixedTermDeposited {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            product: *ctx.accounts.product.to_account_info().key,
+            user: *ctx.accounts.authority.key,
+            principal: amount,
+            interest: interest,
+            maturity_time: maturity_time,
+            total_reserved_reward: ctx.accounts.staking_data.total_reserved_reward,
+            time: now_ts,
+        });
+        Ok(())
+    }
+
+    //pays principal and interest at maturity and closes the deposit
+    pub fn redeem_fixed_term(ctx: Context<RedeemFixedTerm>) -> ProgramResult {
+        let now_ts = Clock::get()?.unix_timestamp as u64;
+        if now_ts < ctx.accounts.deposit.maturity_time {
+            return Err(StakingErrors::FixedTermNotMatured.into());
+        }
+
+        let principal = ctx.accounts.deposit.principal;
+        let interest = ctx.accounts.deposit.interest;
+
+        let authority_seeds = &[&STAKING_AUTH_PDA_SEED[..], ctx.accounts.staking_data.to_account_info().key.as_ref(), &[ctx.accounts.staking_data.bump_auth]];
+        token::transfer(
+            ctx.accounts
+                .into_transfer_to_staker_context()
+                .with_signer(&[&authority_seeds[..]]),
+                principal,
+        )?;
+        if interest > 0 {
+            token::transfer(
+                ctx.accounts
+                    .into_transfer_from_rewarder_to_staker_context()
+                    .with_signer(&[&authority_seeds[..]]),
+                    interest,
+            )?;
+        }
+
+        //update product
+        ctx.accounts.product.total_deposited = ctx.accounts.product.total_deposited - principal;
+        ctx.accounts.product.total_reserved_reward = ctx.accounts.product.total_reserved_reward - interest;
+
+        //update staking data
+        ctx.accounts.staking_data.total_fixed_term_principal = ctx.accounts.staking_data.total_fixed_term_principal - principal;
+        ctx.accounts.staking_data.total_reserved_reward = ctx.accounts.staking_data.total_reserved_reward - interest;
+
+        emit!(FixedTermRedeemed {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            product: *ctx.accounts.product.to_account_info().key,
+            user: *ctx.accounts.authority.key,
+            principal: principal,
+            interest: interest,
+            total_reserved_reward: ctx.accounts.staking_data.total_reserved_reward,
+            time: now_ts,
+        });
+        Ok(())
+    }
+
+    //creates the share mint of the auto-compounding vault, the vault stakes in the pool like one big staker
+    pub fn init_vault(ctx: Context<InitVault>) -> ProgramResult {
+        if ctx.accounts.staking_data.vault_enabled() {
+            return Err(StakingErrors::VaultAlreadyEnabled.into());
+        }
+        //compounding moves the reward into the escrow
+        if ctx.accounts.staking_data.reward_mint() != ctx.accounts.staking_data.mint_address {
+            return Err(StakingErrors::VaultMintMismatch.into());
+        }
+
+        let now_ts = Clock::get()?.unix_timestamp as u64;
+        ctx.accounts.staking_data.update_reward_per_token(now_ts)?;
+        ctx.accounts.staking_data.share_mint = *ctx.accounts.share_mint.to_account_info().key;
+        ctx.accounts.staking_data.check_vault_exit()?;
+        ctx.accounts.staking_data.vault_staked = 0;
+        ctx.accounts.staking_data.vault_reward_per_token_paid = ctx.accounts.staking_data.reward_per_token_stored;
+        ctx.accounts.staking_data.vault_settled_time = now_ts;
+        ctx.accounts.staking_data.vault_cap_headroom = 0;
+
+        emit!(VaultChanged {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            user: *ctx.accounts.authority.key,
+            deposited: 0,
+            redeemed: 0,
+            shares: 0,
+            compounded: 0,
+            vault_staked: 0,
+            share_supply: 0,
+            time: now_ts,
+        });
+        Ok(())
+    }
+
+    pub fn deposit(ctx: Context<Vault>, amount: u64) -> ProgramResult {
+        if amount == 0{
+            return Err(StakingErrors::AmountMustBigThanZero.into());
+        }
+
+        if amount > ctx.accounts.staker_account.amount {
+            return Err(StakingErrors::InSufficientBalance.into());
+        }
+
+        if !ctx.accounts.staking_data.vault_enabled() {
+            return Err(StakingErrors::VaultNotEnabled.into());
+        }
+
+        let now_ts = Clock::get()?.unix_timestamp as u64;
+        let authority_seeds = &[&STAKING_AUTH_PDA_SEED[..], ctx.accounts.staking_data.to_account_info().key.as_ref(), &[ctx.accounts.staking_data.bump_auth]];
+
+        //compound first so the deposit gets the current exchange rate
+        let compounded = ctx.accounts.staking_data.compound_vault(now_ts)?;
+        if compounded > 0 {
+            token::transfer(
+                ctx.accounts
+                    .into_compound_context()
+                    .with_signer(&[&authority_seeds[..]]),
+                    compounded,
+            )?;
+        }
+
+        let share_supply = ctx.accounts.share_mint.supply;
+        let shares = calculate_shares(amount, ctx.accounts.staking_data.vault_staked, share_supply)?;
+        if shares == 0 {
+            return Err(StakingErrors::AmountMustBigThanZero.into());
+        }
+
+        token::transfer(
+            ctx.accounts.into_transfer_to_escrow_context(),
+            amount,
+        )?;
+        token::mint_to(
+            ctx.accounts
+                .into_mint_shares_context()
+                .with_signer(&[&authority_seeds[..]]),
+                shares,
+        )?;
+
+        //update staking data
+        ctx.accounts.staking_data.vault_staked = ctx.accounts.staking_data.vault_staked + amount;
+        ctx.accounts.staking_data.total_staked = ctx.accounts.staking_data.total_staked + amount;
+
+        emit!(VaultChanged {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            user: *ctx.accounts.authority.key,
+            deposited: amount,
+            redeemed: 0,
+            shares: shares,
+            compounded: compounded,
+            vault_staked: ctx.accounts.staking_data.vault_staked,
+            share_supply: share_supply + shares,
+            time: now_ts,
+        });
+        Ok(())
+    }
+
+    pub fn redeem(ctx: Context<Vault>, shares: u64) -> ProgramResult {
+        if shares == 0{
+            return Err(StakingErrors::AmountMustBigThanZero.into());
+        }
+
+        if shares > ctx.accounts.share_account.amount {
+            return Err(StakingErrors::InSufficientBalance.into());
+        }
+
+        let now_ts = Clock::get()?.unix_timestamp as u64;
+        let authority_seeds = &[&STAKING_AUTH_PDA_SEED[..], ctx.accounts.staking_data.to_account_info().key.as_ref(), &[ctx.accounts.staking_data.bump_auth]];
+
+        let compounded = ctx.accounts.staking_data.compound_vault(now_ts)?;
+        if compounded > 0 {
+            token::transfer(
+                ctx.accounts
+                    .into_compound_context()
+                    .with_signer(&[&authority_seeds[..]]),
+                    compounded,
+            )?;
+        }
+
+        let share_supply = ctx.accounts.share_mint.supply;
+        let amount = calculate_redeem_amount(shares, ctx.accounts.staking_data.vault_staked, share_supply)?;
+
+        token::burn(
+            ctx.accounts.into_burn_shares_context(),
+            shares,
+        )?;
+        if amount > 0 {
+            token::transfer(
+                ctx.accounts
+                    .into_transfer_to_staker_context()
+                    .with_signer(&[&authority_seeds[..]]),
+                    amount,
+            )?;
+        }
+
+        //update staking data
+        ctx.accounts.staking_data.vault_staked = ctx.accounts.staking_data.vault_staked - amount;
+        ctx.accounts.staking_data.total_staked = ctx.accounts.staking_data.total_staked - amount;
+
+        emit!(VaultChanged {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            user: *ctx.accounts.authority.key,
+            deposited: 0,
+            redeemed: amount,
+            shares: shares,
+            compounded: compounded,
+            vault_staked: ctx.accounts.staking_data.vault_staked,
+            share_supply: share_supply - shares,
+            time: now_ts,
+        });
+        Ok(())
+    }
+
+    //permissionless, moves the vault reward into the escrow and emits the exchange rate
+    pub fn compound_vault(ctx: Context<CompoundVault>) -> ProgramResult {
+        if !ctx.accounts.staking_data.vault_enabled() {
+            return Err(StakingErrors::VaultNotEnabled.into());
+        }
+
+        let now_ts = Clock::get()?.unix_timestamp as u64;
+        let compounded = ctx.accounts.staking_data.compound_vault(now_ts)?;
+        if compounded > 0 {
+            let authority_seeds = &[&STAKING_AUTH_PDA_SEED[..], ctx.accounts.staking_data.to_account_info().key.as_ref(), &[ctx.accounts.staking_data.bump_auth]];
+            token::transfer(
+                ctx.accounts
+                    .into_compound_context()
+                    .with_signer(&[&authority_seeds[..]]),
+                    compounded,
+            )?;
+        }
+
+        emit!(VaultChanged {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            user: Pubkey::default(),
+            deposited: 0,
+            redeemed: 0,
+            shares: 0,
+            compounded: compounded,
+            vault_staked: ctx.accounts.staking_data.vault_staked,
+            share_supply: ctx.accounts.share_mint.supply,
+            time: now_ts,
+        });
+        Ok(())
+    }
+
+    pub fn init_receipt(ctx: Context<InitReceipt>) -> ProgramResult {
+        if ctx.accounts.staking_data.receipt_enabled() {
+            return Err(StakingErrors::ReceiptAlreadyEnabled.into());
+        }
+
+        let now_ts = Clock::get()?.unix_timestamp as u64;
+        ctx.accounts.staking_data.receipt_mint = *ctx.accounts.receipt_mint.to_account_info().key;
+
+        emit!(ReceiptEnabled {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            authority: *ctx.accounts.authority.key,
+            receipt_mint: ctx.accounts.staking_data.receipt_mint,
+            time: now_ts,
+        });
+        Ok(())
+    }
+
+    //moves part of the position with its receipts, both sides are settled and checkpointed first so rewards follow the holder from now on
+    pub fn transfer_receipt(ctx: Context<TransferReceipt>, amount: u64) -> ProgramResult {
+        if amount == 0{
+            return Err(StakingErrors::AmountMustBigThanZero.into());
+        }
+
+        if !ctx.accounts.staking_data.receipt_enabled() {
+            return Err(StakingErrors::ReceiptNotEnabled.into());
+        }
+
+        if amount > ctx.accounts.stake_state_account.receipt_staked || amount > ctx.accounts.receipt_account.amount {
+            return Err(StakingErrors::InSufficientStakedBalance.into());
+        }
+
+        let now_ts = Clock::get()?.unix_timestamp as u64;
+        if amount > ctx.accounts.stake_state_account.unlocked_amount(now_ts) {
+            return Err(StakingErrors::StakeIsLocked.into());
+        }
+
+        //only principal past the penalty clock moves, the destination keeps its own clock
+        let penalty_period = ctx.accounts.staking_data.penalty_period;
+        let (penalized, _) = ctx.accounts.stake_state_account.take_penalized(amount, penalty_period, now_ts);
+        if ctx.accounts.staking_data.penalty_bps > 0 && penalized > 0 {
+            return Err(StakingErrors::ReceiptUnderPenalty.into());
+        }
+        ctx.accounts.to_stake_state_account.add_penalty_tranche(0, penalty_period, now_ts)?;
+
+        if ctx.accounts.staking_data.index_of_staker(ctx.accounts.stake_state_account.my_crc) >= 0
+            || ctx.accounts.staking_data.index_of_staker(ctx.accounts.to_stake_state_account.my_crc) >= 0 {
+            return Err(StakingErrors::StakerNotMigrated.into());
+        }
+
+        let receipt_mint = ctx.accounts.staking_data.receipt_mint;
+        ctx.accounts.to_stake_state_account.record_receipt_account(ctx.accounts.to_receipt_account.to_account_info().key, &receipt_mint)?;
+
+        //the pool effective stake doesn't change, only the split between the two positions
+        let mut streams = load_reward_streams(ctx.program_id, &ctx.accounts.staking_data, ctx.remaining_accounts)?;
+        settle_position(&mut ctx.accounts.staking_data, &mut streams, &mut ctx.accounts.stake_state_account, now_ts)?;
+        settle_position(&mut ctx.accounts.staking_data, &mut streams, &mut ctx.accounts.to_stake_state_account, now_ts)?;
+        exit_reward_streams(&streams, ctx.program_id)?;
+        checkpoint_receipts(&ctx.accounts.staking_data, &mut ctx.accounts.stake_state_account, &ctx.accounts.receipt_account.to_account_info())?;
+        checkpoint_receipts(&ctx.accounts.staking_data, &mut ctx.accounts.to_stake_state_account, &ctx.accounts.to_receipt_account.to_account_info())?;
+
+        token::transfer(
+            ctx.accounts.into_transfer_receipt_context(),
+            amount,
+        )?;
+
+        //the amount keeps its staked time and what it earned inside min stake period, the destination clock is weighted with it
+        ctx.accounts.stake_state_account.move_pending_share(&mut ctx.accounts.to_stake_state_account, amount)?;
+        ctx.accounts.stake_state_account.move_cap_headroom(Some(&mut *ctx.accounts.to_stake_state_account), amount)?;
+        let staked_time = ctx.accounts.stake_state_account.staked_time;
+        ctx.accounts.to_stake_state_account.add_staked(amount, staked_time)?;
+
+        ctx.accounts.stake_state_account.total_staked = ctx.accounts.stake_state_account.total_staked - amount;
+        ctx.accounts.stake_state_account.receipt_staked = ctx.accounts.stake_state_account.receipt_staked - amount;
+        ctx.accounts.stake_state_account.add_history(now_ts, 7, amount);
+
+        ctx.accounts.to_stake_state_account.receipt_staked = ctx.accounts.to_stake_state_account.receipt_staked + amount;
+        ctx.accounts.to_stake_state_account.add_history(now_ts, 8, amount);
+
+        emit!(ReceiptTransferred {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            from: *ctx.accounts.authority.key,
+            to: ctx.accounts.to_stake_state_account.onwer_address,
+            amount: amount,
+            time: now_ts,
+        });
+        Ok(())
+    }
+
+    //receipts moved with a plain token transfer are redeemed against the position they came from,
+    //the reward they are owed and the unbonding go to the redeemer position
+    pub fn redeem_receipt(ctx: Context<RedeemReceipt>, amount: u64) -> ProgramResult {
+        if amount == 0{
+            return Err(StakingErrors::AmountMustBigThanZero.into());
+        }
+
+        if amount > ctx.accounts.receipt_account.amount {
+            return Err(StakingErrors::InSufficientBalance.into());
+        }
+
+        let now_ts = Clock::get()?.unix_timestamp as u64;
+        let stake_state = &ctx.accounts.stake_state_account;
+
+        //only the receipts the owner doesn't hold anymore, never around the lock or the penalty
+        let shortfall = stake_state.receipt_staked.saturating_sub(ctx.accounts.owner_receipt_account.amount);
+        let (penalty_tranche, _) = stake_state.penalty_tranche(ctx.accounts.staking_data.penalty_period, now_ts);
+        let penalized = amount.saturating_sub(stake_state.total_staked - penalty_tranche);
+        if amount > shortfall || amount > stake_state.unlocked_amount(now_ts)
+            || (ctx.accounts.staking_data.penalty_bps > 0 && penalized > 0) {
+            return Err(StakingErrors::ReceiptNotRedeemable.into());
+        }
+
+        if ctx.accounts.staking_data.index_of_staker(stake_state.my_crc) >= 0
+            || ctx.accounts.staking_data.index_of_staker(ctx.accounts.to_stake_state_account.my_crc) >= 0 {
+            return Err(StakingErrors::StakerNotMigrated.into());
+        }
+
+        token::burn(
+            ctx.accounts.into_burn_receipt_context(),
+            amount,
+        )?;
+
+        //with unbonding the amount waits in the redeemer position like an unstake
+        let unbonding_period = ctx.accounts.staking_data.unbonding_period;
+        if unbonding_period == 0 {
+            let authority_seeds = &[&STAKING_AUTH_PDA_SEED[..], ctx.accounts.staking_data.to_account_info().key.as_ref(), &[ctx.accounts.staking_data.bump_auth]];
+            token::transfer(
+                ctx.accounts
+                    .into_transfer_to_staker_context()
+                    .with_signer(&[&authority_seeds[..]]),
+                    amount,
+            )?;
+        }else{
+            ctx.accounts.staking_data.total_unbonding = ctx.accounts.staking_data.total_unbonding + amount;
+            ctx.accounts.to_stake_state_account.add_unbonding(amount, now_ts + unbonding_period)?;
+        }
+
+        let mut streams = load_reward_streams(ctx.program_id, &ctx.accounts.staking_data, ctx.remaining_accounts)?;
+        settle_position(&mut ctx.accounts.staking_data, &mut streams, &mut ctx.accounts.stake_state_account, now_ts)?;
+        forfeit_pending_reward(&mut ctx.accounts.staking_data, &mut streams, &mut ctx.accounts.stake_state_account, amount)?;
+        ctx.accounts.stake_state_account.move_cap_headroom(None, amount)?;
+        exit_reward_streams(&streams, ctx.program_id)?;
+        checkpoint_receipts(&ctx.accounts.staking_data, &mut ctx.accounts.stake_state_account, &ctx.accounts.owner_receipt_account.to_account_info())?;
+        let receipt_reward = ctx.accounts.stake_state_account.take_away_receipt_reward(amount)?;
+        ctx.accounts.to_stake_state_account.add_gained_reward(&receipt_reward)?;
+
+        //update staking data
+        ctx.accounts.staking_data.total_staked = ctx.accounts.staking_data.total_staked - amount;
+
+        //update staking state
+        ctx.accounts.stake_state_account.total_staked = ctx.accounts.stake_state_account.total_staked - amount;
+        ctx.accounts.stake_state_account.receipt_staked = ctx.accounts.stake_state_account.receipt_staked - amount;
+        ctx.accounts.stake_state_account.add_history(now_ts, 9, amount);
+        ctx.accounts.to_stake_state_account.add_history(now_ts, 9, amount);
+
+        emit!(ReceiptRedeemed {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            user: *ctx.accounts.authority.key,
+            position_owner: ctx.accounts.stake_state_account.onwer_address,
+            amount: amount,
+            total_staked: ctx.accounts.staking_data.total_staked,
+            time: now_ts,
+        });
+        Ok(())
+    }
+
+    //restakes the gained reward, staked_time is kept so compounding doesn't restart min stake period or the penalty
+    pub fn compound(ctx: Context<Compound>) -> ProgramResult {
+        if ctx.accounts.stake_state_account.onwer_address != *ctx.accounts.authority.key
+            && !ctx.accounts.stake_state_account.auto_compound {
+            return Err(StakingErrors::AutoCompoundNotEnabled.into());
+        }
+
+        if ctx.accounts.staking_data.reward_mint() != ctx.accounts.staking_data.mint_address {
+            return Err(StakingErrors::CompoundMintMismatch.into());
+        }
+
+        //restaked reward would skip the vesting schedule claim_reward puts it on
+        if ctx.accounts.staking_data.vesting_duration > 0 {
+            return Err(StakingErrors::CompoundWithVesting.into());
+        }
+
+        if ctx.accounts.staking_data.index_of_staker(ctx.accounts.stake_state_account.my_crc) >= 0 {
+            return Err(StakingErrors::StakerNotMigrated.into());
+        }
+
+        let now_ts = Clock::get()?.unix_timestamp as u64;
+        let staked_time = ctx.accounts.stake_state_account.staked_time;
+
+        //inside min stake period the reward is only pending, nothing is credited to compound
+        if now_ts < staked_time + ctx.accounts.staking_data.min_stake_period {
+            return Err(StakingErrors::InSufficientGainedReward.into());
+        }
+
+        let mut streams = load_reward_streams(ctx.program_id, &ctx.accounts.staking_data, ctx.remaining_accounts)?;
+        settle_position(&mut ctx.accounts.staking_data, &mut streams, &mut ctx.accounts.stake_state_account, now_ts)?;
+        exit_reward_streams(&streams, ctx.program_id)?;
+        checkpoint_receipts(&ctx.accounts.staking_data, &mut ctx.accounts.stake_state_account, &ctx.accounts.owner_receipt_account)?;
+
+        let amount = ctx.accounts.stake_state_account.gained_reward;
+        if amount == 0 {
+            return Err(StakingErrors::InSufficientGainedReward.into());
+        }
+
+        let authority_seeds = &[&STAKING_AUTH_PDA_SEED[..], ctx.accounts.staking_data.to_account_info().key.as_ref(), &[ctx.accounts.staking_data.bump_auth]];
+        token::transfer(
+            ctx.accounts
+                .into_compound_context()
+                .with_signer(&[&authority_seeds[..]]),
+                amount,
+        )?;
+
+        //update staking data
+        ctx.accounts.staking_data.total_reward_paid = ctx.accounts.staking_data.total_reward_paid + amount;
+        ctx.accounts.staking_data.total_staked = ctx.accounts.staking_data.total_staked + amount;
+
+        //update staking state
+        ctx.accounts.stake_state_account.gained_reward = 0;
+        ctx.accounts.stake_state_account.total_rewarded = ctx.accounts.stake_state_account.total_rewarded + amount;
+        ctx.accounts.stake_state_account.last_rewarded = now_ts;
+        ctx.accounts.stake_state_account.total_staked = ctx.accounts.stake_state_account.total_staked + amount;
+        ctx.accounts.stake_state_account.add_history(now_ts, 10, amount);
+
+        emit!(Compounded {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            user: ctx.accounts.stake_state_account.onwer_address,
+            caller: *ctx.accounts.authority.key,
+            amount: amount,
+            user_staked: ctx.accounts.stake_state_account.total_staked,
+            total_staked: ctx.accounts.staking_data.total_staked,
+            time: now_ts,
+        });
+        Ok(())
+    }
+
+    pub fn set_auto_compound(ctx: Context<SetAutoCompound>, auto_compound: bool) -> ProgramResult {
+        let now_ts = Clock::get()?.unix_timestamp as u64;
+        ctx.accounts.stake_state_account.auto_compound = auto_compound;
+
+        emit!(AutoCompoundChanged {
+            pool: ctx.accounts.stake_state_account.staking_account,
+            user: *ctx.accounts.authority.key,
+            auto_compound: auto_compound,
+            time: now_ts,
+        });
+        Ok(())
+    }
+
+    pub fn initialize_vesting(_ctx: Context<InitializeVesting>) -> ProgramResult {
+        Ok(())
+    }
+
+    pub fn withdraw_vested(ctx: Context<WithdrawVested>) -> ProgramResult {
+        let now_ts = Clock::get()?.unix_timestamp as u64;
+        let amount = ctx.accounts.stake_state_account.release_vested(now_ts)?;
+        if amount == 0 {
+            return Err(StakingErrors::NothingVested.into());
+        }
+
+        let authority_seeds = &[&STAKING_AUTH_PDA_SEED[..], ctx.accounts.staking_data.to_account_info().key.as_ref(), &[ctx.accounts.staking_data.bump_auth]];
+        token::transfer(
+            ctx.accounts
+                .into_transfer_to_claimer_context()
+                .with_signer(&[&authority_seeds[..]]),
+                amount,
+        )?;
+        ctx.accounts.stake_state_account.add_history(now_ts, 11, amount);
+
+        emit!(VestedWithdrawn {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            user: *ctx.accounts.authority.key,
+            amount: amount,
+            time: now_ts,
+        });
+        Ok(())
+    }
+
+    pub fn set_rollover_mode(ctx: Context<ChnageSetting>, rollover_mode: u8) -> ProgramResult {
+        if rollover_mode > ROLLOVER_MODE_FUNDER {
+            return Err(StakingErrors::InvalidRolloverMode.into());
+        }
+        ctx.accounts.staking_data.rollover_mode = rollover_mode;
+
+        emit!(RolloverModeChanged {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            authority: *ctx.accounts.authority.key,
+            rollover_mode: rollover_mode,
+            time: Clock::get()?.unix_timestamp as u64,
+        });
+        Ok(())
+    }
+
+    pub fn set_donations_enabled(ctx: Context<ChnageSetting>, donations_enabled: bool) -> ProgramResult {
+        ctx.accounts.staking_data.donations_enabled = donations_enabled;
+
+        emit!(DonationsChanged {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            authority: *ctx.accounts.authority.key,
+            donations_enabled: donations_enabled,
+            time: Clock::get()?.unix_timestamp as u64,
+        });
+        Ok(())
+    }
+
+    pub fn set_keeper_tip(ctx: Context<ChnageSetting>, keeper_tip: u64) -> ProgramResult {
+        ctx.accounts.staking_data.keeper_tip = keeper_tip;
+
+        emit!(KeeperTipChanged {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            authority: *ctx.accounts.authority.key,
+            keeper_tip: keeper_tip,
+            time: Clock::get()?.unix_timestamp as u64,
+        });
+        Ok(())
+    }
+
+    //permissionless checkpoint of a page of positions passed after the reward streams in remaining_accounts,
+    //positions with nothing to settle or legacy entries are skipped, only positions credited with reward earn the tip
+    pub fn settle(ctx: Context<Settle>) -> ProgramResult {
+        let now_ts = Clock::get()?.unix_timestamp as u64;
+        ctx.accounts.staking_data.update_reward_per_token(now_ts)?;
+
+        let mut streams = load_reward_streams(ctx.program_id, &ctx.accounts.staking_data, ctx.remaining_accounts)?;
+        for stream in streams.iter_mut() {
+            stream.update_reward_per_token(ctx.accounts.staking_data.stream_staked(), now_ts)?;
+        }
+        let positions = &ctx.remaining_accounts[streams.len()..];
+        if positions.len() == 0 || positions.len() > StakingData::MAX_SETTLE_PAGE {
+            return Err(StakingErrors::InvalidSettlePage.into());
+        }
+
+        let staking_account = *ctx.accounts.staking_data.to_account_info().key;
+        let mut settled: u64 = 0;
+        let mut settled_reward: u64 = 0;
+        for info in positions.iter() {
+            let mut stake_state = ProgramAccount::<StakingState>::try_from(ctx.program_id, info)?;
+            if stake_state.staking_account != staking_account || !info.is_writable {
+                return Err(StakingErrors::InvalidStakingStateAccount.into());
+            }
+            if ctx.accounts.staking_data.index_of_staker(stake_state.my_crc) >= 0 {
+                continue;
+            }
+            //an expired lock is dropped off the pool even when there is nothing new to settle,
+            //a position is up to date only once neither the pool nor any stream accrued since its checkpoint
+            if stake_state.reward_per_token_paid == ctx.accounts.staking_data.reward_per_token_stored
+                && streams.iter().all(|stream| stake_state.stream_reward_per_token_paid(stream.index) == stream.reward_per_token_stored)
+                && !stake_state.lock_expired(now_ts) {
+                continue;
+            }
+
+            let gained = settle_position(&mut ctx.accounts.staking_data, &mut streams, &mut stake_state, now_ts)?;
+            stake_state.exit(ctx.program_id)?;
+
+            //only positions credited with reward count for the tip, dust or pending reward earns the keeper nothing
+            if gained > 0 {
+                settled = settled + 1;
+                settled_reward = settled_reward.checked_add(gained).ok_or(StakingErrors::MathOverflow)?;
+            }
+        }
+        exit_reward_streams(&streams, ctx.program_id)?;
+
+        let max_tip = mul_div(settled_reward as u128, StakingData::MAX_KEEPER_TIP_BPS as u128, 10000)? as u64;
+        let tip = ctx.accounts.staking_data.keeper_tip.checked_mul(settled).ok_or(StakingErrors::MathOverflow)?
+            .min(max_tip)
+            .min(ctx.accounts.staking_data.free_reward(ctx.accounts.rewarder_account.amount, now_ts)?);
+        if tip > 0 {
+            let authority_seeds = &[&STAKING_AUTH_PDA_SEED[..], ctx.accounts.staking_data.to_account_info().key.as_ref(), &[ctx.accounts.staking_data.bump_auth]];
+            token::transfer(
+                ctx.accounts
+                    .into_transfer_to_keeper_context()
+                    .with_signer(&[&authority_seeds[..]]),
+                    tip,
+            )?;
+        }
+
+        ctx.accounts.staking_data.last_settle_time = now_ts;
+        ctx.accounts.staking_data.total_settled_positions = ctx.accounts.staking_data.total_settled_positions + settled;
+
+        emit!(PositionsSettled {
+            pool: staking_account,
+            keeper: *ctx.accounts.authority.key,
+            positions: settled,
+            settled_reward: settled_reward,
+            tip: tip,
+            total_settled_positions: ctx.accounts.staking_data.total_settled_positions,
+            time: now_ts,
+        });
+        Ok(())
+    }
+
+    //the rewarder never drops below what is owed to stakers, reserved or still to be emitted
+    pub fn withdraw_unallocated(ctx: Context<WithdrawUnallocated>, amount: u64) -> ProgramResult {
+        if amount == 0{
+            return Err(StakingErrors::AmountMustBigThanZero.into());
+        }
+
+        let now_ts = Clock::get()?.unix_timestamp as u64;
+        ctx.accounts.staking_data.update_reward_per_token(now_ts)?;
+        let liabilities = ctx.accounts.staking_data.allocated_reward(now_ts)?;
+        let withdrawable = ctx.accounts.rewarder_account.amount.saturating_sub(liabilities);
+        if amount > withdrawable {
+            return Err(StakingErrors::InSufficientUnallocatedReward.into());
+        }
+
+        let authority_seeds = &[&STAKING_AUTH_PDA_SEED[..], ctx.accounts.staking_data.to_account_info().key.as_ref(), &[ctx.accounts.staking_data.bump_auth]];
+        token::transfer(
+            ctx.accounts
+                .into_transfer_to_funder_context()
+                .with_signer(&[&authority_seeds[..]]),
+                amount,
+        )?;
+
+        //the set aside reward goes first, the rest is left over the next funding won't find
+        ctx.accounts.staking_data.unallocated_reward = ctx.accounts.staking_data.unallocated_reward.saturating_sub(amount);
+
+        emit!(UnallocatedWithdrawn {
+            pool: *ctx.accounts.staking_data.to_account_info().key,
+            funder: *ctx.accounts.authority.key,
+            amount: amount,
+            unallocated_reward: ctx.accounts.staking_data.unallocated_reward,
+            time: now_ts,
+        });
         Ok(())
     }
 }
@@ -2,6 +2,10 @@ const anchor = require('@project-serum/anchor');
 const { TOKEN_PROGRAM_ID } = require('@solana/spl-token');
 const utils = require('../lib/utils');
 
+// apy cap of the test pools: their timeframes last seconds and pay far above any annual rate,
+// so the cap stays off outside the apy max tests in claiming.test.js
+const NO_APY_CAP = 0;
+
 function formatError(errors, err) {
     if (err.InstructionError !== null && err.InstructionError.length === 2) {
         const errCode = err.InstructionError[1].Custom;
@@ -36,6 +40,7 @@ async function initialize(
     minTimeframeInSecond,
     minStakePeriod,
     signer,
+    rewardMint = mintAddress,
 ) {
     const stakingDataAccount = await utils.getStakingDataAccount(signer.publicKey, mintAddress, program.programId);
     const escrowAccount = await utils.getEscrowAccount(stakingDataAccount, program.programId);
@@ -54,6 +59,7 @@ async function initialize(
                     rewarderAccount: rewarderAccount,
                     authority: signer.publicKey,
                     mintAddress: mintAddress,
+                    rewardMint: rewardMint,
                     tokenProgram: TOKEN_PROGRAM_ID,
                     systemProgram: anchor.web3.SystemProgram.programId,
                     rent: anchor.web3.SYSVAR_RENT_PUBKEY,
@@ -114,6 +120,8 @@ async function staking(
     stakerAccount,
     amount,
     signer,
+    lockTier = 0,
+    receiptAccount = stakerAccount,
 ) {
     const stakingData = await utils.getStakingData(program, stakingDataAccount);
     if(stakingData == null)
@@ -126,20 +134,27 @@ async function staking(
     {
         return "stakingState didn't init";
     }
+    const rewardStreams = await utils.getRewardStreamAccounts(stakingData, stakingDataAccount, program.programId);
+    const stakingAuthority = await utils.getStakingAuthAccount(stakingDataAccount, program.programId);
     let result;
 
     try{
         await program.rpc.staking(
             new anchor.BN(amount),
+            lockTier,
             {
                 accounts: {
                     stakingData: stakingDataAccount,
                     stakeStateAccount: stakeStateAccount,
                     escrowAccount: stakingData.escrowAccount,
                     stakerAccount: stakerAccount,
+                    receiptMint: utils.receiptEnabled(stakingData) ? stakingData.receiptMint : stakingData.mintAddress,
+                    receiptAccount: receiptAccount,
                     authority: signer.publicKey,
+                    stakingAuthority: stakingAuthority,
                     tokenProgram: TOKEN_PROGRAM_ID,
                 },
+                remainingAccounts: rewardStreams,
                 signers: [signer],
             },
         );
@@ -159,6 +174,8 @@ async function unstaking(
     reclaimer,
     amount,
     signer,
+    rewardAccount = reclaimer,
+    receiptAccount = reclaimer,
 ) {
     const stakingData = await utils.getStakingData(program, stakingDataAccount);
     if(stakingData == null)
@@ -173,6 +190,7 @@ async function unstaking(
     }
 
     const stakingAuthority = await utils.getStakingAuthAccount(stakingDataAccount, program.programId);
+    const rewardStreams = await utils.getRewardStreamAccounts(stakingData, stakingDataAccount, program.programId);
     let result;
     try{
         await program.rpc.unstaking(
@@ -183,11 +201,17 @@ async function unstaking(
                     stakeStateAccount: stakeStateAccount,
                     escrowAccount: stakingData.escrowAccount,
                     reclaimer: reclaimer,
+                    rewardAccount: rewardAccount,
                     rewarderAccount: stakingData.rewarderAccount,
+                    mintAddress: stakingData.mintAddress,
+                    treasuryAccount: stakingData.penaltyMode === 1 ? stakingData.penaltyTreasury : stakingData.rewarderAccount,
+                    receiptMint: utils.receiptEnabled(stakingData) ? stakingData.receiptMint : stakingData.mintAddress,
+                    receiptAccount: receiptAccount,
                     authority: signer.publicKey,
                     stakingAuthority: stakingAuthority,
                     tokenProgram: TOKEN_PROGRAM_ID,
                 },
+                remainingAccounts: rewardStreams,
                 signers: [signer],
             },
         );   
@@ -206,6 +230,7 @@ async function claimReward(
     claimer,
     amount,
     signer,
+    streamClaims = [],
 ) {
     const stakingData = await utils.getStakingData(program, stakingDataAccount);
     if(stakingData == null)
@@ -220,6 +245,13 @@ async function claimReward(
     }
 
     const stakingAuthority = await utils.getStakingAuthAccount(stakingDataAccount, program.programId)
+    // every reward stream is settled, then each stream claim is [rewardStreamAccount, claimer of the stream mint]
+    const remainingAccounts = await utils.getRewardStreamAccounts(stakingData, stakingDataAccount, program.programId);
+    for (const [rewardStreamAccount, streamClaimer] of streamClaims) {
+        const rewardStream = await utils.getRewardStream(program, rewardStreamAccount);
+        remainingAccounts.push({ pubkey: rewardStream.rewarderAccount, isWritable: true, isSigner: false });
+        remainingAccounts.push({ pubkey: streamClaimer, isWritable: true, isSigner: false });
+    }
     let result;
     try{
         await program.rpc.claimReward(
@@ -230,10 +262,13 @@ async function claimReward(
                     stakeStateAccount: stakeStateAccount,
                     rewarderAccount: stakingData.rewarderAccount,
                     claimer: claimer,
+                    vestingAccount: stakingData.vestingDuration.toNumber() > 0 ? await utils.getVestingAccount(stakeStateAccount, program.programId) : claimer,
+                    ownerReceiptAccount: utils.receiptEnabled(stakingData) ? await utils.getOwnerReceiptAccount(stakingData, stakeState) : claimer,
                     authority: signer.publicKey,
                     stakingAuthority: stakingAuthority,
                     tokenProgram: TOKEN_PROGRAM_ID,
                 },
+                remainingAccounts: remainingAccounts,
                 signers: [signer],
             },
         );    
@@ -286,6 +321,198 @@ async function funding(
     return result;
 }
 
+async function withdrawUnbonded(
+    program,
+    stakingDataAccount,
+    reclaimer,
+    signer,
+) {
+    const stakingData = await utils.getStakingData(program, stakingDataAccount);
+    if(stakingData == null)
+    {
+        return "stakingData didn't init";
+    }
+    const stakeStateAccount = await utils.getStakingStateAccount(stakingDataAccount, signer.publicKey, program.programId);
+    const stakingAuthority = await utils.getStakingAuthAccount(stakingDataAccount, program.programId);
+    let result;
+    try{
+        await program.rpc.withdrawUnbonded(
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    stakeStateAccount: stakeStateAccount,
+                    escrowAccount: stakingData.escrowAccount,
+                    reclaimer: reclaimer,
+                    authority: signer.publicKey,
+                    stakingAuthority: stakingAuthority,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                },
+                signers: [signer],
+            },
+        );
+        result = true;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function cancelUnbonding(
+    program,
+    stakingDataAccount,
+    signer,
+) {
+    const stakingData = await utils.getStakingData(program, stakingDataAccount);
+    const stakeStateAccount = await utils.getStakingStateAccount(stakingDataAccount, signer.publicKey, program.programId);
+    const rewardStreams = await utils.getRewardStreamAccounts(stakingData, stakingDataAccount, program.programId);
+    let result;
+    try{
+        await program.rpc.cancelUnbonding(
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    stakeStateAccount: stakeStateAccount,
+                    authority: signer.publicKey,
+                },
+                remainingAccounts: rewardStreams,
+                signers: [signer],
+            },
+        );
+        result = true;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function setUnbondingPeriod(
+    program,
+    stakingDataAccount,
+    unbondingPeriod,
+    signer,
+) {
+    let result;
+    try{
+        await program.rpc.setUnbondingPeriod(
+            new anchor.BN(unbondingPeriod),
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    authority: signer.publicKey,
+                },
+                signers: [signer],
+            },
+        );
+        result = unbondingPeriod;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function setPenalty(
+    program,
+    stakingDataAccount,
+    penaltyBps,
+    penaltyPeriod,
+    penaltyMode,
+    penaltyTreasury,
+    signer,
+) {
+    let result;
+    try{
+        await program.rpc.setPenalty(
+            penaltyBps,
+            new anchor.BN(penaltyPeriod),
+            penaltyMode,
+            penaltyTreasury,
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    treasuryAccount: penaltyTreasury,
+                    authority: signer.publicKey,
+                },
+                signers: [signer],
+            },
+        );
+        result = penaltyBps;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function migrateStaker(
+    program,
+    stakingDataAccount,
+    stakeStateAccount,
+    signer,
+    otherStakeStateAccounts = [],
+) {
+    let result;
+    try{
+        await program.rpc.migrateStaker(
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    stakeStateAccount: stakeStateAccount,
+                    authority: signer.publicKey,
+                },
+                remainingAccounts: otherStakeStateAccounts.map(
+                    (account) => ({ pubkey: account, isWritable: true, isSigner: false })),
+                signers: [signer],
+            },
+        );
+        result = stakeStateAccount;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function resolveLegacyStaker(
+    program,
+    stakingDataAccount,
+    stakeStateAccount,
+    entryIndex,
+    stakedAmount,
+    stakedTime,
+    signer,
+) {
+    let result;
+    try{
+        await program.rpc.resolveLegacyStaker(
+            entryIndex,
+            new anchor.BN(stakedAmount),
+            new anchor.BN(stakedTime),
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    stakeStateAccount: stakeStateAccount,
+                    authority: signer.publicKey,
+                },
+                signers: [signer],
+            },
+        );
+        result = stakeStateAccount;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
 async function setMaxApy(
     program,
     stakingDataAccount,
@@ -320,14 +547,1125 @@ async function setMaxApy(
     return result;
 }
 
+async function addRewardStream(
+    program,
+    stakingDataAccount,
+    funderAuthority,
+    rewardMint,
+    apyMax,
+    minTimeframeInSecond,
+    signer,
+) {
+    const stakingData = await utils.getStakingData(program, stakingDataAccount);
+    if(stakingData == null)
+    {
+        return "stakingData didn't init";
+    }
+    const rewardStreamAccount = await utils.getRewardStreamAccount(stakingDataAccount, stakingData.rewardStreams, program.programId);
+    const rewarderAccount = await utils.getRewarderAccount(rewardStreamAccount, program.programId);
+    let result;
+    try{
+        await program.rpc.addRewardStream(
+            apyMax,
+            new anchor.BN(minTimeframeInSecond),
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    rewardStream: rewardStreamAccount,
+                    rewarderAccount: rewarderAccount,
+                    funderAuthority: funderAuthority,
+                    rewardMint: rewardMint,
+                    authority: signer.publicKey,
+                    systemProgram: anchor.web3.SystemProgram.programId,
+                    rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                },
+                signers: [signer],
+            },
+        );
+        result = rewardStreamAccount;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function fundRewardStream(
+    program,
+    stakingDataAccount,
+    rewardStreamAccount,
+    funderAccount,
+    amount,
+    timeframeInSecond,
+    signer,
+) {
+    const rewardStream = await utils.getRewardStream(program, rewardStreamAccount);
+    if(rewardStream == null)
+    {
+        return "rewardStream didn't init";
+    }
+    let result;
+    try{
+        await program.rpc.fundRewardStream(
+            new anchor.BN(amount),
+            new anchor.BN(timeframeInSecond),
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    rewardStream: rewardStreamAccount,
+                    rewarderAccount: rewardStream.rewarderAccount,
+                    funderAccount: funderAccount,
+                    authority: signer.publicKey,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                },
+                signers: [signer],
+            },
+        );
+        result = amount;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function claimStreamReward(
+    program,
+    stakingDataAccount,
+    rewardStreamAccount,
+    claimer,
+    amount,
+    signer,
+) {
+    const rewardStream = await utils.getRewardStream(program, rewardStreamAccount);
+    if(rewardStream == null)
+    {
+        return "rewardStream didn't init";
+    }
+    const stakeStateAccount = await utils.getStakingStateAccount(stakingDataAccount, signer.publicKey, program.programId);
+    const stakingAuthority = await utils.getStakingAuthAccount(stakingDataAccount, program.programId);
+    let result;
+    try{
+        await program.rpc.claimStreamReward(
+            new anchor.BN(amount),
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    stakeStateAccount: stakeStateAccount,
+                    rewardStream: rewardStreamAccount,
+                    rewarderAccount: rewardStream.rewarderAccount,
+                    claimer: claimer,
+                    authority: signer.publicKey,
+                    stakingAuthority: stakingAuthority,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                },
+                signers: [signer],
+            },
+        );
+        result = amount;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function setLockTiers(
+    program,
+    stakingDataAccount,
+    lockTiers,
+    signer,
+) {
+    let result;
+    try{
+        await program.rpc.setLockTiers(
+            lockTiers.map((tier) => ({
+                duration: new anchor.BN(tier.duration),
+                multiplierBps: tier.multiplierBps,
+            })),
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    authority: signer.publicKey,
+                },
+                signers: [signer],
+            },
+        );
+        result = true;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function addFixedTermProduct(
+    program,
+    stakingDataAccount,
+    term,
+    aprBps,
+    capacity,
+    signer,
+) {
+    const stakingData = await utils.getStakingData(program, stakingDataAccount);
+    if(stakingData == null)
+    {
+        return "stakingData didn't init";
+    }
+    const productAccount = await utils.getFixedTermProductAccount(stakingDataAccount, stakingData.fixedTermProducts, program.programId);
+    let result;
+    try{
+        await program.rpc.addFixedTermProduct(
+            new anchor.BN(term),
+            aprBps,
+            new anchor.BN(capacity),
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    product: productAccount,
+                    authority: signer.publicKey,
+                    systemProgram: anchor.web3.SystemProgram.programId,
+                    rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+                },
+                signers: [signer],
+            },
+        );
+        result = productAccount;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function setFixedTermProduct(
+    program,
+    stakingDataAccount,
+    productAccount,
+    enabled,
+    signer,
+) {
+    let result;
+    try{
+        await program.rpc.setFixedTermProduct(
+            enabled,
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    product: productAccount,
+                    authority: signer.publicKey,
+                },
+                signers: [signer],
+            },
+        );
+        result = true;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function depositFixedTerm(
+    program,
+    stakingDataAccount,
+    productAccount,
+    stakerAccount,
+    amount,
+    signer,
+    nonce = 0,
+) {
+    const stakingData = await utils.getStakingData(program, stakingDataAccount);
+    if(stakingData == null)
+    {
+        return "stakingData didn't init";
+    }
+    const depositAccount = await utils.getFixedTermDepositAccount(productAccount, signer.publicKey, nonce, program.programId);
+    let result;
+    try{
+        await program.rpc.depositFixedTerm(
+            new anchor.BN(amount),
+            new anchor.BN(nonce),
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    product: productAccount,
+                    deposit: depositAccount,
+                    escrowAccount: stakingData.escrowAccount,
+                    rewarderAccount: stakingData.rewarderAccount,
+                    stakerAccount: stakerAccount,
+                    authority: signer.publicKey,
+                    systemProgram: anchor.web3.SystemProgram.programId,
+                    rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                },
+                signers: [signer],
+            },
+        );
+        result = depositAccount;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function redeemFixedTerm(
+    program,
+    stakingDataAccount,
+    productAccount,
+    reclaimer,
+    signer,
+    rewardAccount = reclaimer,
+    nonce = 0,
+) {
+    const stakingData = await utils.getStakingData(program, stakingDataAccount);
+    if(stakingData == null)
+    {
+        return "stakingData didn't init";
+    }
+    const depositAccount = await utils.getFixedTermDepositAccount(productAccount, signer.publicKey, nonce, program.programId);
+    const stakingAuthority = await utils.getStakingAuthAccount(stakingDataAccount, program.programId);
+    let result;
+    try{
+        await program.rpc.redeemFixedTerm(
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    product: productAccount,
+                    deposit: depositAccount,
+                    escrowAccount: stakingData.escrowAccount,
+                    rewarderAccount: stakingData.rewarderAccount,
+                    reclaimer: reclaimer,
+                    rewardAccount: rewardAccount,
+                    authority: signer.publicKey,
+                    stakingAuthority: stakingAuthority,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                },
+                signers: [signer],
+            },
+        );
+        result = true;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function initVault(
+    program,
+    stakingDataAccount,
+    signer,
+) {
+    const stakingData = await utils.getStakingData(program, stakingDataAccount);
+    if(stakingData == null)
+    {
+        return "stakingData didn't init";
+    }
+    const shareMint = await utils.getShareMintAccount(stakingDataAccount, program.programId);
+    const stakingAuthority = await utils.getStakingAuthAccount(stakingDataAccount, program.programId);
+    let result;
+    try{
+        await program.rpc.initVault(
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    shareMint: shareMint,
+                    mintAddress: stakingData.mintAddress,
+                    authority: signer.publicKey,
+                    stakingAuthority: stakingAuthority,
+                    systemProgram: anchor.web3.SystemProgram.programId,
+                    rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                },
+                signers: [signer],
+            },
+        );
+        result = shareMint;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function vaultAccounts(program, stakingDataAccount, stakerAccount, shareAccount, signer) {
+    const stakingData = await utils.getStakingData(program, stakingDataAccount);
+    return {
+        stakingData: stakingDataAccount,
+        escrowAccount: stakingData.escrowAccount,
+        rewarderAccount: stakingData.rewarderAccount,
+        shareMint: stakingData.shareMint,
+        stakerAccount: stakerAccount,
+        shareAccount: shareAccount,
+        authority: signer.publicKey,
+        stakingAuthority: await utils.getStakingAuthAccount(stakingDataAccount, program.programId),
+        tokenProgram: TOKEN_PROGRAM_ID,
+    };
+}
+
+async function deposit(
+    program,
+    stakingDataAccount,
+    stakerAccount,
+    shareAccount,
+    amount,
+    signer,
+) {
+    let result;
+    try{
+        await program.rpc.deposit(
+            new anchor.BN(amount),
+            {
+                accounts: await vaultAccounts(program, stakingDataAccount, stakerAccount, shareAccount, signer),
+                signers: [signer],
+            },
+        );
+        result = amount;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function redeem(
+    program,
+    stakingDataAccount,
+    stakerAccount,
+    shareAccount,
+    shares,
+    signer,
+) {
+    let result;
+    try{
+        await program.rpc.redeem(
+            new anchor.BN(shares),
+            {
+                accounts: await vaultAccounts(program, stakingDataAccount, stakerAccount, shareAccount, signer),
+                signers: [signer],
+            },
+        );
+        result = shares;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function compoundVault(
+    program,
+    stakingDataAccount,
+) {
+    const stakingData = await utils.getStakingData(program, stakingDataAccount);
+    if(stakingData == null)
+    {
+        return "stakingData didn't init";
+    }
+    let result;
+    try{
+        await program.rpc.compoundVault(
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    escrowAccount: stakingData.escrowAccount,
+                    rewarderAccount: stakingData.rewarderAccount,
+                    shareMint: stakingData.shareMint,
+                    stakingAuthority: await utils.getStakingAuthAccount(stakingDataAccount, program.programId),
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                },
+            },
+        );
+        result = true;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function initReceipt(
+    program,
+    stakingDataAccount,
+    signer,
+) {
+    const stakingData = await utils.getStakingData(program, stakingDataAccount);
+    if(stakingData == null)
+    {
+        return "stakingData didn't init";
+    }
+    const receiptMint = await utils.getReceiptMintAccount(stakingDataAccount, program.programId);
+    const stakingAuthority = await utils.getStakingAuthAccount(stakingDataAccount, program.programId);
+    let result;
+    try{
+        await program.rpc.initReceipt(
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    receiptMint: receiptMint,
+                    mintAddress: stakingData.mintAddress,
+                    authority: signer.publicKey,
+                    stakingAuthority: stakingAuthority,
+                    systemProgram: anchor.web3.SystemProgram.programId,
+                    rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                },
+                signers: [signer],
+            },
+        );
+        result = receiptMint;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+async function transferReceipt(
+    program,
+    stakingDataAccount,
+    receiptAccount,
+    toOwner,
+    toReceiptAccount,
+    amount,
+    signer,
+) {
+    const stakingData = await utils.getStakingData(program, stakingDataAccount);
+    if(stakingData == null)
+    {
+        return "stakingData didn't init";
+    }
+    const stakeStateAccount = await utils.getStakingStateAccount(stakingDataAccount, signer.publicKey, program.programId);
+    const toStakeStateAccount = await utils.getStakingStateAccount(stakingDataAccount, toOwner, program.programId);
+    const rewardStreams = await utils.getRewardStreamAccounts(stakingData, stakingDataAccount, program.programId);
+    let result;
+    try{
+        await program.rpc.transferReceipt(
+            new anchor.BN(amount),
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    stakeStateAccount: stakeStateAccount,
+                    toStakeStateAccount: toStakeStateAccount,
+                    receiptAccount: receiptAccount,
+                    toReceiptAccount: toReceiptAccount,
+                    authority: signer.publicKey,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                },
+                remainingAccounts: rewardStreams,
+                signers: [signer],
+            },
+        );
+        result = amount;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+async function redeemReceipt(
+    program,
+    stakingDataAccount,
+    positionOwner,
+    ownerReceiptAccount,
+    receiptAccount,
+    reclaimer,
+    amount,
+    signer,
+) {
+    const stakingData = await utils.getStakingData(program, stakingDataAccount);
+    if(stakingData == null)
+    {
+        return "stakingData didn't init";
+    }
+    const stakeStateAccount = await utils.getStakingStateAccount(stakingDataAccount, positionOwner, program.programId);
+    const toStakeStateAccount = await utils.getStakingStateAccount(stakingDataAccount, signer.publicKey, program.programId);
+    const rewardStreams = await utils.getRewardStreamAccounts(stakingData, stakingDataAccount, program.programId);
+    let result;
+    try{
+        await program.rpc.redeemReceipt(
+            new anchor.BN(amount),
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    stakeStateAccount: stakeStateAccount,
+                    ownerReceiptAccount: ownerReceiptAccount,
+                    toStakeStateAccount: toStakeStateAccount,
+                    receiptMint: stakingData.receiptMint,
+                    receiptAccount: receiptAccount,
+                    escrowAccount: stakingData.escrowAccount,
+                    reclaimer: reclaimer,
+                    authority: signer.publicKey,
+                    stakingAuthority: await utils.getStakingAuthAccount(stakingDataAccount, program.programId),
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                },
+                remainingAccounts: rewardStreams,
+                signers: [signer],
+            },
+        );
+        result = amount;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function compound(
+    program,
+    stakingDataAccount,
+    owner,
+    signer,
+) {
+    const stakingData = await utils.getStakingData(program, stakingDataAccount);
+    if(stakingData == null)
+    {
+        return "stakingData didn't init";
+    }
+    const stakeStateAccount = await utils.getStakingStateAccount(stakingDataAccount, owner, program.programId);
+    const stakeState = await utils.getStakingState(program, stakeStateAccount);
+    if(stakeState == null)
+    {
+        return "stakingState didn't init";
+    }
+    const rewardStreams = await utils.getRewardStreamAccounts(stakingData, stakingDataAccount, program.programId);
+    let result;
+    try{
+        await program.rpc.compound(
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    stakeStateAccount: stakeStateAccount,
+                    escrowAccount: stakingData.escrowAccount,
+                    rewarderAccount: stakingData.rewarderAccount,
+                    ownerReceiptAccount: utils.receiptEnabled(stakingData) ? await utils.getOwnerReceiptAccount(stakingData, stakeState) : stakingData.escrowAccount,
+                    authority: signer.publicKey,
+                    stakingAuthority: await utils.getStakingAuthAccount(stakingDataAccount, program.programId),
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                },
+                remainingAccounts: rewardStreams,
+                signers: [signer],
+            },
+        );
+        result = true;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+async function setAutoCompound(
+    program,
+    stakingDataAccount,
+    autoCompound,
+    signer,
+) {
+    const stakeStateAccount = await utils.getStakingStateAccount(stakingDataAccount, signer.publicKey, program.programId);
+    let result;
+    try{
+        await program.rpc.setAutoCompound(
+            autoCompound,
+            {
+                accounts: {
+                    stakeStateAccount: stakeStateAccount,
+                    authority: signer.publicKey,
+                },
+                signers: [signer],
+            },
+        );
+        result = autoCompound;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function changeSetting(
+    program,
+    stakingDataAccount,
+    apyMax,
+    minStakePeriod,
+    vestingDuration,
+    vestingCliff,
+    signer,
+) {
+    const stakingData = await utils.getStakingData(program, stakingDataAccount);
+    if(stakingData == null)
+    {
+        return "stakingData didn't init";
+    }
+    let result;
+    try{
+        await program.rpc.changeSetting(
+            apyMax,
+            new anchor.BN(minStakePeriod),
+            new anchor.BN(vestingDuration),
+            new anchor.BN(vestingCliff),
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    authority: signer.publicKey,
+                },
+                signers: [signer],
+            },
+        );
+        result = true;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+async function initializeVesting(
+    program,
+    stakingDataAccount,
+    signer,
+) {
+    const stakingData = await utils.getStakingData(program, stakingDataAccount);
+    if(stakingData == null)
+    {
+        return "stakingData didn't init";
+    }
+    const stakeStateAccount = await utils.getStakingStateAccount(stakingDataAccount, signer.publicKey, program.programId);
+    const vestingAccount = await utils.getVestingAccount(stakeStateAccount, program.programId);
+    let result;
+    try{
+        await program.rpc.initializeVesting(
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    stakeStateAccount: stakeStateAccount,
+                    vestingAccount: vestingAccount,
+                    rewardMint: utils.getRewardMint(stakingData),
+                    authority: signer.publicKey,
+                    stakingAuthority: await utils.getStakingAuthAccount(stakingDataAccount, program.programId),
+                    systemProgram: anchor.web3.SystemProgram.programId,
+                    rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                },
+                signers: [signer],
+            },
+        );
+        result = vestingAccount;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+async function withdrawVested(
+    program,
+    stakingDataAccount,
+    claimer,
+    signer,
+) {
+    const stakeStateAccount = await utils.getStakingStateAccount(stakingDataAccount, signer.publicKey, program.programId);
+    let result;
+    try{
+        await program.rpc.withdrawVested(
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    stakeStateAccount: stakeStateAccount,
+                    vestingAccount: await utils.getVestingAccount(stakeStateAccount, program.programId),
+                    claimer: claimer,
+                    authority: signer.publicKey,
+                    stakingAuthority: await utils.getStakingAuthAccount(stakingDataAccount, program.programId),
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                },
+                signers: [signer],
+            },
+        );
+        result = true;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function setRolloverMode(
+    program,
+    stakingDataAccount,
+    rolloverMode,
+    signer,
+) {
+    let result;
+    try{
+        await program.rpc.setRolloverMode(
+            rolloverMode,
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    authority: signer.publicKey,
+                },
+                signers: [signer],
+            },
+        );
+        result = rolloverMode;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function withdrawUnallocated(
+    program,
+    stakingDataAccount,
+    funderAccount,
+    amount,
+    signer,
+) {
+    const stakingData = await utils.getStakingData(program, stakingDataAccount);
+    if(stakingData == null)
+    {
+        return "stakingData didn't init";
+    }
+    let result;
+    try{
+        await program.rpc.withdrawUnallocated(
+            new anchor.BN(amount),
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    rewarderAccount: stakingData.rewarderAccount,
+                    funderAccount: funderAccount,
+                    authority: signer.publicKey,
+                    stakingAuthority: await utils.getStakingAuthAccount(stakingDataAccount, program.programId),
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                },
+                signers: [signer],
+            },
+        );
+        result = amount;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function topUp(
+    program,
+    stakingDataAccount,
+    funderAccount,
+    amount,
+    extendInSecond,
+    signer,
+) {
+    const stakingData = await utils.getStakingData(program, stakingDataAccount);
+    if(stakingData == null)
+    {
+        return "stakingData didn't init";
+    }
+
+    let result;
+    try{
+        await program.rpc.topUp(
+            new anchor.BN(amount),
+            new anchor.BN(extendInSecond),
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    rewarderAccount: stakingData.rewarderAccount,
+                    funderAccount: funderAccount,
+                    authority: signer.publicKey,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                },
+                signers: [signer],
+            },
+        );
+        result = amount;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function queueEmissionEpochs(
+    program,
+    stakingDataAccount,
+    funderAccount,
+    epochs,
+    signer,
+) {
+    const stakingData = await utils.getStakingData(program, stakingDataAccount);
+    if(stakingData == null)
+    {
+        return "stakingData didn't init";
+    }
+
+    let result;
+    try{
+        await program.rpc.queueEmissionEpochs(
+            epochs.map((epoch) => ({
+                start: new anchor.BN(epoch.start),
+                end: new anchor.BN(epoch.end),
+                amount: new anchor.BN(epoch.amount),
+            })),
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    rewarderAccount: stakingData.rewarderAccount,
+                    funderAccount: funderAccount,
+                    authority: signer.publicKey,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                },
+                signers: [signer],
+            },
+        );
+        result = epochs.length;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function setDonationsEnabled(
+    program,
+    stakingDataAccount,
+    donationsEnabled,
+    signer,
+) {
+    let result;
+    try{
+        await program.rpc.setDonationsEnabled(
+            donationsEnabled,
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    authority: signer.publicKey,
+                },
+                signers: [signer],
+            },
+        );
+        result = donationsEnabled;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function initializeDonor(
+    program,
+    stakingDataAccount,
+    signer,
+) {
+    const donorRecordAccount = await utils.getDonorRecordAccount(stakingDataAccount, signer.publicKey, program.programId);
+
+    let result;
+    try{
+        await program.rpc.initializeDonor(
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    donorRecord: donorRecordAccount,
+                    authority: signer.publicKey,
+                    systemProgram: anchor.web3.SystemProgram.programId,
+                    rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+                },
+                signers: [signer],
+            },
+        );
+        result = donorRecordAccount;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function donate(
+    program,
+    stakingDataAccount,
+    donorAccount,
+    amount,
+    signer,
+) {
+    const stakingData = await utils.getStakingData(program, stakingDataAccount);
+    if(stakingData == null)
+    {
+        return "stakingData didn't init";
+    }
+    const donorRecordAccount = await utils.getDonorRecordAccount(stakingDataAccount, signer.publicKey, program.programId);
+
+    let result;
+    try{
+        await program.rpc.donate(
+            new anchor.BN(amount),
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    rewarderAccount: stakingData.rewarderAccount,
+                    donorAccount: donorAccount,
+                    donorRecord: donorRecordAccount,
+                    authority: signer.publicKey,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                },
+                signers: [signer],
+            },
+        );
+        result = amount;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+async function setKeeperTip(
+    program,
+    stakingDataAccount,
+    keeperTip,
+    signer,
+) {
+    let result;
+    try{
+        await program.rpc.setKeeperTip(
+            new anchor.BN(keeperTip),
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    authority: signer.publicKey,
+                },
+                signers: [signer],
+            },
+        );
+        result = keeperTip;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
+// settles the positions of owners, a page of at most 16
+async function settle(
+    program,
+    stakingDataAccount,
+    keeperAccount,
+    owners,
+    signer,
+) {
+    const stakingData = await utils.getStakingData(program, stakingDataAccount);
+    if(stakingData == null)
+    {
+        return "stakingData didn't init";
+    }
+    const stakingAuthority = await utils.getStakingAuthAccount(stakingDataAccount, program.programId);
+    const remainingAccounts = await utils.getRewardStreamAccounts(stakingData, stakingDataAccount, program.programId);
+    for (const owner of owners) {
+        remainingAccounts.push({
+            pubkey: await utils.getStakingStateAccount(stakingDataAccount, owner, program.programId),
+            isWritable: true,
+            isSigner: false,
+        });
+    }
+
+    let result;
+    try{
+        await program.rpc.settle(
+            {
+                accounts: {
+                    stakingData: stakingDataAccount,
+                    rewarderAccount: stakingData.rewarderAccount,
+                    keeperAccount: keeperAccount,
+                    authority: signer.publicKey,
+                    stakingAuthority: stakingAuthority,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                },
+                remainingAccounts: remainingAccounts,
+                signers: [signer],
+            },
+        );
+        result = owners.length;
+    }catch(e){
+        if (e.msg === undefined) {
+            result = parseErrorNumber(program._idl.errors, e.logs);
+        } else result = e.msg;
+    }
+    return result;
+}
+
 
 module.exports = {
+    NO_APY_CAP,
     initialize,   
     initializeStakeState,
     staking,
     unstaking,
     claimReward,
     funding,
-    setMaxApy
+    withdrawUnbonded,
+    cancelUnbonding,
+    setUnbondingPeriod,
+    setPenalty,
+    migrateStaker,
+    resolveLegacyStaker,
+    setMaxApy,
+    addRewardStream,
+    fundRewardStream,
+    claimStreamReward,
+    setLockTiers,
+    addFixedTermProduct,
+    setFixedTermProduct,
+    depositFixedTerm,
+    redeemFixedTerm,
+    initVault,
+    deposit,
+    redeem,
+    compoundVault,
+    initReceipt,
+    transferReceipt,
+    redeemReceipt,
+    compound,
+    setAutoCompound,
+    changeSetting,
+    initializeVesting,
+    withdrawVested,
+    setRolloverMode,
+    withdrawUnallocated,
+    topUp,
+    queueEmissionEpochs,
+    setDonationsEnabled,
+    initializeDonor,
+    donate,
+    setKeeperTip,
+    settle,
 }
 
@@ -41,7 +41,7 @@ describe('Claiming tests', () => {
     let funderAccount;
     const minTimeframeInSecond = 30;
     const minStakePeriod = 30;
-    const apyMax = 800;
+    const apyMax = lib.NO_APY_CAP;
 
     beforeEach(async () => {
         // create wallet A
@@ -122,14 +122,16 @@ describe('Claiming tests', () => {
         const timeframeInSecond = 30;
         await lib.funding(program, stakingDataAccount, funderAccount, fundAmount, timeframeInSecond, funderAuthority);
         stakingData = await utils.getStakingData(program, stakingDataAccount);
+        const stakerStateAccount = await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId);
+        const stakerState = await utils.getStakingState(program, stakerStateAccount);
 
-        //sleep 32 second call funding again
+        //sleep 32 second, claiming settles the finished timeframe
         console.log("waiting 32 seconds");
         await sleep(32000);
 
-        await lib.funding(program, stakingDataAccount, funderAccount, fundAmount, timeframeInSecond, funderAuthority);
-        stakingData = await utils.getStakingData(program, stakingDataAccount);
-        const gainedReward = stakingData.stakers[0].gainedReward.toNumber();
+        const frameEnd = stakingData.timeframeStarted.toNumber() + stakingData.timeframeInSecond.toNumber();
+        const gainedReward = utils.pendingReward(stakingData, stakerState, amount, stakerState.stakedTime.toNumber(), frameEnd);
+        assert(gainedReward > 0);
 
         const res = await lib.claimReward(program, stakingDataAccount, stakerAccount, gainedReward, stakerInitializer);
   
@@ -138,7 +140,7 @@ describe('Claiming tests', () => {
         stakingData = await utils.getStakingData(program, stakingDataAccount);
 
         assert(stakingData.totalRewardPaid.toNumber() === gainedReward);
-        assert(stakingData.stakers[0].gainedReward.toNumber() === 0);
+        assert((await utils.getStakingState(program, stakerStateAccount)).gainedReward.toNumber() === 0);
 
     });
 
@@ -163,14 +165,16 @@ describe('Claiming tests', () => {
         const timeframeInSecond = 30;
         await lib.funding(program, stakingDataAccount, funderAccount, fundAmount, timeframeInSecond, funderAuthority);
         stakingData = await utils.getStakingData(program, stakingDataAccount);
+        const stakerStateAccount = await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId);
+        const stakerState = await utils.getStakingState(program, stakerStateAccount);
 
-        //sleep 32 second call funding again
+        //sleep 32 second, claiming settles the finished timeframe
         console.log("waiting 32 seconds");
         await sleep(32000);
 
-        await lib.funding(program, stakingDataAccount, funderAccount, fundAmount, timeframeInSecond, funderAuthority);
-        stakingData = await utils.getStakingData(program, stakingDataAccount);
-        const gainedReward = stakingData.stakers[0].gainedReward.toNumber();
+        const frameEnd = stakingData.timeframeStarted.toNumber() + stakingData.timeframeInSecond.toNumber();
+        const gainedReward = utils.pendingReward(stakingData, stakerState, amount, stakerState.stakedTime.toNumber(), frameEnd);
+        assert(gainedReward > 0);
 
         console.log("claiming gained reward=", 1);
         const res = await lib.claimReward(program, stakingDataAccount, stakerAccount, 1, stakerInitializer);
@@ -180,10 +184,336 @@ describe('Claiming tests', () => {
         stakingData = await utils.getStakingData(program, stakingDataAccount);
 
         assert(stakingData.totalRewardPaid.toNumber() === 1);
-        assert(stakingData.stakers[0].gainedReward.toNumber() === gainedReward - 1);
+        assert((await utils.getStakingState(program, stakerStateAccount)).gainedReward.toNumber() === gainedReward - 1);
+
+
+    });
+
+    it('Claiming when total staked changes in the timeframe', async () => {
+        //second staker
+        const secondStaker = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, secondStaker.publicKey, 1000_000_000);
+        const secondStakerAccount = await mint.createAccount(secondStaker.publicKey);
+        await mint.mintTo(secondStakerAccount, mintAuthority.publicKey, [mintAuthority], 100_000_000_000);
+        const secondStateAccount = await lib.initializeStakeState(program, stakingDataAccount, secondStaker);
+
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+
+        const fundAmount = 1000;
+        const timeframeInSecond = 40;
+        await lib.funding(program, stakingDataAccount, funderAccount, fundAmount, timeframeInSecond, funderAuthority);
+
+        //second staker joins in the middle of the timeframe
+        console.log("waiting 10 seconds");
+        await sleep(10000);
+        await lib.staking(program, stakingDataAccount, secondStakerAccount, amount, secondStaker);
+
+        let stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.totalStaked.toNumber() === amount * 2);
+        const firstStateAccount = await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId);
+        const firstState = await utils.getStakingState(program, firstStateAccount);
+        const secondState = await utils.getStakingState(program, secondStateAccount);
+
+        console.log("waiting 42 seconds");
+        await sleep(42000);
+
+        const frameEnd = stakingData.timeframeStarted.toNumber() + stakingData.timeframeInSecond.toNumber();
+        const firstReward = utils.pendingReward(stakingData, firstState, amount, firstState.stakedTime.toNumber(), frameEnd);
+        const secondReward = utils.pendingReward(stakingData, secondState, amount, secondState.stakedTime.toNumber(), frameEnd);
+        assert(firstReward > secondReward);
+        assert(firstReward + secondReward <= fundAmount);
+
+        let res = await lib.claimReward(program, stakingDataAccount, stakerAccount, firstReward, stakerInitializer);
+        assert(res === firstReward);
+        res = await lib.claimReward(program, stakingDataAccount, secondStakerAccount, secondReward, secondStaker);
+        assert(res === secondReward);
+
+        stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.totalRewardPaid.toNumber() === firstReward + secondReward);
+        assert((await utils.getStakingState(program, firstStateAccount)).gainedReward.toNumber() === 0);
+        assert((await utils.getStakingState(program, secondStateAccount)).gainedReward.toNumber() === 0);
+    });
+
+    it('Claiming in a separate reward mint', async () => {
+        //reward token with other decimals than the staking token
+        const rewardMint = await splToken.Token.createMint(
+            provider.connection,
+            stakingInitializer,
+            mintAuthority.publicKey,
+            null,
+            6,
+            splToken.TOKEN_PROGRAM_ID,
+        );
+        const rewardFunderAccount = await rewardMint.createAccount(funderAuthority.publicKey);
+        await rewardMint.mintTo(rewardFunderAccount, mintAuthority.publicKey, [mintAuthority], 100_000_000_000);
+        const stakerRewardAccount = await rewardMint.createAccount(stakerInitializer.publicKey);
+
+        const poolInitializer = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, poolInitializer.publicKey, 1000_000_000);
+        const rewardPool = await lib.initialize(program, funderAuthority.publicKey, mint.publicKey, apyMax, minTimeframeInSecond, minStakePeriod, poolInitializer, rewardMint.publicKey);
+        let stakingData = await utils.getStakingData(program, rewardPool);
+        assert(stakingData.rewardMint.toBase58() === rewardMint.publicKey.toBase58());
+        assert(stakingData.mintDecimals === 0);
+        assert(stakingData.rewardDecimals === 6);
+        const stakerStateAccount = await lib.initializeStakeState(program, rewardPool, stakerInitializer);
+
+        //the rewarder only takes the reward token
+        let res = await lib.funding(program, rewardPool, funderAccount, 1000, 30, funderAuthority);
+        assert(res !== 1000);
+
+        const amount = 1000;
+        await lib.staking(program, rewardPool, stakerAccount, amount, stakerInitializer);
+        const fundAmount = 1000_000;
+        const timeframeInSecond = 30;
+        res = await lib.funding(program, rewardPool, rewardFunderAccount, fundAmount, timeframeInSecond, funderAuthority);
+        assert(res === fundAmount);
+        stakingData = await utils.getStakingData(program, rewardPool);
+        const stakerState = await utils.getStakingState(program, stakerStateAccount);
+
+        console.log("waiting 32 seconds");
+        await sleep(32000);
+
+        const frameEnd = stakingData.timeframeStarted.toNumber() + stakingData.timeframeInSecond.toNumber();
+        const gainedReward = utils.pendingReward(stakingData, stakerState, amount, stakerState.stakedTime.toNumber(), frameEnd);
+        assert(gainedReward > 0);
+
+        //the claimer must hold the reward token
+        res = await lib.claimReward(program, rewardPool, stakerAccount, gainedReward, stakerInitializer);
+        assert(res !== gainedReward);
+        res = await lib.claimReward(program, rewardPool, stakerRewardAccount, gainedReward, stakerInitializer);
+        assert(res === gainedReward);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, stakerRewardAccount) === gainedReward);
+    });
+
+    it('Apy max is an annual rate in basis points', async () => {
+        const year = 365 * 24 * 60 * 60;
+        //8% a year on 10000 staked
+        const allowance = utils.calculateCapAllowance(800, 10000, 0, 0, year);
+        assert(utils.calculateReward(800, 1000_000, allowance)[0] === 800);
+        assert(utils.calculateReward(800, 1000_000, utils.calculateCapAllowance(800, 10000, 0, 0, year / 2))[0] === 400);
+        //earned below the cap is credited as is, the headroom left carries to the next settle
+        const [gained, left] = utils.calculateReward(800, 100, allowance);
+        assert(gained === 100);
+        assert(utils.calculateReward(800, 1000_000, left)[0] === 700);
+        //0 disables the cap
+        assert(utils.calculateReward(0, 1000_000, 0n)[0] === 1000_000);
+    });
+
+    it('Apy max takes rates under 1% and over 100% in basis points', async () => {
+        const poolInitializer = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, poolInitializer.publicKey, 1000_000_000);
+        const lowPool = await lib.initialize(program, funderAuthority.publicKey, mint.publicKey, 50, minTimeframeInSecond, minStakePeriod, poolInitializer);
+        let stakingData = await utils.getStakingData(program, lowPool);
+        assert(stakingData.apyMax === 50);
+
+        const highInitializer = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, highInitializer.publicKey, 1000_000_000);
+        const highPool = await lib.initialize(program, funderAuthority.publicKey, mint.publicKey, 20000, minTimeframeInSecond, minStakePeriod, highInitializer);
+        stakingData = await utils.getStakingData(program, highPool);
+        assert(stakingData.apyMax === 20000);
+
+        const tooHighInitializer = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, tooHighInitializer.publicKey, 1000_000_000);
+        const tooHighPool = await lib.initialize(program, funderAuthority.publicKey, mint.publicKey, 1000_001, minTimeframeInSecond, minStakePeriod, tooHighInitializer);
+        assert(await utils.getStakingData(program, tooHighPool) === null);
+
+        let res = await lib.changeSetting(program, lowPool, 10000, minStakePeriod, 0, 0, poolInitializer);
+        assert(res === true);
+        res = await lib.changeSetting(program, lowPool, 1000_001, minStakePeriod, 0, 0, poolInitializer);
+        assert(res === 'apy max must be at most 1000000 bps');
+        stakingData = await utils.getStakingData(program, lowPool);
+        assert(stakingData.apyMax === 10000);
+        assert(stakingData.apyMaxInBps === true);
+    });
+
+    it('Apy max caps the claimed reward at its annual rate', async () => {
+        const poolInitializer = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, poolInitializer.publicKey, 1000_000_000);
+        const apyMaxBps = 1000;
+        const cappedPool = await lib.initialize(program, funderAuthority.publicKey, mint.publicKey, apyMaxBps, minTimeframeInSecond, minStakePeriod, poolInitializer);
+        await lib.initializeStakeState(program, cappedPool, stakerInitializer);
 
-        const nowTs = await utils.getNowTs(program.provider.connection);
-        const reward = utils.calculateReward(800, 1000, 1000, 0, 30, 1000, 0, stakingData.minStakePeriod, nowTs);
+        //10% a year on a large stake is a few tokens a second, far below what the timeframe emits
+        const amount = 10_000_000_000;
+        const fundAmount = 1000_000;
+        await lib.staking(program, cappedPool, stakerAccount, amount, stakerInitializer);
+        await lib.funding(program, cappedPool, funderAccount, fundAmount, 30, funderAuthority);
+
+        console.log("waiting 32 seconds");
+        await sleep(32000);
+
+        const res = await lib.claimReward(program, cappedPool, stakerAccount, 1, stakerInitializer);
+        assert(res === 1);
+        const stakerState = await utils.getStakingState(program, await utils.getStakingStateAccount(cappedPool, stakerInitializer.publicKey, program.programId));
+        const stakingData = await utils.getStakingData(program, cappedPool);
+        const stakedTime = stakerState.stakedTime.toNumber();
+        const allowance = utils.calculateCapAllowance(apyMaxBps, amount, 0, 0, stakerState.settledTime.toNumber() - stakedTime);
+        const [cap] = utils.calculateReward(apyMaxBps, fundAmount, allowance);
+        assert(cap > 0);
+        assert(stakerState.gainedReward.toNumber() + 1 === cap);
+        assert(stakingData.cappedReward.toNumber() > 0);
+        assert(stakingData.apyMaxInBps === true);
+    });
+
+    it('Apy max caps the same reward however often the position is settled', async () => {
+        const poolInitializer = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, poolInitializer.publicKey, 1000_000_000);
+        const apyMaxBps = 1000;
+        const cappedPool = await lib.initialize(program, funderAuthority.publicKey, mint.publicKey, apyMaxBps, minTimeframeInSecond, minStakePeriod, poolInitializer);
+        const stakerStateAccount = await lib.initializeStakeState(program, cappedPool, stakerInitializer);
+
+        const amount = 10_000_000_000;
+        await lib.staking(program, cappedPool, stakerAccount, amount, stakerInitializer);
+        await lib.funding(program, cappedPool, funderAccount, 1000_000, 30, funderAuthority);
+        const keeper = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, keeper.publicKey, 1000_000_000);
+        const keeperAccount = await mint.createAccount(keeper.publicKey);
+
+        //every settle is capped, what it credits and the headroom left add up to the cap since the stake
+        for (let i = 0; i < 3; i++) {
+            console.log("waiting 5 seconds");
+            await sleep(5000);
+            await lib.settle(program, cappedPool, keeperAccount, [stakerInitializer.publicKey], keeper);
+        }
+        const stakerState = await utils.getStakingState(program, stakerStateAccount);
+        const stakedTime = stakerState.stakedTime.toNumber();
+        const allowance = utils.calculateCapAllowance(apyMaxBps, amount, 0, 0, stakerState.settledTime.toNumber() - stakedTime);
+        const gained = stakerState.gainedReward.toNumber();
+        assert(gained > 0);
+        assert(BigInt(gained) * 10000n * BigInt(365 * 24 * 60 * 60) + BigInt(stakerState.capHeadroom.toString()) === allowance);
+    });
+
+    it('Capped reward rolls into the next timeframe', async () => {
+        const poolInitializer = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, poolInitializer.publicKey, 1000_000_000);
+        const cappedPool = await lib.initialize(program, funderAuthority.publicKey, mint.publicKey, 9999, minTimeframeInSecond, minStakePeriod, poolInitializer);
+        await lib.initializeStakeState(program, cappedPool, stakerInitializer);
+
+        const amount = 1000;
+        await lib.staking(program, cappedPool, stakerAccount, amount, stakerInitializer);
+        await lib.funding(program, cappedPool, funderAccount, 1000, 30, funderAuthority);
+
+        console.log("waiting 32 seconds");
+        await sleep(32000);
+
+        //99.99% a year on 1000 staked doesn't reach a single token in 32 seconds, a top-up settles the position
+        await lib.staking(program, cappedPool, stakerAccount, 1, stakerInitializer);
+        const stakerState = await utils.getStakingState(program, await utils.getStakingStateAccount(cappedPool, stakerInitializer.publicKey, program.programId));
+        assert(stakerState.gainedReward.toNumber() === 0);
+        let res = await lib.claimReward(program, cappedPool, stakerAccount, 1, stakerInitializer);
+        assert(res === 'insufficient gained reward');
+        let stakingData = await utils.getStakingData(program, cappedPool);
+        const cappedReward = stakingData.cappedReward.toNumber();
+        assert(cappedReward > 0);
+
+        res = await lib.funding(program, cappedPool, funderAccount, 1000, 30, funderAuthority);
+        assert(res === 1000);
+        stakingData = await utils.getStakingData(program, cappedPool);
+        assert(stakingData.poolReward.toNumber() === 1000 + cappedReward);
+        assert(stakingData.cappedReward.toNumber() === 0);
+    });
+
+    it('Claiming after lock end settles the boost only up to lock end', async () => {
+        await lib.setLockTiers(program, stakingDataAccount, [{ duration: 5, multiplierBps: 20000 }], stakingInitializer);
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer, 1);
+        await lib.funding(program, stakingDataAccount, funderAccount, 1000, 60, funderAuthority);
+        const stakerStateAccount = await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId);
+        const stakerState = await utils.getStakingState(program, stakerStateAccount);
+        assert(stakerState.lockBoost.toNumber() === amount);
+
+        console.log("waiting 32 seconds");
+        await sleep(32000);
+
+        const res = await lib.claimReward(program, stakingDataAccount, stakerAccount, 1, stakerInitializer);
+        assert(res === 1);
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        const stakerState1 = await utils.getStakingState(program, stakerStateAccount);
+        assert(stakingData.totalLockBoost.toNumber() === 0);
+        assert(stakerState1.lockBoost.toNumber() === 0);
+
+        //the boost earned until lock end only, the rest stays in the pool
+        const earned = utils.calculatePositionEarned(stakingData.rewardPerTokenStored.toString(), stakerState.rewardPerTokenPaid.toString(),
+            amount, amount, stakerState.lockEnd.toNumber(), stakerState.settledTime.toNumber(), stakerState1.settledTime.toNumber());
+        assert(stakerState1.gainedReward.toNumber() === earned - 1);
+        assert(earned < utils.calculateEarned(stakingData.rewardPerTokenStored.toString(), stakerState.rewardPerTokenPaid.toString(), amount * 2));
+    });
+
+    it('Anyone settles a page of positions for a keeper tip', async () => {
+        const amount = 1000;
+        let res = await lib.setKeeperTip(program, stakingDataAccount, 5, stakingInitializer);
+        assert(res === 5);
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+
+        //a dust position earns less than a token over the whole timeframe
+        const dustStaker = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, dustStaker.publicKey, 1000_000_000);
+        const dustAccount = await mint.createAccount(dustStaker.publicKey);
+        await mint.mintTo(dustAccount, mintAuthority.publicKey, [mintAuthority], 1);
+        await lib.initializeStakeState(program, stakingDataAccount, dustStaker);
+        await lib.staking(program, stakingDataAccount, dustAccount, 1, dustStaker);
+        await lib.funding(program, stakingDataAccount, funderAccount, 1000, 30, funderAuthority);
+        const owners = [stakerInitializer.publicKey, dustStaker.publicKey];
+
+        //free reward in the rewarder pays the tip
+        let stakingData = await utils.getStakingData(program, stakingDataAccount);
+        await mint.mintTo(stakingData.rewarderAccount, mintAuthority.publicKey, [mintAuthority], 100);
+        const keeper = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, keeper.publicKey, 1000_000_000);
+        const keeperAccount = await mint.createAccount(keeper.publicKey);
+
+        //inside the min stake period nothing is credited, so nothing is tipped
+        res = await lib.settle(program, stakingDataAccount, keeperAccount, owners, keeper);
+        assert(res === 2);
+        stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.totalSettledPositions.toNumber() === 0);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, keeperAccount) === 0);
+
+        console.log("waiting 32 seconds");
+        await sleep(32000);
+
+        //the dust position is settled without a tip
+        res = await lib.settle(program, stakingDataAccount, keeperAccount, owners, keeper);
+        assert(res === 2);
+        stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.totalSettledPositions.toNumber() === 1);
+        const dustState = await utils.getStakingState(program, await utils.getStakingStateAccount(stakingDataAccount, dustStaker.publicKey, program.programId));
+        assert(dustState.gainedReward.toNumber() === 0);
+        assert(dustState.rewardPerTokenPaid.toString() === stakingData.rewardPerTokenStored.toString());
+        assert(stakingData.lastSettleTime.toNumber() > 0);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, keeperAccount) === 5);
+
+        const stakerStateAccount = await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId);
+        const stakerState = await utils.getStakingState(program, stakerStateAccount);
+        assert(stakerState.gainedReward.toNumber() > 0);
+        assert(stakerState.rewardPerTokenPaid.toString() === stakingData.rewardPerTokenStored.toString());
+
+        //nothing left to settle, no tip
+        res = await lib.settle(program, stakingDataAccount, keeperAccount, owners, keeper);
+        assert(res === 2);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, keeperAccount) === 5);
+    });
+
+    it('Keeper tip is capped at a share of the settled reward', async () => {
+        const amount = 1000;
+        await lib.setKeeperTip(program, stakingDataAccount, 1000, stakingInitializer);
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        await lib.funding(program, stakingDataAccount, funderAccount, 1000, 30, funderAuthority);
+        let stakingData = await utils.getStakingData(program, stakingDataAccount);
+        await mint.mintTo(stakingData.rewarderAccount, mintAuthority.publicKey, [mintAuthority], 1000);
+        const keeper = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, keeper.publicKey, 1000_000_000);
+        const keeperAccount = await mint.createAccount(keeper.publicKey);
+
+        console.log("waiting 32 seconds");
+        await sleep(32000);
 
+        const res = await lib.settle(program, stakingDataAccount, keeperAccount, [stakerInitializer.publicKey], keeper);
+        assert(res === 1);
+        const stakerState = await utils.getStakingState(program, await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId));
+        const gained = stakerState.gainedReward.toNumber();
+        assert(gained > 0);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, keeperAccount) === Math.floor(gained * 1000 / 10000));
     });
 })
\ No newline at end of file
@@ -0,0 +1,137 @@
+const utils = require('../../lib/utils');
+
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const splToken = require('@solana/spl-token');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+function sleep(ms) {
+    return new Promise((resolve) => {
+        setTimeout(resolve, ms)
+    })
+}
+  
+
+describe('Compound tests', () => {
+    const homedir = os.homedir();
+    process.env.ANCHOR_WALLET = `${homedir}/.config/solana/id.json`;
+
+    // Configure the local cluster.
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    // Read the generated IDL.
+    const idl = JSON.parse(fs.readFileSync('./target/idl/staking.json', 'utf8'));
+
+    // Address of the deployed program.
+    const programId = new anchor.web3.PublicKey(idl.metadata.address);
+    const program = new anchor.Program(idl, programId);
+    const mintAuthority = anchor.web3.Keypair.generate();
+
+    let mint;
+    let stakingInitializer;
+    let stakerInitializer;
+    let stakerAccount;
+    let stakingDataAccount;
+    let stakerStateAccount;
+    let keeper;
+
+    let funderAuthority;
+    let funderAccount;
+    const minTimeframeInSecond = 30;
+    const minStakePeriod = 30;
+    const apyMax = lib.NO_APY_CAP;
+
+    beforeEach(async () => {
+        stakingInitializer = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, stakingInitializer.publicKey, 1000_000_000);
+        mint = await splToken.Token.createMint(
+            provider.connection,
+            stakingInitializer,
+            mintAuthority.publicKey,
+            null,
+            0,
+            splToken.TOKEN_PROGRAM_ID,
+        );
+
+        //init funder
+        funderAuthority = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, funderAuthority.publicKey, 1000_000_000);
+        funderAccount = await mint.createAccount(funderAuthority.publicKey);
+        await mint.mintTo(funderAccount, mintAuthority.publicKey, [mintAuthority], 100_000_000_000);
+
+        //init staker
+        stakerInitializer = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, stakerInitializer.publicKey, 1000_000_000);
+        stakerAccount = await mint.createAccount(stakerInitializer.publicKey);
+        await mint.mintTo(stakerAccount, mintAuthority.publicKey, [mintAuthority], 100_000_000_000);
+
+        //a keeper compounding for others
+        keeper = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, keeper.publicKey, 1000_000_000);
+
+        //init staking
+        stakingDataAccount = await lib.initialize(program, funderAuthority.publicKey, mint.publicKey, apyMax, minTimeframeInSecond, minStakePeriod, stakingInitializer);
+        stakerStateAccount = await lib.initializeStakeState(program, stakingDataAccount, stakerInitializer);
+    });
+
+    it('Compounding restakes the gained reward', async () => {
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        await lib.funding(program, stakingDataAccount, funderAccount, 1000, 30, funderAuthority);
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        const stakerState = await utils.getStakingState(program, stakerStateAccount);
+
+        console.log("waiting 32 seconds");
+        await sleep(32000);
+
+        const frameEnd = stakingData.timeframeStarted.toNumber() + stakingData.timeframeInSecond.toNumber();
+        const gainedReward = utils.pendingReward(stakingData, stakerState, amount, stakerState.stakedTime.toNumber(), frameEnd);
+        assert(gainedReward > 0);
+
+        const escrowBalance = await utils.getTokenAccountBalance(program.provider.connection, stakingData.escrowAccount);
+        const res = await lib.compound(program, stakingDataAccount, stakerInitializer.publicKey, stakerInitializer);
+        assert(res === true);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, stakingData.escrowAccount) === escrowBalance + gainedReward);
+
+        const stakingData1 = await utils.getStakingData(program, stakingDataAccount);
+        const stakerState1 = await utils.getStakingState(program, stakerStateAccount);
+        assert(stakingData1.totalStaked.toNumber() === amount + gainedReward);
+        assert(stakingData1.totalRewardPaid.toNumber() === gainedReward);
+        assert(stakerState1.totalStaked.toNumber() === amount + gainedReward);
+        assert(stakerState1.gainedReward.toNumber() === 0);
+        assert(stakerState1.stakedTime.toNumber() === stakerState.stakedTime.toNumber());
+        assert(stakerState1.history[stakerState1.history.length - 1].action === 10);
+    });
+
+    it('Anybody compounds once the staker opts in', async () => {
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        await lib.funding(program, stakingDataAccount, funderAccount, 1000, 30, funderAuthority);
+
+        console.log("waiting 32 seconds");
+        await sleep(32000);
+
+        const res = await lib.compound(program, stakingDataAccount, stakerInitializer.publicKey, keeper);
+        assert(res === 'auto compound isn`t enabled');
+
+        assert(await lib.setAutoCompound(program, stakingDataAccount, true, stakerInitializer) === true);
+        const res1 = await lib.compound(program, stakingDataAccount, stakerInitializer.publicKey, keeper);
+        assert(res1 === true);
+
+        const stakerState = await utils.getStakingState(program, stakerStateAccount);
+        assert(stakerState.autoCompound === true);
+        assert(stakerState.totalStaked.toNumber() > amount);
+        assert(stakerState.gainedReward.toNumber() === 0);
+    });
+
+    it('Compounding with vesting', async () => {
+        await lib.changeSetting(program, stakingDataAccount, apyMax, minStakePeriod, 100, 0, stakingInitializer);
+        await lib.staking(program, stakingDataAccount, stakerAccount, 1000, stakerInitializer);
+        const res = await lib.compound(program, stakingDataAccount, stakerInitializer.publicKey, stakerInitializer);
+        assert(res === 'reward vests, it can`t be compounded');
+    });
+})
@@ -0,0 +1,174 @@
+const utils = require('../../lib/utils');
+
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const splToken = require('@solana/spl-token');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+function sleep(ms) {
+    return new Promise((resolve) => {
+        setTimeout(resolve, ms)
+    })
+}
+
+
+describe('Fixed term tests', () => {
+    const homedir = os.homedir();
+    process.env.ANCHOR_WALLET = `${homedir}/.config/solana/id.json`;
+
+    // Configure the local cluster.
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    // Read the generated IDL.
+    const idl = JSON.parse(fs.readFileSync('./target/idl/staking.json', 'utf8'));
+
+    // Address of the deployed program.
+    const programId = new anchor.web3.PublicKey(idl.metadata.address);
+    const program = new anchor.Program(idl, programId);
+    const mintAuthority = anchor.web3.Keypair.generate();
+
+    let mint;
+    let stakingInitializer;
+    let stakerInitializer;
+    let stakerAccount;
+    let stakingDataAccount;
+
+    let funderAuthority;
+    const minTimeframeInSecond = 30;
+    const minStakePeriod = 30;
+    const apyMax = lib.NO_APY_CAP;
+    const term = 5;
+    const aprBps = 10000;
+
+    beforeEach(async () => {
+        stakingInitializer = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, stakingInitializer.publicKey, 1000_000_000);
+        mint = await splToken.Token.createMint(
+            provider.connection,
+            stakingInitializer,
+            mintAuthority.publicKey,
+            null,
+            0,
+            splToken.TOKEN_PROGRAM_ID,
+        );
+
+        funderAuthority = anchor.web3.Keypair.generate();
+
+        //init staker
+        stakerInitializer = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, stakerInitializer.publicKey, 1000_000_000);
+        stakerAccount = await mint.createAccount(stakerInitializer.publicKey);
+        await mint.mintTo(stakerAccount, mintAuthority.publicKey, [mintAuthority], 100_000_000_000);
+
+        //init staking
+        stakingDataAccount = await lib.initialize(program, funderAuthority.publicKey, mint.publicKey, apyMax, minTimeframeInSecond, minStakePeriod, stakingInitializer);
+    });
+
+    it('Adding a fixed term product', async () => {
+        let res = await lib.addFixedTermProduct(program, stakingDataAccount, 0, aprBps, 0, stakingInitializer);
+        assert(res === 'term must big than zero');
+
+        const productAccount = await lib.addFixedTermProduct(program, stakingDataAccount, term, aprBps, 0, stakingInitializer);
+        const product = await program.account.fixedTermProduct.fetch(productAccount);
+        assert(product.term.toNumber() === term);
+        assert(product.aprBps === aprBps);
+        assert(product.enabled === true);
+        assert((await utils.getStakingData(program, stakingDataAccount)).fixedTermProducts === 1);
+
+        //closed products take no deposit
+        await mint.mintTo(await utils.getRewarderAccount(stakingDataAccount, program.programId), mintAuthority.publicKey, [mintAuthority], 1000);
+        res = await lib.setFixedTermProduct(program, stakingDataAccount, productAccount, false, stakingInitializer);
+        assert(res === true);
+        res = await lib.depositFixedTerm(program, stakingDataAccount, productAccount, stakerAccount, 1000, stakerInitializer);
+        assert(res === 'fixed term product is closed');
+    });
+
+    it('Depositing needs the rewarder to cover the interest', async () => {
+        const productAccount = await lib.addFixedTermProduct(program, stakingDataAccount, term, aprBps, 0, stakingInitializer);
+        const amount = 1000_000_000;
+        const interest = utils.calculateFixedTermInterest(amount, aprBps, term, 0, 0);
+        assert(interest > 0);
+
+        let res = await lib.depositFixedTerm(program, stakingDataAccount, productAccount, stakerAccount, amount, stakerInitializer);
+        assert(res === 'rewarder can`t cover the fixed term reward');
+
+        const rewarderAccount = await utils.getRewarderAccount(stakingDataAccount, program.programId);
+        await mint.mintTo(rewarderAccount, mintAuthority.publicKey, [mintAuthority], interest);
+        res = await lib.depositFixedTerm(program, stakingDataAccount, productAccount, stakerAccount, amount, stakerInitializer);
+        const depositAccount = await utils.getFixedTermDepositAccount(productAccount, stakerInitializer.publicKey, 0, program.programId);
+        assert(res.toBase58() === depositAccount.toBase58());
+
+        const deposit = await program.account.fixedTermDeposit.fetch(depositAccount);
+        assert(deposit.principal.toNumber() === amount);
+        assert(deposit.interest.toNumber() === interest);
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.totalReservedReward.toNumber() === interest);
+        assert(stakingData.totalFixedTermPrincipal.toNumber() === amount);
+        assert(stakingData.totalStaked.toNumber() === 0);
+    });
+
+    it('Redeeming principal and interest at maturity', async () => {
+        const productAccount = await lib.addFixedTermProduct(program, stakingDataAccount, term, aprBps, 0, stakingInitializer);
+        const amount = 1000_000_000;
+        const interest = utils.calculateFixedTermInterest(amount, aprBps, term, 0, 0);
+        const rewarderAccount = await utils.getRewarderAccount(stakingDataAccount, program.programId);
+        await mint.mintTo(rewarderAccount, mintAuthority.publicKey, [mintAuthority], interest);
+
+        const balance = await utils.getTokenAccountBalance(program.provider.connection, stakerAccount);
+        await lib.depositFixedTerm(program, stakingDataAccount, productAccount, stakerAccount, amount, stakerInitializer);
+
+        let res = await lib.redeemFixedTerm(program, stakingDataAccount, productAccount, stakerAccount, stakerInitializer);
+        assert(res === 'fixed term isn`t matured');
+
+        console.log("waiting", term + 1, "seconds");
+        await sleep((term + 1) * 1000);
+
+        res = await lib.redeemFixedTerm(program, stakingDataAccount, productAccount, stakerAccount, stakerInitializer);
+        assert(res === true);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, stakerAccount) === balance + interest);
+
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.totalReservedReward.toNumber() === 0);
+        assert(stakingData.totalFixedTermPrincipal.toNumber() === 0);
+        const depositAccount = await utils.getFixedTermDepositAccount(productAccount, stakerInitializer.publicKey, 0, program.programId);
+        assert(await provider.connection.getAccountInfo(depositAccount) === null);
+    });
+
+    it('Holding two deposits in one product', async () => {
+        const productAccount = await lib.addFixedTermProduct(program, stakingDataAccount, term, aprBps, 0, stakingInitializer);
+        const amount = 1000_000_000;
+        const interest = utils.calculateFixedTermInterest(amount, aprBps, term, 0, 0);
+        const rewarderAccount = await utils.getRewarderAccount(stakingDataAccount, program.programId);
+        await mint.mintTo(rewarderAccount, mintAuthority.publicKey, [mintAuthority], interest * 2);
+
+        const first = await lib.depositFixedTerm(program, stakingDataAccount, productAccount, stakerAccount, amount, stakerInitializer, 0);
+        const second = await lib.depositFixedTerm(program, stakingDataAccount, productAccount, stakerAccount, amount, stakerInitializer, 1);
+        assert(first.toBase58() !== second.toBase58());
+        assert((await program.account.fixedTermDeposit.fetch(first)).nonce.toNumber() === 0);
+        assert((await program.account.fixedTermDeposit.fetch(second)).nonce.toNumber() === 1);
+
+        //a nonce in use takes no other deposit
+        await lib.depositFixedTerm(program, stakingDataAccount, productAccount, stakerAccount, amount, stakerInitializer, 1);
+        let stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.totalFixedTermPrincipal.toNumber() === amount * 2);
+        assert(stakingData.totalReservedReward.toNumber() === interest * 2);
+
+        console.log("waiting", term + 1, "seconds");
+        await sleep((term + 1) * 1000);
+
+        //each redeems on its own
+        const balance = await utils.getTokenAccountBalance(program.provider.connection, stakerAccount);
+        assert(await lib.redeemFixedTerm(program, stakingDataAccount, productAccount, stakerAccount, stakerInitializer, stakerAccount, 1) === true);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, stakerAccount) === balance + amount + interest);
+        assert(await provider.connection.getAccountInfo(second) === null);
+        assert((await program.account.fixedTermDeposit.fetch(first)).principal.toNumber() === amount);
+
+        stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.totalFixedTermPrincipal.toNumber() === amount);
+        assert(stakingData.totalReservedReward.toNumber() === interest);
+    });
+})
@@ -8,6 +8,10 @@ const os = require('os');
 const fs = require('fs');
 const lib = require('../lib');
 
+function sleep(ms) {
+    return new Promise(resolve => setTimeout(resolve, ms));
+}
+
 describe('Funding tests', () => {
     const homedir = os.homedir();
     process.env.ANCHOR_WALLET = `${homedir}/.config/solana/id.json`;
@@ -34,7 +38,7 @@ describe('Funding tests', () => {
     let funderAccount;
     const minTimeframeInSecond = 31;
     const minStakePeriod = 32;
-    const apyMax = 800;
+    const apyMax = lib.NO_APY_CAP;
 
 
     beforeEach(async () => {
@@ -112,4 +116,247 @@ describe('Funding tests', () => {
         assert(res === "timeframe must big than min stake period")
     });
 
+    it('Funding carries the left over reward into the new timeframe', async () => {
+        const timeframeInSecond = 3600;
+        await lib.funding(program, stakingDataAccount, funderAccount, 1000, timeframeInSecond, funderAuthority);
+
+        //nobody staked, the whole reward of the dropped timeframe is left over
+        await lib.funding(program, stakingDataAccount, funderAccount, 500, timeframeInSecond, funderAuthority);
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.poolReward.toNumber() === 1500);
+        assert(stakingData.unallocatedReward.toNumber() === 0);
+        assert(stakingData.totalFunded.toNumber() === 1500);
+    });
+
+    it('Funding sets the left over reward aside for the funder', async () => {
+        const timeframeInSecond = 3600;
+        let res = await lib.setRolloverMode(program, stakingDataAccount, 2, stakingInitializer);
+        assert(res === "invalid rollover mode");
+        res = await lib.setRolloverMode(program, stakingDataAccount, 1, stakingInitializer);
+        assert(res === 1);
+
+        await lib.funding(program, stakingDataAccount, funderAccount, 1000, timeframeInSecond, funderAuthority);
+        await lib.funding(program, stakingDataAccount, funderAccount, 500, timeframeInSecond, funderAuthority);
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.poolReward.toNumber() === 500);
+        assert(stakingData.unallocatedReward.toNumber() === 1000);
+    });
+
+    it('Funder withdraws only the unallocated reward', async () => {
+        const timeframeInSecond = 3600;
+        await lib.setRolloverMode(program, stakingDataAccount, 1, stakingInitializer);
+        await lib.funding(program, stakingDataAccount, funderAccount, 1000, timeframeInSecond, funderAuthority);
+
+        //the running timeframe still has to emit its reward
+        let res = await lib.withdrawUnallocated(program, stakingDataAccount, funderAccount, 100, funderAuthority);
+        assert(res === "amount exceeds the unallocated reward");
+
+        await lib.funding(program, stakingDataAccount, funderAccount, 500, timeframeInSecond, funderAuthority);
+        const funderBalance = await utils.getTokenAccountBalance(program.provider.connection, funderAccount);
+        res = await lib.withdrawUnallocated(program, stakingDataAccount, funderAccount, 1000, stakingInitializer);
+        assert(res !== 1000);
+        res = await lib.withdrawUnallocated(program, stakingDataAccount, funderAccount, 1000, funderAuthority);
+        assert(res === 1000);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, funderAccount) === funderBalance + 1000);
+
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.unallocatedReward.toNumber() === 0);
+        res = await lib.withdrawUnallocated(program, stakingDataAccount, funderAccount, 100, funderAuthority);
+        assert(res === "amount exceeds the unallocated reward");
+    });
+
+    it('Top-up keeps the running timeframe', async () => {
+        const timeframeInSecond = 3600;
+        let res = await lib.topUp(program, stakingDataAccount, funderAccount, 500, 0, funderAuthority);
+        assert(res === "timeframe is ended");
+
+        await lib.funding(program, stakingDataAccount, funderAccount, 1000, timeframeInSecond, funderAuthority);
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+
+        res = await lib.topUp(program, stakingDataAccount, funderAccount, 500, 0, funderAuthority);
+        assert(res === 500);
+        const stakingData1 = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData1.timeframeStarted.toNumber() === stakingData.timeframeStarted.toNumber());
+        assert(stakingData1.timeframeInSecond.toNumber() === timeframeInSecond);
+        //the whole timeframe at the new rate, a little more than funded since some seconds already passed
+        assert(stakingData1.poolReward.toNumber() >= 1500);
+        assert(stakingData1.totalFunded.toNumber() === 1500);
+
+        //the end moves out
+        res = await lib.topUp(program, stakingDataAccount, funderAccount, 500, 3600, funderAuthority);
+        assert(res === 500);
+        const stakingData2 = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData2.timeframeStarted.toNumber() === stakingData.timeframeStarted.toNumber());
+        assert(stakingData2.timeframeInSecond.toNumber() === timeframeInSecond + 3600);
+    });
+
+    it('Queued emission epochs start one after another', async () => {
+        const now = await utils.getNowTs(program.provider.connection);
+        const epochs = [
+            { start: now + 15, end: now + 55, amount: 1000 },
+            { start: now + 55, end: now + 95, amount: 500 },
+        ];
+
+        let res = await lib.queueEmissionEpochs(program, stakingDataAccount, funderAccount, [
+            { start: now + 15, end: now + 55, amount: 1000 },
+            { start: now + 50, end: now + 90, amount: 500 },
+        ], funderAuthority);
+        assert(res === "invalid emission epoch");
+        res = await lib.queueEmissionEpochs(program, stakingDataAccount, funderAccount, epochs, funderAuthority);
+        assert(res === 2);
+        let stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.emissionEpochs.length === 2);
+        assert(stakingData.totalFunded.toNumber() === 1500);
+
+        //a new timeframe would overlap the schedule
+        res = await lib.funding(program, stakingDataAccount, funderAccount, 1000, 3600, funderAuthority);
+        assert(res === "emission epochs are queued");
+
+        await lib.staking(program, stakingDataAccount, stakerAccount, 1000, stakerInitializer);
+
+        //sleep past the end of the second epoch, staking settles both epochs piecewise
+        await sleep((now + 97 - await utils.getNowTs(program.provider.connection)) * 1000);
+        await lib.staking(program, stakingDataAccount, stakerAccount, 1, stakerInitializer);
+        stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.emissionEpochs.length === 0);
+        assert(stakingData.timeframeStarted.toNumber() === epochs[1].start);
+        assert(stakingData.timeframeInSecond.toNumber() === epochs[1].end - epochs[1].start);
+        assert(stakingData.poolReward.toNumber() === 500);
+        assert(stakingData.totalRewardAccrued.toNumber() >= 1490);
+        assert(stakingData.totalRewardAccrued.toNumber() <= 1502);
+    });
+
+    it('Anyone donates into the running timeframe', async () => {
+        const timeframeInSecond = 3600;
+        const donorRecordAccount = await lib.initializeDonor(program, stakingDataAccount, stakerInitializer);
+
+        let res = await lib.donate(program, stakingDataAccount, stakerAccount, 500, stakerInitializer);
+        assert(res === "donations aren`t enabled");
+        res = await lib.setDonationsEnabled(program, stakingDataAccount, true, funderAuthority);
+        assert(res !== true);
+        res = await lib.setDonationsEnabled(program, stakingDataAccount, true, stakingInitializer);
+        assert(res === true);
+
+        res = await lib.donate(program, stakingDataAccount, stakerAccount, 500, stakerInitializer);
+        assert(res === "no emission window to donate into");
+
+        await lib.funding(program, stakingDataAccount, funderAccount, 1000, timeframeInSecond, funderAuthority);
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        res = await lib.donate(program, stakingDataAccount, stakerAccount, 500, stakerInitializer);
+        assert(res === 500);
+        res = await lib.donate(program, stakingDataAccount, stakerAccount, 250, stakerInitializer);
+        assert(res === 250);
+
+        //the timeframe is kept, the donation is spread over the rest of it
+        const stakingData1 = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData1.timeframeStarted.toNumber() === stakingData.timeframeStarted.toNumber());
+        assert(stakingData1.timeframeInSecond.toNumber() === timeframeInSecond);
+        assert(stakingData1.poolReward.toNumber() >= 1750);
+        assert(stakingData1.totalFunded.toNumber() === 1750);
+        assert(stakingData1.totalDonated.toNumber() === 750);
+
+        const donorRecord = await utils.getDonorRecord(program, donorRecordAccount);
+        assert(donorRecord.donor.toBase58() === stakerInitializer.publicKey.toBase58());
+        assert(donorRecord.totalDonated.toNumber() === 750);
+    });
+
+    it('Funding doesn`t walk the stakers', async () => {
+        const timeframeInSecond = 3600;
+        const stakers = [stakerInitializer];
+        for (let i = 0; i < 4; i++) {
+            const staker = anchor.web3.Keypair.generate();
+            await utils.createWallet(provider.connection, staker.publicKey, 1000_000_000);
+            await lib.initializeStakeState(program, stakingDataAccount, staker);
+            stakers.push(staker);
+        }
+        for (const staker of stakers) {
+            const account = await mint.createAccount(staker.publicKey);
+            await mint.mintTo(account, mintAuthority.publicKey, [mintAuthority], 1000);
+            await lib.staking(program, stakingDataAccount, account, 1000, staker);
+        }
+
+        await lib.funding(program, stakingDataAccount, funderAccount, 1000, timeframeInSecond, funderAuthority);
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        const res = await lib.funding(program, stakingDataAccount, funderAccount, 1000, timeframeInSecond, funderAuthority);
+        assert(res === 1000);
+
+        //the new timeframe is live at once, the positions settle against the accumulator later
+        const stakingData1 = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData1.totalStaked.toNumber() === 5000);
+        assert(stakingData1.timeframeStarted.toNumber() >= stakingData.timeframeStarted.toNumber());
+        assert(stakingData1.poolReward.toNumber() >= 1000);
+        assert(stakingData1.totalFunded.toNumber() === 2000);
+        for (const staker of stakers) {
+            const stakerStateAccount = await utils.getStakingStateAccount(stakingDataAccount, staker.publicKey, program.programId);
+            const stakerState = await utils.getStakingState(program, stakerStateAccount);
+            assert(stakerState.gainedReward.toNumber() === 0);
+        }
+    });
+
+    it('Funding reconciles tokens already in the rewarder', async () => {
+        const timeframeInSecond = 3600;
+        let stakingData = await utils.getStakingData(program, stakingDataAccount);
+        await mint.mintTo(stakingData.rewarderAccount, mintAuthority.publicKey, [mintAuthority], 300);
+
+        const funderBalance = await utils.getTokenAccountBalance(program.provider.connection, funderAccount);
+        let res = await lib.funding(program, stakingDataAccount, funderAccount, 1000, timeframeInSecond, funderAuthority);
+        assert(res === 1000);
+
+        //the whole amount is transferred and counted, the stray tokens carry into the timeframe
+        stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, funderAccount) === funderBalance - 1000);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, stakingData.rewarderAccount) === 1300);
+        assert(stakingData.totalFunded.toNumber() === 1000);
+        assert(stakingData.poolReward.toNumber() === 1300);
+
+        //the funder can't fund more than it holds
+        res = await lib.funding(program, stakingDataAccount, funderAccount, funderBalance, timeframeInSecond, funderAuthority);
+        assert(res === "insufficient balance");
+    });
+
+    it('Funding sets tokens already in the rewarder aside for the funder', async () => {
+        const timeframeInSecond = 3600;
+        await lib.setRolloverMode(program, stakingDataAccount, 1, stakingInitializer);
+        let stakingData = await utils.getStakingData(program, stakingDataAccount);
+        await mint.mintTo(stakingData.rewarderAccount, mintAuthority.publicKey, [mintAuthority], 300);
+
+        const res = await lib.funding(program, stakingDataAccount, funderAccount, 1000, timeframeInSecond, funderAuthority);
+        assert(res === 1000);
+        stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.poolReward.toNumber() === 1000);
+        assert(stakingData.unallocatedReward.toNumber() === 300);
+        assert(stakingData.totalFunded.toNumber() === 1000);
+
+        //rewarder = pending + reserved + unallocated + pool reward
+        const rewarderBalance = await utils.getTokenAccountBalance(program.provider.connection, stakingData.rewarderAccount);
+        assert(rewarderBalance === stakingData.unallocatedReward.toNumber() + stakingData.poolReward.toNumber());
+    });
+
+    it('Funding a pool with many positions fits one transaction', async () => {
+        const timeframeInSecond = 3600;
+        const positions = 32;
+        for (let i = 0; i < positions; i++) {
+            const staker = anchor.web3.Keypair.generate();
+            await utils.createWallet(provider.connection, staker.publicKey, 1000_000_000);
+            await lib.initializeStakeState(program, stakingDataAccount, staker);
+            const account = await mint.createAccount(staker.publicKey);
+            await mint.mintTo(account, mintAuthority.publicKey, [mintAuthority], 1000);
+            await lib.staking(program, stakingDataAccount, account, 1000, staker);
+        }
+
+        const res = await lib.funding(program, stakingDataAccount, funderAccount, 1000, timeframeInSecond, funderAuthority);
+        assert(res === 1000);
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.totalStaked.toNumber() === positions * 1000);
+        assert(stakingData.poolReward.toNumber() === 1000);
+        assert(stakingData.timeframeInSecond.toNumber() === timeframeInSecond);
+
+        //a second funding over the same positions replaces the running timeframe in one transaction too
+        const res1 = await lib.funding(program, stakingDataAccount, funderAccount, 1000, timeframeInSecond, funderAuthority);
+        assert(res1 === 1000);
+        const stakingData1 = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData1.totalFunded.toNumber() === 2000);
+        assert(stakingData1.poolReward.toNumber() >= 1000);
+    });
+
 })
\ No newline at end of file
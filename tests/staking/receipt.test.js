@@ -0,0 +1,281 @@
+const utils = require('../../lib/utils');
+
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const splToken = require('@solana/spl-token');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+function sleep(ms) {
+    return new Promise((resolve) => {
+        setTimeout(resolve, ms)
+    })
+}
+
+
+describe('Receipt tests', () => {
+    const homedir = os.homedir();
+    process.env.ANCHOR_WALLET = `${homedir}/.config/solana/id.json`;
+
+    // Configure the local cluster.
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    // Read the generated IDL.
+    const idl = JSON.parse(fs.readFileSync('./target/idl/staking.json', 'utf8'));
+
+    // Address of the deployed program.
+    const programId = new anchor.web3.PublicKey(idl.metadata.address);
+    const program = new anchor.Program(idl, programId);
+    const mintAuthority = anchor.web3.Keypair.generate();
+
+    let mint;
+    let receiptMint;
+    let stakingInitializer;
+    let stakerInitializer;
+    let stakerAccount;
+    let receiptAccount;
+    let holder;
+    let holderAccount;
+    let holderReceiptAccount;
+    let stakingDataAccount;
+
+    let funderAuthority;
+    const minTimeframeInSecond = 30;
+    const minStakePeriod = 0;
+    const apyMax = lib.NO_APY_CAP;
+
+    beforeEach(async () => {
+        stakingInitializer = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, stakingInitializer.publicKey, 1000_000_000);
+        mint = await splToken.Token.createMint(
+            provider.connection,
+            stakingInitializer,
+            mintAuthority.publicKey,
+            null,
+            0,
+            splToken.TOKEN_PROGRAM_ID,
+        );
+        funderAuthority = anchor.web3.Keypair.generate();
+
+        //init staker
+        stakerInitializer = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, stakerInitializer.publicKey, 1000_000_000);
+        stakerAccount = await mint.createAccount(stakerInitializer.publicKey);
+        await mint.mintTo(stakerAccount, mintAuthority.publicKey, [mintAuthority], 100_000_000_000);
+
+        //init the receipt holder
+        holder = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, holder.publicKey, 1000_000_000);
+        holderAccount = await mint.createAccount(holder.publicKey);
+
+        //init staking and receipts
+        stakingDataAccount = await lib.initialize(program, funderAuthority.publicKey, mint.publicKey, apyMax, minTimeframeInSecond, minStakePeriod, stakingInitializer);
+        const receiptMintAccount = await lib.initReceipt(program, stakingDataAccount, stakingInitializer);
+        receiptMint = new splToken.Token(provider.connection, receiptMintAccount, splToken.TOKEN_PROGRAM_ID, stakingInitializer);
+        receiptAccount = await utils.createAssociatedTokenAccount(provider.connection, receiptMintAccount, stakerInitializer, stakerInitializer.publicKey);
+        holderReceiptAccount = await utils.createAssociatedTokenAccount(provider.connection, receiptMintAccount, holder, holder.publicKey);
+
+        await lib.initializeStakeState(program, stakingDataAccount, stakerInitializer);
+        await lib.initializeStakeState(program, stakingDataAccount, holder);
+    });
+
+    it('Staking mints receipts and unstaking burns them', async () => {
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.receiptMint.toBase58() === receiptMint.publicKey.toBase58());
+
+        const amount = 1000;
+        const res = await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer, 0, receiptAccount);
+        assert(res === amount);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, receiptAccount) === amount);
+
+        const stakeStateAccount = await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId);
+        const stakeState = await utils.getStakingState(program, stakeStateAccount);
+        assert(stakeState.receiptStaked.toNumber() === amount);
+
+        const res1 = await lib.unstaking(program, stakingDataAccount, stakerAccount, 400, stakerInitializer, stakerAccount, receiptAccount);
+        assert(res1 === 400);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, receiptAccount) === amount - 400);
+        const stakeState1 = await utils.getStakingState(program, stakeStateAccount);
+        assert(stakeState1.receiptStaked.toNumber() === amount - 400);
+    });
+
+    it('Transferring receipts moves the position', async () => {
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer, 0, receiptAccount);
+
+        const res = await lib.transferReceipt(program, stakingDataAccount, receiptAccount, holder.publicKey, holderReceiptAccount, 600, stakerInitializer);
+        assert(res === 600);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, holderReceiptAccount) === 600);
+
+        const stakeState = await utils.getStakingState(program, await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId));
+        const holderState = await utils.getStakingState(program, await utils.getStakingStateAccount(stakingDataAccount, holder.publicKey, program.programId));
+        assert(stakeState.totalStaked.toNumber() === amount - 600);
+        assert(holderState.totalStaked.toNumber() === 600);
+        assert(holderState.receiptStaked.toNumber() === 600);
+
+        //the pool total doesn't change
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.totalStaked.toNumber() === amount);
+
+        //the holder unstakes with the receipts
+        const res1 = await lib.unstaking(program, stakingDataAccount, holderAccount, 600, holder, holderAccount, holderReceiptAccount);
+        assert(res1 === 600);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, holderAccount) === 600);
+    });
+
+    it('Transferring receipts keeps the staked time and the pending reward', async () => {
+        await lib.changeSetting(program, stakingDataAccount, apyMax, 30, 0, 0, stakingInitializer);
+        await utils.createWallet(provider.connection, funderAuthority.publicKey, 1000_000_000);
+        const funderAccount = await mint.createAccount(funderAuthority.publicKey);
+        await mint.mintTo(funderAccount, mintAuthority.publicKey, [mintAuthority], 1000);
+        await mint.mintTo(holderAccount, mintAuthority.publicKey, [mintAuthority], 1000);
+
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, holderAccount, amount, holder, 0, holderReceiptAccount);
+        console.log("waiting 3 seconds");
+        await sleep(3000);
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer, 0, receiptAccount);
+        await lib.funding(program, stakingDataAccount, funderAccount, 1000, 30, funderAuthority);
+        console.log("waiting 3 seconds");
+        await sleep(3000);
+
+        const stakeStateAccount = await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId);
+        const holderStateAccount = await utils.getStakingStateAccount(stakingDataAccount, holder.publicKey, program.programId);
+        const stakeState = await utils.getStakingState(program, stakeStateAccount);
+        const holderState = await utils.getStakingState(program, holderStateAccount);
+        const res = await lib.transferReceipt(program, stakingDataAccount, receiptAccount, holder.publicKey, holderReceiptAccount, 600, stakerInitializer);
+        assert(res === 600);
+
+        //the older holder position doesn't absorb the transferred amount
+        const stakeState1 = await utils.getStakingState(program, stakeStateAccount);
+        const holderState1 = await utils.getStakingState(program, holderStateAccount);
+        assert(stakeState1.stakedTime.toNumber() === stakeState.stakedTime.toNumber());
+        assert(holderState1.stakedTime.toNumber() === Math.floor((amount * holderState.stakedTime.toNumber() + 600 * stakeState.stakedTime.toNumber()) / (amount + 600)));
+
+        //inside min stake period the reward stays pending on both sides, none of it is forfeited
+        assert(stakeState1.pendingReward.toNumber() > 0);
+        assert(holderState1.pendingReward.toNumber() > 0);
+        assert(stakeState1.gainedReward.toNumber() === 0);
+        assert(holderState1.gainedReward.toNumber() === 0);
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        const pending = stakeState1.pendingReward.toNumber() + holderState1.pendingReward.toNumber();
+        assert(stakingData.totalRewardAccrued.toNumber() - pending <= 2);
+    });
+
+    it('Transferring receipts inside penalty period', async () => {
+        await lib.setPenalty(program, stakingDataAccount, 500, 100, 0, anchor.web3.PublicKey.default, stakingInitializer);
+        await lib.staking(program, stakingDataAccount, stakerAccount, 1000, stakerInitializer, 0, receiptAccount);
+        const res = await lib.transferReceipt(program, stakingDataAccount, receiptAccount, holder.publicKey, holderReceiptAccount, 600, stakerInitializer);
+        assert(res === 'receipt can`t move while its stake is inside penalty period');
+    });
+
+    it('Receipts moved with a token transfer are redeemed against the position', async () => {
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer, 0, receiptAccount);
+        await receiptMint.transfer(receiptAccount, holderReceiptAccount, stakerInitializer, [], 300);
+
+        //the owner can't unstake the receipts it doesn't hold anymore
+        const res = await lib.unstaking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer, stakerAccount, receiptAccount);
+        assert(res !== amount);
+
+        //the holder can't redeem more than the owner gave away
+        const res1 = await lib.redeemReceipt(program, stakingDataAccount, stakerInitializer.publicKey, receiptAccount, holderReceiptAccount, holderAccount, 400, holder);
+        assert(res1 === "receipt can`t be redeemed against this position");
+
+        const res2 = await lib.redeemReceipt(program, stakingDataAccount, stakerInitializer.publicKey, receiptAccount, holderReceiptAccount, holderAccount, 300, holder);
+        assert(res2 === 300);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, holderAccount) === 300);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, holderReceiptAccount) === 0);
+
+        const stakeState = await utils.getStakingState(program, await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId));
+        assert(stakeState.totalStaked.toNumber() === amount - 300);
+        assert(stakeState.receiptStaked.toNumber() === amount - 300);
+    });
+
+    it('Redeeming under unbonding moves the reward share and the unbonding to the holder', async () => {
+        await lib.setUnbondingPeriod(program, stakingDataAccount, 100, stakingInitializer);
+        await utils.createWallet(provider.connection, funderAuthority.publicKey, 1000_000_000);
+        const funderAccount = await mint.createAccount(funderAuthority.publicKey);
+        await mint.mintTo(funderAccount, mintAuthority.publicKey, [mintAuthority], 1000);
+
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer, 0, receiptAccount);
+        await lib.funding(program, stakingDataAccount, funderAccount, 1000, 30, funderAuthority);
+        await receiptMint.transfer(receiptAccount, holderReceiptAccount, stakerInitializer, [], 300);
+
+        console.log("waiting 5 seconds");
+        await sleep(5000);
+
+        const res = await lib.redeemReceipt(program, stakingDataAccount, stakerInitializer.publicKey, receiptAccount, holderReceiptAccount, holderAccount, 300, holder);
+        assert(res === 300);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, holderAccount) === 0);
+
+        const stakeState = await utils.getStakingState(program, await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId));
+        const holderState = await utils.getStakingState(program, await utils.getStakingStateAccount(stakingDataAccount, holder.publicKey, program.programId));
+        assert(holderState.unbondingAmount.toNumber() === 300);
+        assert(holderState.totalStaked.toNumber() === 0);
+
+        //the holder takes the share of the reward the redeemed receipts earned, the owner the share of the ones it holds
+        const gained = stakeState.gainedReward.toNumber() + holderState.gainedReward.toNumber();
+        assert(holderState.gainedReward.toNumber() > 0);
+        assert(stakeState.gainedReward.toNumber() === Math.floor(gained * 700 / amount));
+        assert(stakeState.receiptReward.toNumber() === 0);
+        assert(stakeState.awayReceiptReward.toNumber() === 0);
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.totalUnbonding.toNumber() === 300);
+    });
+
+    it('Claiming leaves the reward of the receipts given away to their holder', async () => {
+        await utils.createWallet(provider.connection, funderAuthority.publicKey, 1000_000_000);
+        const funderAccount = await mint.createAccount(funderAuthority.publicKey);
+        await mint.mintTo(funderAccount, mintAuthority.publicKey, [mintAuthority], 1000);
+
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer, 0, receiptAccount);
+        await lib.funding(program, stakingDataAccount, funderAccount, 1000, 30, funderAuthority);
+        await receiptMint.transfer(receiptAccount, holderReceiptAccount, stakerInitializer, [], 300);
+
+        console.log("waiting 5 seconds");
+        await sleep(5000);
+
+        //the owner only gets the reward of the receipts it still holds
+        const res = await lib.claimReward(program, stakingDataAccount, stakerAccount, 10, stakerInitializer);
+        assert(res === 10);
+        const stakeStateAccount = await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId);
+        const stakeState = await utils.getStakingState(program, stakeStateAccount);
+        const claimed = stakeState.gainedReward.toNumber() + 10;
+        const awayReward = stakeState.awayReceiptReward.toNumber();
+        assert(stakeState.awayReceipts.toNumber() === 300);
+        assert(awayReward > 0);
+        assert(claimed === Math.floor((claimed + awayReward) * 700 / amount));
+
+        const res1 = await lib.redeemReceipt(program, stakingDataAccount, stakerInitializer.publicKey, receiptAccount, holderReceiptAccount, holderAccount, 300, holder);
+        assert(res1 === 300);
+
+        //the holder gets what the receipts earned before and after the claim
+        const holderState = await utils.getStakingState(program, await utils.getStakingStateAccount(stakingDataAccount, holder.publicKey, program.programId));
+        assert(holderState.gainedReward.toNumber() >= awayReward);
+        const stakeState1 = await utils.getStakingState(program, stakeStateAccount);
+        assert(stakeState1.awayReceipts.toNumber() === 0);
+        assert(stakeState1.awayReceiptReward.toNumber() === 0);
+    });
+
+    it('Redeeming measures the shortfall on the account the receipts went to', async () => {
+        //the owner keeps the receipts in an account other than the associated one
+        const otherReceiptAccount = await receiptMint.createAccount(stakerInitializer.publicKey);
+        await lib.staking(program, stakingDataAccount, stakerAccount, 1000, stakerInitializer, 0, otherReceiptAccount);
+        let res = await lib.staking(program, stakingDataAccount, stakerAccount, 1000, stakerInitializer, 0, receiptAccount);
+        assert(res === 'invalid receipt account');
+
+        //receipts of the holder position can't be redeemed against the owner position
+        await mint.mintTo(holderAccount, mintAuthority.publicKey, [mintAuthority], 300);
+        await lib.staking(program, stakingDataAccount, holderAccount, 300, holder, 0, holderReceiptAccount);
+        res = await lib.redeemReceipt(program, stakingDataAccount, stakerInitializer.publicKey, receiptAccount, holderReceiptAccount, holderAccount, 300, holder);
+        assert(res !== 300);
+        res = await lib.redeemReceipt(program, stakingDataAccount, stakerInitializer.publicKey, otherReceiptAccount, holderReceiptAccount, holderAccount, 300, holder);
+        assert(res === "receipt can`t be redeemed against this position");
+    });
+})
@@ -8,6 +8,12 @@ const os = require('os');
 const fs = require('fs');
 const lib = require('../lib');
 
+function sleep(ms) {
+    return new Promise((resolve) => {
+        setTimeout(resolve, ms)
+    })
+}
+
 describe('Staking tests', () => {
     const homedir = os.homedir();
     process.env.ANCHOR_WALLET = `${homedir}/.config/solana/id.json`;
@@ -34,7 +40,7 @@ describe('Staking tests', () => {
     let funderAccount;
     const minTimeframeInSecond = 30;
     const minStakePeriod = 30;
-    const apyMax = 800;
+    const apyMax = lib.NO_APY_CAP;
 
     beforeEach(async () => {
         // create wallet A
@@ -94,4 +100,204 @@ describe('Staking tests', () => {
         assert(res === "insufficient balance")
     });
 
+    it('Staking keeps the position in the staker state', async () => {
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.totalStaked.toNumber() === amount * 2);
+        assert(stakingData.stakers.length === 0);
+
+        const stakerStateAccount = await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId);
+        const stakerState = await utils.getStakingState(program, stakerStateAccount);
+        assert(stakerState.totalStaked.toNumber() === amount * 2);
+        assert(stakerState.stakedTime.toNumber() > 0);
+        assert(stakerState.gainedReward.toNumber() === 0);
+    });
+
+    it('Migrating staker without legacy entry', async () => {
+        const stakerStateAccount = await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId);
+        const res = await lib.migrateStaker(program, stakingDataAccount, stakerStateAccount, stakingInitializer);
+        assert(res === 'invalid staking state account data. Can`t find entry!');
+    });
+
+    it('Migrating staker by its owner', async () => {
+        const stakerStateAccount = await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId);
+        const res = await lib.migrateStaker(program, stakingDataAccount, stakerStateAccount, stakerInitializer);
+        assert(res === 'invalid staking state account data. Can`t find entry!');
+
+        //only the initializer migrates other staker states
+        const others = await lib.migrateStaker(program, stakingDataAccount, stakerStateAccount, stakerInitializer, [stakerStateAccount]);
+        assert(others === 'signer isn`t the initializer');
+    });
+
+    it('Migrating staker by non owner', async () => {
+        const stakerStateAccount = await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId);
+        const res = await lib.migrateStaker(program, stakingDataAccount, stakerStateAccount, funderAuthority);
+        assert(res === 'signer is neither the initializer nor the staker');
+    });
+
+    it('Resolving legacy staker without the named entry', async () => {
+        const stakerStateAccount = await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId);
+        const res = await lib.resolveLegacyStaker(program, stakingDataAccount, stakerStateAccount, 0, 1000, 1, stakingInitializer);
+        assert(res === 'invalid staking state account data. Can`t find entry!');
+
+        const other = await lib.resolveLegacyStaker(program, stakingDataAccount, stakerStateAccount, 0, 1000, 1, stakerInitializer);
+        assert(other === 'signer isn`t the initializer');
+    });
+
+    it('Staking with other staker state', async () => {
+        //second staker
+        const secondStaker = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, secondStaker.publicKey, 1000_000_000);
+        await lib.initializeStakeState(program, stakingDataAccount, secondStaker);
+        const stakerStateAccount = await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId);
+        const stakerState = await utils.getStakingState(program, stakerStateAccount);
+        assert(stakerState.myCrc === 0);
+
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        const secondStateAccount = await utils.getStakingStateAccount(stakingDataAccount, secondStaker.publicKey, program.programId);
+        let failed = false;
+        try{
+            await program.rpc.staking(
+                new anchor.BN(1000),
+                {
+                    accounts: {
+                        stakingData: stakingDataAccount,
+                        stakeStateAccount: secondStateAccount,
+                        escrowAccount: stakingData.escrowAccount,
+                        stakerAccount: stakerAccount,
+                        authority: stakerInitializer.publicKey,
+                        tokenProgram: splToken.TOKEN_PROGRAM_ID,
+                    },
+                    signers: [stakerInitializer],
+                },
+            );
+        }catch(e){
+            failed = true;
+        }
+        assert(failed);
+    });
+
+    it('Staking emits Staked event', async () => {
+        const amount = 1000;
+        let event;
+        const listener = program.addEventListener('Staked', (e) => {
+            event = e;
+        });
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        for (let i = 0; i < 20 && event === undefined; i++) {
+            await new Promise((resolve) => setTimeout(resolve, 500));
+        }
+        await program.removeEventListener(listener);
+
+        assert(event !== undefined);
+        assert(event.pool.toBase58() === stakingDataAccount.toBase58());
+        assert(event.user.toBase58() === stakerInitializer.publicKey.toBase58());
+        assert(event.amount.toNumber() === amount);
+        assert(event.userStaked.toNumber() === amount);
+        assert(event.totalStaked.toNumber() === amount);
+    });
+
+    it('Staking top-up keeps accrued reward', async () => {
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+
+        //funding
+        const fundAmount = 1000;
+        const timeframeInSecond = 60;
+        await lib.funding(program, stakingDataAccount, funderAccount, fundAmount, timeframeInSecond, funderAuthority);
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        const stakerStateAccount = await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId);
+        const stakerState = await utils.getStakingState(program, stakerStateAccount);
+
+        console.log("waiting", minStakePeriod + 2, "seconds");
+        await sleep((minStakePeriod + 2) * 1000);
+
+        //top-up
+        const res = await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        assert(res === amount);
+        const nowTs = await utils.getNowTs(program.provider.connection);
+
+        const stakerState1 = await utils.getStakingState(program, stakerStateAccount);
+        const gainedReward = stakerState1.gainedReward.toNumber();
+        assert(gainedReward > 0);
+        assert(gainedReward >= stakerState.gainedReward.toNumber());
+        assert(gainedReward === utils.pendingReward(stakingData, stakerState, amount, stakerState.stakedTime.toNumber(), nowTs));
+        assert(stakerState1.totalStaked.toNumber() === amount * 2);
+
+        //settled reward is claimable right after the top-up
+        const claimed = await lib.claimReward(program, stakingDataAccount, stakerAccount, gainedReward, stakerInitializer);
+        assert(claimed === gainedReward);
+    });
+
+    it('Staking top-up before min stake period', async () => {
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        await lib.funding(program, stakingDataAccount, funderAccount, 1000, 60, funderAuthority);
+
+        const stakerStateAccount = await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId);
+        const stakerState = await utils.getStakingState(program, stakerStateAccount);
+        await sleep(10 * 1000);
+
+        //what the position earned so far stays pending instead of being forfeited by the top-up
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        const stakerState1 = await utils.getStakingState(program, stakerStateAccount);
+        const pendingReward = stakerState1.pendingReward.toNumber();
+        assert(pendingReward > 0);
+        assert(stakerState1.gainedReward.toNumber() === 0);
+
+        //the top-up only moves staked_time by its weight
+        const stakedTime = stakerState.stakedTime.toNumber();
+        const stakedTime1 = stakerState1.stakedTime.toNumber();
+        assert(stakedTime1 > stakedTime);
+        assert(stakedTime1 < stakerState1.settledTime.toNumber());
+
+        //once min stake period passed the pending reward is credited with what was earned since
+        const nowTs = await utils.getNowTs(program.provider.connection);
+        console.log("waiting", stakedTime1 + minStakePeriod + 2 - nowTs, "seconds");
+        await sleep((stakedTime1 + minStakePeriod + 2 - nowTs) * 1000);
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        const stakerState2 = await utils.getStakingState(program, stakerStateAccount);
+        assert(stakerState2.pendingReward.toNumber() === 0);
+        assert(stakerState2.gainedReward.toNumber() > pendingReward);
+    });
+
+    it('Staking with a lock tier', async () => {
+        let res = await lib.setLockTiers(program, stakingDataAccount, [{ duration: 30 * 86400, multiplierBps: 9000 }], stakingInitializer);
+        assert(res === 'lock multiplier must big than 10000');
+        res = await lib.setLockTiers(program, stakingDataAccount, [
+            { duration: 30 * 86400, multiplierBps: 12000 },
+            { duration: 90 * 86400, multiplierBps: 15000 },
+            { duration: 365 * 86400, multiplierBps: 20000 },
+        ], stakingInitializer);
+        assert(res === true);
+
+        const amount = 1000;
+        res = await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer, 4);
+        assert(res === 'invalid lock tier');
+
+        res = await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer, 3);
+        assert(res === amount);
+        const nowTs = await utils.getNowTs(program.provider.connection);
+
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.totalStaked.toNumber() === amount);
+        assert(stakingData.totalLockBoost.toNumber() === amount);
+        assert(utils.getEffectiveStaked(stakingData) === amount * 2);
+
+        const stakerStateAccount = await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId);
+        const stakerState = await utils.getStakingState(program, stakerStateAccount);
+        assert(stakerState.lockedAmount.toNumber() === amount);
+        assert(stakerState.lockBoost.toNumber() === amount);
+        assert(stakerState.lockEnd.toNumber() >= nowTs + 365 * 86400 - 5);
+
+        //unlocked top-up keeps the running lock
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        const stakerState1 = await utils.getStakingState(program, stakerStateAccount);
+        assert(stakerState1.lockedAmount.toNumber() === amount);
+        assert(stakerState1.lockEnd.toNumber() === stakerState.lockEnd.toNumber());
+    });
+
 })
\ No newline at end of file
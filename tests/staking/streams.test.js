@@ -0,0 +1,160 @@
+const utils = require('../../lib/utils');
+
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const splToken = require('@solana/spl-token');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+function sleep(ms) {
+    return new Promise((resolve) => {
+        setTimeout(resolve, ms)
+    })
+}
+
+
+describe('Reward stream tests', () => {
+    const homedir = os.homedir();
+    process.env.ANCHOR_WALLET = `${homedir}/.config/solana/id.json`;
+
+    // Configure the local cluster.
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    // Read the generated IDL.
+    const idl = JSON.parse(fs.readFileSync('./target/idl/staking.json', 'utf8'));
+
+    // Address of the deployed program.
+    const programId = new anchor.web3.PublicKey(idl.metadata.address);
+    const program = new anchor.Program(idl, programId);
+    const mintAuthority = anchor.web3.Keypair.generate();
+
+    let mint;
+    let streamMint;
+    let stakingInitializer;
+    let stakerInitializer;
+    let stakerAccount;
+    let stakerStreamAccount;
+    let stakingDataAccount;
+
+    let funderAuthority;
+    let funderAccount;
+    let streamFunderAccount;
+    const minTimeframeInSecond = 30;
+    const minStakePeriod = 30;
+    const apyMax = lib.NO_APY_CAP;
+
+    beforeEach(async () => {
+        stakingInitializer = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, stakingInitializer.publicKey, 1000_000_000);
+        mint = await splToken.Token.createMint(
+            provider.connection,
+            stakingInitializer,
+            mintAuthority.publicKey,
+            null,
+            0,
+            splToken.TOKEN_PROGRAM_ID,
+        );
+        streamMint = await splToken.Token.createMint(
+            provider.connection,
+            stakingInitializer,
+            mintAuthority.publicKey,
+            null,
+            6,
+            splToken.TOKEN_PROGRAM_ID,
+        );
+
+        //init funder
+        funderAuthority = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, funderAuthority.publicKey, 1000_000_000);
+        funderAccount = await mint.createAccount(funderAuthority.publicKey);
+        await mint.mintTo(funderAccount, mintAuthority.publicKey, [mintAuthority], 100_000_000_000);
+        streamFunderAccount = await streamMint.createAccount(funderAuthority.publicKey);
+        await streamMint.mintTo(streamFunderAccount, mintAuthority.publicKey, [mintAuthority], 100_000_000_000);
+
+        //init staker
+        stakerInitializer = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, stakerInitializer.publicKey, 1000_000_000);
+        stakerAccount = await mint.createAccount(stakerInitializer.publicKey);
+        await mint.mintTo(stakerAccount, mintAuthority.publicKey, [mintAuthority], 100_000_000_000);
+        stakerStreamAccount = await streamMint.createAccount(stakerInitializer.publicKey);
+
+        //init staking
+        stakingDataAccount = await lib.initialize(program, funderAuthority.publicKey, mint.publicKey, apyMax, minTimeframeInSecond, minStakePeriod, stakingInitializer);
+        await lib.initializeStakeState(program, stakingDataAccount, stakerInitializer);
+    });
+
+    it('Adding a reward stream', async () => {
+        //only the initializer adds streams
+        let res = await lib.addRewardStream(program, stakingDataAccount, funderAuthority.publicKey, streamMint.publicKey, apyMax, minTimeframeInSecond, stakerInitializer);
+        assert((await utils.getStakingData(program, stakingDataAccount)).rewardStreams === 0);
+
+        res = await lib.addRewardStream(program, stakingDataAccount, funderAuthority.publicKey, streamMint.publicKey, apyMax, minTimeframeInSecond, stakingInitializer);
+        const rewardStreamAccount = await utils.getRewardStreamAccount(stakingDataAccount, 0, program.programId);
+        assert(res.toBase58() === rewardStreamAccount.toBase58());
+
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.rewardStreams === 1);
+        const rewardStream = await utils.getRewardStream(program, rewardStreamAccount);
+        assert(rewardStream.stakingAccount.toBase58() === stakingDataAccount.toBase58());
+        assert(rewardStream.index === 0);
+        assert(rewardStream.rewardMint.toBase58() === streamMint.publicKey.toBase58());
+        assert(rewardStream.rewardDecimals === 6);
+        assert(rewardStream.funderAuthority.toBase58() === funderAuthority.publicKey.toBase58());
+
+        //the stream rewarder only takes the stream token
+        res = await lib.fundRewardStream(program, stakingDataAccount, rewardStreamAccount, funderAccount, 1000, 30, funderAuthority);
+        assert(res !== 1000);
+        res = await lib.fundRewardStream(program, stakingDataAccount, rewardStreamAccount, streamFunderAccount, 1000, 30, funderAuthority);
+        assert(res === 1000);
+    });
+
+    it('Stakers accrue every stream independently', async () => {
+        await lib.addRewardStream(program, stakingDataAccount, funderAuthority.publicKey, streamMint.publicKey, apyMax, minTimeframeInSecond, stakingInitializer);
+        await lib.addRewardStream(program, stakingDataAccount, funderAuthority.publicKey, streamMint.publicKey, apyMax, minTimeframeInSecond, stakingInitializer);
+        const firstStream = await utils.getRewardStreamAccount(stakingDataAccount, 0, program.programId);
+        const secondStream = await utils.getRewardStreamAccount(stakingDataAccount, 1, program.programId);
+
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+
+        await lib.funding(program, stakingDataAccount, funderAccount, 1000, 30, funderAuthority);
+        await lib.fundRewardStream(program, stakingDataAccount, firstStream, streamFunderAccount, 1000_000, 30, funderAuthority);
+        await lib.fundRewardStream(program, stakingDataAccount, secondStream, streamFunderAccount, 3000_000, 30, funderAuthority);
+
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        const stakerStateAccount = await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId);
+        const stakerState = await utils.getStakingState(program, stakerStateAccount);
+        const firstData = await utils.getRewardStream(program, firstStream);
+        const secondData = await utils.getRewardStream(program, secondStream);
+
+        console.log("waiting 32 seconds");
+        await sleep(32000);
+
+        const stakedTime = stakerState.stakedTime.toNumber();
+        const baseReward = utils.pendingReward(stakingData, stakerState, amount, stakedTime, stakingData.timeframeStarted.toNumber() + 30);
+        const firstReward = utils.pendingStreamReward(stakingData, firstData, stakerState, amount, stakedTime, firstData.timeframeStarted.toNumber() + 30);
+        const secondReward = utils.pendingStreamReward(stakingData, secondData, stakerState, amount, stakedTime, secondData.timeframeStarted.toNumber() + 30);
+        assert(firstReward > 0);
+        assert(secondReward > firstReward);
+
+        //one stream
+        let res = await lib.claimStreamReward(program, stakingDataAccount, firstStream, stakerStreamAccount, firstReward, stakerInitializer);
+        assert(res === firstReward);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, stakerStreamAccount) === firstReward);
+
+        //the pool reward together with the rest of the streams
+        res = await lib.claimReward(program, stakingDataAccount, stakerAccount, baseReward, stakerInitializer,
+            [[secondStream, stakerStreamAccount]]);
+        assert(res === baseReward);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, stakerStreamAccount) === firstReward + secondReward);
+
+        const state = await utils.getStakingState(program, stakerStateAccount);
+        assert(state.gainedReward.toNumber() === 0);
+        assert(state.streamPositions[0].gainedReward.toNumber() === 0);
+        assert(state.streamPositions[1].gainedReward.toNumber() === 0);
+        assert((await utils.getRewardStream(program, secondStream)).totalRewardPaid.toNumber() === secondReward);
+    });
+})
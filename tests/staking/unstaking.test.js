@@ -45,7 +45,7 @@ describe('Unstaking tests', () => {
     let funderAccount;
     const minTimeframeInSecond = 60;
     const minStakePeriod = 30;
-    const apyMax = 800;
+    const apyMax = lib.NO_APY_CAP;
 
 
     beforeEach(async () => {
@@ -84,6 +84,11 @@ describe('Unstaking tests', () => {
         assert(stakerState.onwerAddress.toBase58() === stakerInitializer.publicKey.toBase58());
     });
 
+    async function getStakerState() {
+        const stakerStateAccount = await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId);
+        return utils.getStakingState(program, stakerStateAccount);
+    }
+
     it('UnStaking full amount', async () => {
         //staking 
         const amount = 1000;
@@ -99,7 +104,7 @@ describe('Unstaking tests', () => {
 
         let stakingData = await utils.getStakingData(program, stakingDataAccount);
         assert(stakingData.totalStaked.toNumber() === amount);
-        assert(stakingData.stakers.length === 1);
+        assert((await getStakerState()).totalStaked.toNumber() > 0);
 
         //unstaking
         const unstakingAmount = 1000;
@@ -108,7 +113,7 @@ describe('Unstaking tests', () => {
 
         stakingData = await utils.getStakingData(program, stakingDataAccount);
         assert(stakingData.totalStaked.toNumber() === amount - unstakingAmount);
-        assert(stakingData.stakers.length === 0);
+        assert((await getStakerState()).totalStaked.toNumber() === 0);
     });
 
     it('UnStaking some amount', async () => {
@@ -126,7 +131,7 @@ describe('Unstaking tests', () => {
 
         let stakingData = await utils.getStakingData(program, stakingDataAccount);
         assert(stakingData.totalStaked.toNumber() === amount);
-        assert(stakingData.stakers.length === 1);
+        assert((await getStakerState()).totalStaked.toNumber() > 0);
 
         //unstaking
         const unstakingAmount = 500;
@@ -135,7 +140,7 @@ describe('Unstaking tests', () => {
 
         stakingData = await utils.getStakingData(program, stakingDataAccount);
         assert(stakingData.totalStaked.toNumber() === amount - unstakingAmount);
-        assert(stakingData.stakers.length === 1);
+        assert((await getStakerState()).totalStaked.toNumber() > 0);
     });
 
     it('UnStaking big amount than staked', async () => {
@@ -153,7 +158,7 @@ describe('Unstaking tests', () => {
 
         let stakingData = await utils.getStakingData(program, stakingDataAccount);
         assert(stakingData.totalStaked.toNumber() === amount);
-        assert(stakingData.stakers.length === 1);
+        assert((await getStakerState()).totalStaked.toNumber() > 0);
 
         //unstaking
         const unstakingAmount = amount + 1;
@@ -162,7 +167,7 @@ describe('Unstaking tests', () => {
 
         stakingData = await utils.getStakingData(program, stakingDataAccount);
         assert(stakingData.totalStaked.toNumber() === amount);
-        assert(stakingData.stakers.length === 1);
+        assert((await getStakerState()).totalStaked.toNumber() > 0);
     });
 
     it('UnStaking some amount before min stake period', async () => {
@@ -180,7 +185,7 @@ describe('Unstaking tests', () => {
 
         let stakingData = await utils.getStakingData(program, stakingDataAccount);
         assert(stakingData.totalStaked.toNumber() === amount);
-        assert(stakingData.stakers.length === 1);
+        assert((await getStakerState()).totalStaked.toNumber() > 0);
 
         //first fund 
         const timeframeInSecond = 100
@@ -196,7 +201,7 @@ describe('Unstaking tests', () => {
 
 
         stakingData = await utils.getStakingData(program, stakingDataAccount);
-        assert(stakingData.stakers[0].gainedReward.toNumber() === 0)
+        assert((await getStakerState()).gainedReward.toNumber() === 0)
     });
 
     it('UnStaking full amount before min stake period', async () => {
@@ -214,7 +219,7 @@ describe('Unstaking tests', () => {
 
         let stakingData = await utils.getStakingData(program, stakingDataAccount);
         assert(stakingData.totalStaked.toNumber() === amount);
-        assert(stakingData.stakers.length === 1);
+        assert((await getStakerState()).totalStaked.toNumber() > 0);
 
         //first fund 
         const timeframeInSecond = 100
@@ -229,7 +234,7 @@ describe('Unstaking tests', () => {
         assert(stakerAccountBalance2 === stakerAccountBalance1 + unstakingAmount);
 
         stakingData = await utils.getStakingData(program, stakingDataAccount);
-        assert(stakingData.stakers.length === 0)
+        assert((await getStakerState()).totalStaked.toNumber() === 0)
     });
 
     it('UnStaking some amount before min stake period with some delay', async () => {
@@ -247,7 +252,7 @@ describe('Unstaking tests', () => {
 
         let stakingData = await utils.getStakingData(program, stakingDataAccount);
         assert(stakingData.totalStaked.toNumber() === amount);
-        assert(stakingData.stakers.length === 1);
+        assert((await getStakerState()).totalStaked.toNumber() > 0);
 
         //first fund 
         const timeframeInSecond = 100
@@ -266,7 +271,7 @@ describe('Unstaking tests', () => {
 
 
         stakingData = await utils.getStakingData(program, stakingDataAccount);
-        assert(stakingData.stakers[0].gainedReward.toNumber() === 0)
+        assert((await getStakerState()).gainedReward.toNumber() === 0)
     });
 
     it('UnStaking full amount before min stake period with some delay', async () => {
@@ -284,7 +289,7 @@ describe('Unstaking tests', () => {
 
         let stakingData = await utils.getStakingData(program, stakingDataAccount);
         assert(stakingData.totalStaked.toNumber() === amount);
-        assert(stakingData.stakers.length === 1);
+        assert((await getStakerState()).totalStaked.toNumber() > 0);
 
         //first fund 
         const timeframeInSecond = 100
@@ -302,7 +307,7 @@ describe('Unstaking tests', () => {
         assert(stakerAccountBalance2 === stakerAccountBalance1 + unstakingAmount);
 
         stakingData = await utils.getStakingData(program, stakingDataAccount);
-        assert(stakingData.stakers.length === 0)
+        assert((await getStakerState()).totalStaked.toNumber() === 0)
     });
 
     it('UnStaking some amount after min stake period with some delay', async () => {
@@ -320,13 +325,15 @@ describe('Unstaking tests', () => {
 
         let stakingData = await utils.getStakingData(program, stakingDataAccount);
         assert(stakingData.totalStaked.toNumber() === amount);
-        assert(stakingData.stakers.length === 1);
+        assert((await getStakerState()).totalStaked.toNumber() > 0);
 
         //first fund 
         const timeframeInSecond = 100
         await lib.funding(program, stakingDataAccount, funderAccount, amount, timeframeInSecond, funderAuthority);
 
         stakingData = await utils.getStakingData(program, stakingDataAccount);
+        const stakerStateAccount = await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId);
+        const stakerState = await utils.getStakingState(program, stakerStateAccount);
        
         //wait some period
         await sleep_sec(minStakePeriod + 1);
@@ -340,20 +347,17 @@ describe('Unstaking tests', () => {
         assert(stakerAccountBalance2 === stakerAccountBalance1 + unstakingAmount);
 
         const nowTs = await utils.getNowTs(program.provider.connection);
-        const gainedReward = utils.calculateReward(
-            stakingData.apyMax, 
-            stakingData.totalStaked.toNumber(),
-            stakingData.poolReward.toNumber(),
-            stakingData.timeframeStarted.toNumber(),
-            stakingData.timeframeStarted.toNumber() + stakingData.timeframeInSecond.toNumber(),
-            500,
-            stakingData.stakers[0].stakedTime.toNumber(),
-            stakingData.minStakePeriod.toNumber(),
+        //whole position is settled, not only the unstaked part
+        const gainedReward = utils.pendingReward(
+            stakingData,
+            stakerState,
+            amount,
+            stakerState.stakedTime.toNumber(),
             nowTs);
 
-        stakingData = await utils.getStakingData(program, stakingDataAccount);
-        assert(stakingData.stakers[0].gainedReward.toNumber() > 0);
-        assert(stakingData.stakers[0].gainedReward.toNumber() === Math.trunc(gainedReward));
+        const stakerState1 = await getStakerState();
+        assert(stakerState1.gainedReward.toNumber() > 0);
+        assert(stakerState1.gainedReward.toNumber() === Math.trunc(gainedReward));
     });
 
     it('UnStaking full amount after min stake period with some delay', async () => {
@@ -371,13 +375,15 @@ describe('Unstaking tests', () => {
 
         let stakingData = await utils.getStakingData(program, stakingDataAccount);
         assert(stakingData.totalStaked.toNumber() === amount);
-        assert(stakingData.stakers.length === 1);
+        assert((await getStakerState()).totalStaked.toNumber() > 0);
 
         //first fund 
         const timeframeInSecond = 100
         await lib.funding(program, stakingDataAccount, funderAccount, amount, timeframeInSecond, funderAuthority);
 
         stakingData = await utils.getStakingData(program, stakingDataAccount);
+        const stakerStateAccount = await utils.getStakingStateAccount(stakingDataAccount, stakerInitializer.publicKey, program.programId);
+        const stakerState = await utils.getStakingState(program, stakerStateAccount);
        
         //wait some period
         await sleep_sec(minStakePeriod + 1);
@@ -388,15 +394,11 @@ describe('Unstaking tests', () => {
         assert(res === unstakingAmount)
 
         const nowTs = await utils.getNowTs(program.provider.connection);
-        const gainedReward = utils.calculateReward(
-            stakingData.apyMax, 
-            stakingData.totalStaked.toNumber(),
-            stakingData.poolReward.toNumber(),
-            stakingData.timeframeStarted.toNumber(),
-            stakingData.timeframeStarted.toNumber() + stakingData.timeframeInSecond.toNumber(),
+        const gainedReward = utils.pendingReward(
+            stakingData,
+            stakerState,
             unstakingAmount,
-            stakingData.stakers[0].stakedTime.toNumber(),
-            stakingData.minStakePeriod.toNumber(),
+            stakerState.stakedTime.toNumber(),
             nowTs);
 
         assert(gainedReward > 0)
@@ -405,7 +407,250 @@ describe('Unstaking tests', () => {
         assert(stakerAccountBalance2 === stakerAccountBalance1 + unstakingAmount + Math.trunc(gainedReward));
 
         stakingData = await utils.getStakingData(program, stakingDataAccount);
-        assert(stakingData.stakers.length == 0);
+        assert((await getStakerState()).totalStaked.toNumber() === 0);
+    });
+
+
+    it('UnStaking with unbonding period', async () => {
+        const unbondingPeriod = 10;
+        const res0 = await lib.setUnbondingPeriod(program, stakingDataAccount, unbondingPeriod, stakingInitializer);
+        assert(res0 === unbondingPeriod);
+
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        const escrowAccount = await utils.getEscrowAccount(stakingDataAccount, program.programId);
+        const escrowAccountBalance = await utils.getTokenAccountBalance(program.provider.connection, escrowAccount);
+        const stakerAccountBalance = await utils.getTokenAccountBalance(program.provider.connection, stakerAccount);
+
+        const unstakingAmount = 500;
+        const res = await lib.unstaking(program, stakingDataAccount, stakerAccount, unstakingAmount, stakerInitializer);
+        assert(res === unstakingAmount);
+
+        //tokens stay in escrow until the unbonding period is over
+        assert(await utils.getTokenAccountBalance(program.provider.connection, escrowAccount) === escrowAccountBalance);
+        let stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.totalStaked.toNumber() === amount - unstakingAmount);
+        assert(stakingData.totalUnbonding.toNumber() === unstakingAmount);
+        assert((await getStakerState()).unbondingAmount.toNumber() === unstakingAmount);
+
+        const res1 = await lib.withdrawUnbonded(program, stakingDataAccount, stakerAccount, stakerInitializer);
+        assert(res1 === 'unbonding period isn`t finished');
+
+        await sleep_sec(unbondingPeriod + 1);
+        const res2 = await lib.withdrawUnbonded(program, stakingDataAccount, stakerAccount, stakerInitializer);
+        assert(res2 === true);
+
+        const stakerAccountBalance1 = await utils.getTokenAccountBalance(program.provider.connection, stakerAccount);
+        assert(stakerAccountBalance1 === stakerAccountBalance + unstakingAmount);
+        stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.totalUnbonding.toNumber() === 0);
+        assert((await getStakerState()).unbondingAmount.toNumber() === 0);
+    });
+
+    it('UnStaking again keeps the end of the earlier unbonding', async () => {
+        const unbondingPeriod = 10;
+        await lib.setUnbondingPeriod(program, stakingDataAccount, unbondingPeriod, stakingInitializer);
+
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        const stakerAccountBalance = await utils.getTokenAccountBalance(program.provider.connection, stakerAccount);
+
+        await lib.unstaking(program, stakingDataAccount, stakerAccount, 300, stakerInitializer);
+        await sleep_sec(6);
+        await lib.unstaking(program, stakingDataAccount, stakerAccount, 200, stakerInitializer);
+        let stakerState = await getStakerState();
+        assert(stakerState.unbondingAmount.toNumber() === 500);
+        assert(stakerState.unbondingRequests.length === 2);
+
+        //only the first request reached its end
+        await sleep_sec(unbondingPeriod - 6 + 1);
+        let res = await lib.withdrawUnbonded(program, stakingDataAccount, stakerAccount, stakerInitializer);
+        assert(res === true);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, stakerAccount) === stakerAccountBalance + 300);
+        stakerState = await getStakerState();
+        assert(stakerState.unbondingAmount.toNumber() === 200);
+        assert(stakerState.unbondingRequests.length === 1);
+
+        res = await lib.withdrawUnbonded(program, stakingDataAccount, stakerAccount, stakerInitializer);
+        assert(res === 'unbonding period isn`t finished');
+
+        await sleep_sec(6);
+        res = await lib.withdrawUnbonded(program, stakingDataAccount, stakerAccount, stakerInitializer);
+        assert(res === true);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, stakerAccount) === stakerAccountBalance + 500);
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.totalUnbonding.toNumber() === 0);
+    });
+
+    it('UnStaking cancel unbonding', async () => {
+        await lib.setUnbondingPeriod(program, stakingDataAccount, 100, stakingInitializer);
+
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        await lib.unstaking(program, stakingDataAccount, stakerAccount, 400, stakerInitializer);
+
+        const res = await lib.cancelUnbonding(program, stakingDataAccount, stakerInitializer);
+        assert(res === true);
+
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.totalStaked.toNumber() === amount);
+        assert(stakingData.totalUnbonding.toNumber() === 0);
+        const stakerState = await getStakerState();
+        assert(stakerState.totalStaked.toNumber() === amount);
+        assert(stakerState.unbondingAmount.toNumber() === 0);
+
+        const res1 = await lib.cancelUnbonding(program, stakingDataAccount, stakerInitializer);
+        assert(res1 === 'no unbonding amount');
+    });
+
+
+    it('UnStaking before penalty period burns penalty', async () => {
+        const res0 = await lib.setPenalty(program, stakingDataAccount, 1000, 100, 2, anchor.web3.PublicKey.default, stakingInitializer);
+        assert(res0 === 1000);
+
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        const stakerAccountBalance = await utils.getTokenAccountBalance(program.provider.connection, stakerAccount);
+        const supply = (await mint.getMintInfo()).supply.toNumber();
+
+        const res = await lib.unstaking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        assert(res === amount);
+
+        const penalty = amount / 10;
+        const stakerAccountBalance1 = await utils.getTokenAccountBalance(program.provider.connection, stakerAccount);
+        assert(stakerAccountBalance1 === stakerAccountBalance + amount - penalty);
+        assert((await mint.getMintInfo()).supply.toNumber() === supply - penalty);
+
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.totalPenalty.toNumber() === penalty);
+        const history = (await getStakerState()).history;
+        assert(history[history.length - 1].action === 5);
+    });
+
+    it('UnStaking before penalty period sends penalty to treasury', async () => {
+        const treasuryAccount = await mint.createAccount(stakingInitializer.publicKey);
+        await lib.setPenalty(program, stakingDataAccount, 500, 100, 1, treasuryAccount, stakingInitializer);
+
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        const res = await lib.unstaking(program, stakingDataAccount, stakerAccount, 400, stakerInitializer);
+        assert(res === 400);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, treasuryAccount) === 20);
+    });
+
+    it('UnStaking before penalty period redistributes penalty', async () => {
+        await lib.setPenalty(program, stakingDataAccount, 1000, 100, 0, anchor.web3.PublicKey.default, stakingInitializer);
+        const rewarderAccount = await utils.getRewarderAccount(stakingDataAccount, program.programId);
+        const rewarderAccountBalance = await utils.getTokenAccountBalance(program.provider.connection, rewarderAccount);
+
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        await lib.unstaking(program, stakingDataAccount, stakerAccount, 500, stakerInitializer);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, rewarderAccount) === rewarderAccountBalance + 50);
+    });
+
+    it('UnStaking matured principal after a top-up pays no penalty', async () => {
+        const penaltyPeriod = 5;
+        await lib.setPenalty(program, stakingDataAccount, 1000, penaltyPeriod, 2, anchor.web3.PublicKey.default, stakingInitializer);
+
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        await sleep_sec(penaltyPeriod + 1);
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        const stakerState = await getStakerState();
+        assert(stakerState.penaltyAmount.toNumber() === amount);
+
+        //the matured first stake leaves first
+        const stakerAccountBalance = await utils.getTokenAccountBalance(program.provider.connection, stakerAccount);
+        let res = await lib.unstaking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        assert(res === amount);
+        const stakerAccountBalance1 = await utils.getTokenAccountBalance(program.provider.connection, stakerAccount);
+        assert(stakerAccountBalance1 === stakerAccountBalance + amount);
+
+        //the top-up is still inside the penalty period
+        res = await lib.unstaking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        assert(res === amount);
+        const stakerAccountBalance2 = await utils.getTokenAccountBalance(program.provider.connection, stakerAccount);
+        assert(stakerAccountBalance2 === stakerAccountBalance1 + amount - amount / 10);
+    });
+
+    it('UnStaking spreads the redistributed penalty over the rest of the timeframe', async () => {
+        await lib.setPenalty(program, stakingDataAccount, 1000, 100, 0, anchor.web3.PublicKey.default, stakingInitializer);
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        await lib.funding(program, stakingDataAccount, funderAccount, 1000, 100, funderAuthority);
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+
+        await lib.unstaking(program, stakingDataAccount, stakerAccount, 500, stakerInitializer);
+        const stakingData1 = await utils.getStakingData(program, stakingDataAccount);
+
+        //same as top_up_timeframe at the unstaking time
+        const nowTs = stakingData1.lastUpdateTime.toNumber();
+        const frame = stakingData.timeframeInSecond.toNumber();
+        const end = stakingData.timeframeStarted.toNumber() + frame;
+        const unemitted = Math.floor(stakingData.poolReward.toNumber() * (end - nowTs) / frame);
+        assert(stakingData1.poolReward.toNumber() === Math.floor((unemitted + 50) * frame / (end - nowTs)));
+    });
+
+    it('UnStaking keeps the penalty redistributed outside a timeframe for the next one', async () => {
+        await lib.setPenalty(program, stakingDataAccount, 1000, 100, 0, anchor.web3.PublicKey.default, stakingInitializer);
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        await lib.unstaking(program, stakingDataAccount, stakerAccount, 500, stakerInitializer);
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.pendingRedistribution.toNumber() === 50);
+
+        //the funder can't take it back
+        const res = await lib.withdrawUnallocated(program, stakingDataAccount, funderAccount, 50, funderAuthority);
+        assert(res === 'amount exceeds the unallocated reward');
+
+        await lib.funding(program, stakingDataAccount, funderAccount, 1000, minTimeframeInSecond, funderAuthority);
+        const stakingData1 = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData1.poolReward.toNumber() === 1050);
+        assert(stakingData1.pendingRedistribution.toNumber() === 0);
+    });
+
+    it('Setting a penalty treasury of another mint', async () => {
+        const otherMint = await splToken.Token.createMint(
+            provider.connection,
+            stakingInitializer,
+            mintAuthority.publicKey,
+            null,
+            0,
+            splToken.TOKEN_PROGRAM_ID,
+        );
+        const treasuryAccount = await otherMint.createAccount(stakingInitializer.publicKey);
+        const res = await lib.setPenalty(program, stakingDataAccount, 500, 100, 1, treasuryAccount, stakingInitializer);
+        assert(res === 'penalty treasury must be a token account of the staking mint');
+    });
+
+    it('UnStaking penalty more than 10000 bps', async () => {
+        const res = await lib.setPenalty(program, stakingDataAccount, 10001, 100, 0, anchor.web3.PublicKey.default, stakingInitializer);
+        assert(res === 'penalty must less than 10000');
+    });
+
+    it('UnStaking locked amount', async () => {
+        await lib.setLockTiers(program, stakingDataAccount, [{ duration: 5, multiplierBps: 15000 }], stakingInitializer);
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer, 1);
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+
+        //only the unlocked top-up can leave before lock end
+        let res = await lib.unstaking(program, stakingDataAccount, stakerAccount, amount + 1, stakerInitializer);
+        assert(res === 'staked amount is locked');
+        res = await lib.unstaking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        assert(res === amount);
+
+        console.log("waiting 6 seconds");
+        await sleep(6000);
+
+        res = await lib.unstaking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        assert(res === amount);
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.totalLockBoost.toNumber() === 0);
+        const stakerState = await getStakerState();
+        assert(stakerState.lockedAmount.toNumber() === 0);
+        assert(stakerState.lockBoost.toNumber() === 0);
     });
 
 })
\ No newline at end of file
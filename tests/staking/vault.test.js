@@ -0,0 +1,137 @@
+const utils = require('../../lib/utils');
+
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const splToken = require('@solana/spl-token');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+function sleep(ms) {
+    return new Promise((resolve) => {
+        setTimeout(resolve, ms)
+    })
+}
+
+
+describe('Vault tests', () => {
+    const homedir = os.homedir();
+    process.env.ANCHOR_WALLET = `${homedir}/.config/solana/id.json`;
+
+    // Configure the local cluster.
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    // Read the generated IDL.
+    const idl = JSON.parse(fs.readFileSync('./target/idl/staking.json', 'utf8'));
+
+    // Address of the deployed program.
+    const programId = new anchor.web3.PublicKey(idl.metadata.address);
+    const program = new anchor.Program(idl, programId);
+    const mintAuthority = anchor.web3.Keypair.generate();
+
+    let mint;
+    let shareMint;
+    let stakingInitializer;
+    let stakerInitializer;
+    let stakerAccount;
+    let shareAccount;
+    let stakingDataAccount;
+
+    let funderAuthority;
+    let funderAccount;
+    const minTimeframeInSecond = 30;
+    const minStakePeriod = 0; // shares redeem at once, the vault refuses a min stake period
+    const apyMax = lib.NO_APY_CAP;
+
+    beforeEach(async () => {
+        stakingInitializer = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, stakingInitializer.publicKey, 1000_000_000);
+        mint = await splToken.Token.createMint(
+            provider.connection,
+            stakingInitializer,
+            mintAuthority.publicKey,
+            null,
+            0,
+            splToken.TOKEN_PROGRAM_ID,
+        );
+
+        //init funder
+        funderAuthority = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, funderAuthority.publicKey, 1000_000_000);
+        funderAccount = await mint.createAccount(funderAuthority.publicKey);
+        await mint.mintTo(funderAccount, mintAuthority.publicKey, [mintAuthority], 100_000_000_000);
+
+        //init staker
+        stakerInitializer = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, stakerInitializer.publicKey, 1000_000_000);
+        stakerAccount = await mint.createAccount(stakerInitializer.publicKey);
+        await mint.mintTo(stakerAccount, mintAuthority.publicKey, [mintAuthority], 100_000_000_000);
+
+        //init staking and vault
+        stakingDataAccount = await lib.initialize(program, funderAuthority.publicKey, mint.publicKey, apyMax, minTimeframeInSecond, minStakePeriod, stakingInitializer);
+        const shareMintAccount = await lib.initVault(program, stakingDataAccount, stakingInitializer);
+        shareMint = new splToken.Token(provider.connection, shareMintAccount, splToken.TOKEN_PROGRAM_ID, stakingInitializer);
+        shareAccount = await shareMint.createAccount(stakerInitializer.publicKey);
+    });
+
+    it('Depositing mints shares one to one in an empty vault', async () => {
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.shareMint.toBase58() === shareMint.publicKey.toBase58());
+
+        const amount = 1000;
+        const res = await lib.deposit(program, stakingDataAccount, stakerAccount, shareAccount, amount, stakerInitializer);
+        assert(res === amount);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, shareAccount) === amount);
+
+        const stakingData1 = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData1.vaultStaked.toNumber() === amount);
+        assert(stakingData1.totalStaked.toNumber() === amount);
+    });
+
+    it('Funding raises the exchange rate', async () => {
+        const amount = 1000;
+        await lib.deposit(program, stakingDataAccount, stakerAccount, shareAccount, amount, stakerInitializer);
+        await lib.funding(program, stakingDataAccount, funderAccount, 1000, 30, funderAuthority);
+
+        console.log("waiting 32 seconds");
+        await sleep(32000);
+
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        const frameEnd = stakingData.timeframeStarted.toNumber() + stakingData.timeframeInSecond.toNumber();
+        const rate = utils.getVaultExchangeRate(stakingData, amount, frameEnd);
+        assert(rate.assets > amount);
+        assert(rate.shares === amount);
+
+        const res = await lib.compoundVault(program, stakingDataAccount);
+        assert(res === true);
+        const stakingData1 = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData1.vaultStaked.toNumber() === rate.assets);
+
+        //every share redeems its part of the compounded assets
+        const balance = await utils.getTokenAccountBalance(program.provider.connection, stakerAccount);
+        await lib.redeem(program, stakingDataAccount, stakerAccount, shareAccount, amount, stakerInitializer);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, stakerAccount) === balance + rate.assets);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, shareAccount) === 0);
+        const stakingData2 = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData2.vaultStaked.toNumber() === 0);
+        assert(stakingData2.totalStaked.toNumber() === 0);
+    });
+
+    it('Vault refuses holding stake back on exit', async () => {
+        let res = await lib.setUnbondingPeriod(program, stakingDataAccount, 10, stakingInitializer);
+        assert(res === 'vault can`t run with unbonding, penalty or min stake period');
+        res = await lib.setPenalty(program, stakingDataAccount, 500, 100, 0, anchor.web3.PublicKey.default, stakingInitializer);
+        assert(res === 'vault can`t run with unbonding, penalty or min stake period');
+        res = await lib.changeSetting(program, stakingDataAccount, apyMax, 30, 0, 0, stakingInitializer);
+        assert(res === 'vault can`t run with unbonding, penalty or min stake period');
+
+        //a pool with a min stake period can't start a vault
+        const poolInitializer = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, poolInitializer.publicKey, 1000_000_000);
+        const otherPool = await lib.initialize(program, funderAuthority.publicKey, mint.publicKey, apyMax, minTimeframeInSecond, 30, poolInitializer);
+        res = await lib.initVault(program, otherPool, poolInitializer);
+        assert(res === 'vault can`t run with unbonding, penalty or min stake period');
+    });
+})
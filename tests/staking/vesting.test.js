@@ -0,0 +1,167 @@
+const utils = require('../../lib/utils');
+
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const splToken = require('@solana/spl-token');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+function sleep(ms) {
+    return new Promise((resolve) => {
+        setTimeout(resolve, ms)
+    })
+}
+  
+
+describe('Vesting tests', () => {
+    const homedir = os.homedir();
+    process.env.ANCHOR_WALLET = `${homedir}/.config/solana/id.json`;
+
+    // Configure the local cluster.
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    // Read the generated IDL.
+    const idl = JSON.parse(fs.readFileSync('./target/idl/staking.json', 'utf8'));
+
+    // Address of the deployed program.
+    const programId = new anchor.web3.PublicKey(idl.metadata.address);
+    const program = new anchor.Program(idl, programId);
+    const mintAuthority = anchor.web3.Keypair.generate();
+
+    let mint;
+    let stakingInitializer;
+    let stakerInitializer;
+    let stakerAccount;
+    let stakingDataAccount;
+    let stakerStateAccount;
+    let vestingAccount;
+
+    let funderAuthority;
+    let funderAccount;
+    const minTimeframeInSecond = 30;
+    const minStakePeriod = 30;
+    const apyMax = lib.NO_APY_CAP;
+    const vestingDuration = 20;
+    const vestingCliff = 10;
+
+    beforeEach(async () => {
+        stakingInitializer = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, stakingInitializer.publicKey, 1000_000_000);
+        mint = await splToken.Token.createMint(
+            provider.connection,
+            stakingInitializer,
+            mintAuthority.publicKey,
+            null,
+            0,
+            splToken.TOKEN_PROGRAM_ID,
+        );
+
+        //init funder
+        funderAuthority = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, funderAuthority.publicKey, 1000_000_000);
+        funderAccount = await mint.createAccount(funderAuthority.publicKey);
+        await mint.mintTo(funderAccount, mintAuthority.publicKey, [mintAuthority], 100_000_000_000);
+
+        //init staker
+        stakerInitializer = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, stakerInitializer.publicKey, 1000_000_000);
+        stakerAccount = await mint.createAccount(stakerInitializer.publicKey);
+        await mint.mintTo(stakerAccount, mintAuthority.publicKey, [mintAuthority], 100_000_000_000);
+
+        //init staking with vesting
+        stakingDataAccount = await lib.initialize(program, funderAuthority.publicKey, mint.publicKey, apyMax, minTimeframeInSecond, minStakePeriod, stakingInitializer);
+        await lib.changeSetting(program, stakingDataAccount, apyMax, minStakePeriod, vestingDuration, vestingCliff, stakingInitializer);
+        stakerStateAccount = await lib.initializeStakeState(program, stakingDataAccount, stakerInitializer);
+        vestingAccount = await lib.initializeVesting(program, stakingDataAccount, stakerInitializer);
+    });
+
+    it('Vesting cliff can`t be longer than the duration', async () => {
+        const res = await lib.changeSetting(program, stakingDataAccount, apyMax, minStakePeriod, 10, 20, stakingInitializer);
+        assert(res === 'vesting cliff must less than vesting duration');
+
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        assert(stakingData.vestingDuration.toNumber() === vestingDuration);
+        assert(stakingData.vestingCliff.toNumber() === vestingCliff);
+    });
+
+    it('Claimed reward vests after the cliff', async () => {
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        await lib.funding(program, stakingDataAccount, funderAccount, 1000, 30, funderAuthority);
+        const stakingData = await utils.getStakingData(program, stakingDataAccount);
+        const stakerState = await utils.getStakingState(program, stakerStateAccount);
+
+        console.log("waiting 32 seconds");
+        await sleep(32000);
+
+        const frameEnd = stakingData.timeframeStarted.toNumber() + stakingData.timeframeInSecond.toNumber();
+        const gainedReward = utils.pendingReward(stakingData, stakerState, amount, stakerState.stakedTime.toNumber(), frameEnd);
+        assert(gainedReward > 0);
+
+        //the claim lands in the vesting account
+        const balance = await utils.getTokenAccountBalance(program.provider.connection, stakerAccount);
+        const res = await lib.claimReward(program, stakingDataAccount, stakerAccount, gainedReward, stakerInitializer);
+        assert(res === gainedReward);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, stakerAccount) === balance);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, vestingAccount) === gainedReward);
+
+        const stakerState1 = await utils.getStakingState(program, stakerStateAccount);
+        assert(stakerState1.vestingAmount.toNumber() === gainedReward);
+        assert(stakerState1.vestingEnd.toNumber() === stakerState1.vestingStart.toNumber() + vestingDuration);
+
+        //nothing before the cliff
+        const res1 = await lib.withdrawVested(program, stakingDataAccount, stakerAccount, stakerInitializer);
+        assert(res1 === 'nothing is vested');
+
+        console.log("waiting 22 seconds");
+        await sleep(22000);
+
+        const res2 = await lib.withdrawVested(program, stakingDataAccount, stakerAccount, stakerInitializer);
+        assert(res2 === true);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, stakerAccount) === balance + gainedReward);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, vestingAccount) === 0);
+    });
+
+    it('A claim partway through a schedule leaves the earlier one its end', async () => {
+        const amount = 1000;
+        await lib.staking(program, stakingDataAccount, stakerAccount, amount, stakerInitializer);
+        await lib.funding(program, stakingDataAccount, funderAccount, 1000, 30, funderAuthority);
+
+        console.log("waiting 32 seconds");
+        await sleep(32000);
+
+        const balance = await utils.getTokenAccountBalance(program.provider.connection, stakerAccount);
+        const firstClaim = 100;
+        assert(await lib.claimReward(program, stakingDataAccount, stakerAccount, firstClaim, stakerInitializer) === firstClaim);
+        const stakerState = await utils.getStakingState(program, stakerStateAccount);
+        const firstEnd = stakerState.vestingEnd.toNumber();
+
+        console.log("waiting 15 seconds");
+        await sleep(15000);
+
+        //the second claim vests on its own, the first keeps vesting until its end
+        const secondClaim = 100;
+        assert(await lib.claimReward(program, stakingDataAccount, stakerAccount, secondClaim, stakerInitializer) === secondClaim);
+        const stakerState1 = await utils.getStakingState(program, stakerStateAccount);
+        assert(stakerState1.vestingTranches.length === 1);
+        assert(stakerState1.vestingTranches[0].amount.toNumber() === firstClaim);
+        assert(stakerState1.vestingTranches[0].end.toNumber() === firstEnd);
+        assert(stakerState1.vestingAmount.toNumber() === secondClaim);
+
+        const nowTs = await utils.getNowTs(program.provider.connection);
+        console.log(`waiting ${firstEnd - nowTs + 1} seconds`);
+        await sleep((firstEnd - nowTs + 1) * 1000);
+
+        //the first claim is fully unlocked while the second is still before its cliff
+        assert(await utils.getNowTs(program.provider.connection) < stakerState1.vestingCliffEnd.toNumber());
+        const res = await lib.withdrawVested(program, stakingDataAccount, stakerAccount, stakerInitializer);
+        assert(res === true);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, stakerAccount) === balance + firstClaim);
+        const stakerState2 = await utils.getStakingState(program, stakerStateAccount);
+        assert(stakerState2.vestingTranches.length === 0);
+        assert(await utils.getTokenAccountBalance(program.provider.connection, vestingAccount) === secondClaim);
+    });
+})